@@ -0,0 +1,161 @@
+//! A benchmark measuring the throughput of the shared in-memory cache when it is read and written
+//! by many concurrent requests, which is the access pattern of the search route fetching the
+//! previous, current and next pages at once, along with the size and the speed of the formats in
+//! which the cached search results can be serialized.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use websurfx::{
+    cache::{
+        cache_key::CacheKey,
+        cacher::{InMemoryCache, SharedCache},
+        codec::{CodecPipeline, Serializer},
+    },
+    models::aggregation_models::{SearchResult, SearchResults},
+};
+
+/// The number of distinct queries stored in the cache.
+const QUERIES: usize = 64;
+
+/// The number of cache operations made by every concurrent task.
+const OPERATIONS_PER_TASK: usize = 100;
+
+/// Builds the search results stored for every query, made of ten results like a real page.
+fn search_results(query: usize) -> SearchResults {
+    SearchResults::new(
+        (0..10)
+            .map(|index| {
+                SearchResult::new(
+                    &format!("Result {index} for query {query}"),
+                    &format!("https://www.example.com/{query}/{index}"),
+                    "A description long enough to look like the snippet of a real search result.",
+                    &["bing", "brave"],
+                )
+            })
+            .collect(),
+        &[],
+    )
+}
+
+/// Builds the cache key of the given query.
+fn cache_key(query: usize) -> CacheKey {
+    CacheKey::new(&format!("query {query}"), 0, 0, ["bing", "brave"])
+}
+
+/// Measures the throughput of concurrent reads and of mixed reads and writes for an increasing
+/// number of concurrent tasks.
+fn parallel_cache_access(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let cache = Arc::new(SharedCache::new(InMemoryCache::new(
+        600,
+        None,
+        64 * 1024 * 1024,
+        CodecPipeline::with_codecs(false, false),
+    )));
+    let (results, keys): (Vec<SearchResults>, Vec<CacheKey>) = (0..QUERIES)
+        .map(|query| (search_results(query), cache_key(query)))
+        .unzip();
+    runtime
+        .block_on(cache.cache_results(&results, &keys))
+        .unwrap();
+    let entries = Arc::new((results, keys));
+
+    let mut group = c.benchmark_group("parallel_cache_access");
+    for tasks in [1, 4, 16, 64] {
+        group.throughput(Throughput::Elements((tasks * OPERATIONS_PER_TASK) as u64));
+
+        group.bench_with_input(BenchmarkId::new("reads", tasks), &tasks, |b, &tasks| {
+            b.iter(|| {
+                runtime.block_on(run_tasks(tasks, &cache, &entries, |_| false));
+            })
+        });
+
+        // Every tenth operation writes the results back, like the caching of a fresh search.
+        group.bench_with_input(
+            BenchmarkId::new("mixed_reads_and_writes", tasks),
+            &tasks,
+            |b, &tasks| {
+                b.iter(|| {
+                    runtime.block_on(run_tasks(tasks, &cache, &entries, |operation| {
+                        operation % 10 == 0
+                    }));
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Runs the given number of concurrent tasks, each making `OPERATIONS_PER_TASK` cache
+/// operations, and waits for all of them.
+async fn run_tasks(
+    tasks: usize,
+    cache: &Arc<SharedCache>,
+    entries: &Arc<(Vec<SearchResults>, Vec<CacheKey>)>,
+    is_write: fn(usize) -> bool,
+) {
+    let handles: Vec<_> = (0..tasks)
+        .map(|task| {
+            let (cache, entries) = (cache.clone(), entries.clone());
+            tokio::spawn(async move {
+                for operation in 0..OPERATIONS_PER_TASK {
+                    let index = (task * OPERATIONS_PER_TASK + operation) % QUERIES;
+                    let (results, keys) = (&entries.0[index..=index], &entries.1[index..=index]);
+                    if is_write(operation) {
+                        cache.cache_results(results, keys).await.unwrap();
+                    } else {
+                        cache.cached_results(&keys[0]).await.unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
+
+/// Measures the time taken to encode and decode a page of search results with every serializer,
+/// with and without compression, and prints the size of the encoded page.
+fn serialization_formats(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (results, key) = (search_results(0), cache_key(0));
+
+    // The serializers which were not compiled in are left out.
+    let serializers = ["json", "messagepack"]
+        .into_iter()
+        .filter_map(|name| Some((name, Serializer::parse(name)?)));
+
+    let mut group = c.benchmark_group("serialization_formats");
+    for (name, serializer) in serializers {
+        for compress in [false, true] {
+            let codec = CodecPipeline::with_codecs(compress, false).with_serializer(serializer);
+            let name = match codec.compresses() {
+                true => format!("{name}+brotli"),
+                false => name.to_owned(),
+            };
+            let encoded = runtime.block_on(codec.encode(&key, &results)).unwrap();
+            println!("{name}: {} bytes", encoded.len());
+
+            group.throughput(Throughput::Bytes(encoded.len() as u64));
+            group.bench_function(BenchmarkId::new("encode", &name), |b| {
+                b.iter(|| runtime.block_on(codec.encode(&key, &results)).unwrap())
+            });
+            group.bench_function(BenchmarkId::new("decode", &name), |b| {
+                b.iter(|| runtime.block_on(codec.decode(&encoded)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parallel_cache_access, serialization_formats);
+criterion_main!(benches);
@@ -0,0 +1,164 @@
+/**
+ * The length (in characters) of the serialized list of blocked sites above which the list is
+ * stored compressed in the preferences cookie, so that the cookie stays below the size limit of
+ * the browsers.
+ */
+const BLOCKED_SITES_COMPRESSION_THRESHOLD = 1024
+
+/**
+ * This function compresses the list of blocked sites into an unpadded base64 encoded and
+ * deflate compressed string of the newline separated sites.
+ *
+ * @function
+ * @param {string[]} sites - It takes the list of blocked sites.
+ * @returns {Promise<string>} The compressed list of blocked sites.
+ */
+async function compressBlockedSites(sites) {
+  let stream = new Blob([sites.join('\n')])
+    .stream()
+    .pipeThrough(new CompressionStream('deflate-raw'))
+  let bytes = new Uint8Array(await new Response(stream).arrayBuffer())
+  let binary = ''
+  bytes.forEach((byte) => (binary += String.fromCharCode(byte)))
+  return btoa(binary).replace(/=+$/, '')
+}
+
+/**
+ * This function decompresses the list of blocked sites compressed by the
+ * `compressBlockedSites` function.
+ *
+ * @function
+ * @param {string} compressed_sites - It takes the compressed list of blocked sites.
+ * @returns {Promise<string[]>} The list of blocked sites.
+ */
+async function decompressBlockedSites(compressed_sites) {
+  let bytes = Uint8Array.from(atob(compressed_sites), (char) =>
+    char.charCodeAt(0),
+  )
+  let stream = new Blob([bytes])
+    .stream()
+    .pipeThrough(new DecompressionStream('deflate-raw'))
+  let text = await new Response(stream).text()
+  return text.split('\n').filter((site) => site.length)
+}
+
+/**
+ * This function gets the list of blocked sites stored in the parsed preferences cookie, whether
+ * it was stored compressed or not.
+ *
+ * @function
+ * @param {Object} cookie_value - It takes the parsed preferences cookie.
+ * @returns {Promise<string[]>} The list of blocked sites.
+ */
+async function getBlockedSites(cookie_value) {
+  let sites = cookie_value['blocked_sites'] || []
+  if (cookie_value['compressed_blocked_sites']) {
+    sites = sites.concat(
+      await decompressBlockedSites(cookie_value['compressed_blocked_sites']),
+    )
+  }
+  return sites
+}
+
+/**
+ * This function stores the list of blocked sites in the preferences cookie dictionary,
+ * compressing it when it grows too large to be stored as is.
+ *
+ * @function
+ * @param {Object} cookie_dictionary - It takes the preferences cookie dictionary to be saved.
+ * @param {string[]} sites - It takes the list of blocked sites.
+ * @returns {Promise<void>}
+ */
+async function setBlockedSites(cookie_dictionary, sites) {
+  sites = [...new Set(sites)]
+  if (JSON.stringify(sites).length > BLOCKED_SITES_COMPRESSION_THRESHOLD) {
+    cookie_dictionary['blocked_sites'] = []
+    cookie_dictionary['compressed_blocked_sites'] =
+      await compressBlockedSites(sites)
+  } else {
+    cookie_dictionary['blocked_sites'] = sites
+    delete cookie_dictionary['compressed_blocked_sites']
+  }
+}
+
+/**
+ * This function gets the parsed preferences cookie if it is present on the user's machine.
+ *
+ * @function
+ * @returns {Object|null} The parsed preferences cookie or null if it is not present.
+ */
+function getAppCookie() {
+  let cookie = decodeURIComponent(document.cookie)
+    .split(';')
+    .map((item) => item.trim())
+    .find((item) => item.startsWith('appCookie='))
+  return cookie ? JSON.parse(cookie.slice('appCookie='.length)) : null
+}
+
+/**
+ * This function saves the preferences cookie dictionary in a cookie on the user's machine which
+ * expires in a year.
+ *
+ * @function
+ * @param {Object} cookie_dictionary - It takes the preferences cookie dictionary to be saved.
+ * @returns {void}
+ */
+function saveAppCookie(cookie_dictionary) {
+  let expiration_date = new Date()
+  expiration_date.setFullYear(expiration_date.getFullYear() + 1)
+
+  document.cookie = `appCookie=${JSON.stringify(
+    cookie_dictionary,
+  )}; expires=${expiration_date.toUTCString()}`
+}
+
+/**
+ * This function builds the preferences cookie dictionary from the default settings of the
+ * server, which are read from the settings page, for the users who have not saved any
+ * preferences yet.
+ *
+ * @function
+ * @returns {Promise<Object>} The preferences cookie dictionary with the default settings.
+ */
+async function getDefaultAppCookie() {
+  let settings_page = new DOMParser().parseFromString(
+    await (await fetch('/settings')).text(),
+    'text/html',
+  )
+  let value = (name) =>
+    settings_page.querySelector(`select[name="${name}"]`)?.value
+
+  return {
+    theme: value('themes'),
+    colorscheme: value('colorschemes'),
+    animation: value('animations') || null,
+    safe_search_level: Number(value('safe_search_levels')),
+    engines: Array.from(settings_page.querySelectorAll('.engine'))
+      .filter((engine_checkbox) => engine_checkbox.checked)
+      .map((engine_checkbox) =>
+        engine_checkbox.parentNode.parentNode.textContent.trim(),
+      ),
+  }
+}
+
+/**
+ * This function blocks the site of a search result by adding it to the blocked sites stored in
+ * the preferences cookie and hides all the results of the site from the current page.
+ *
+ * @function
+ * @param {HTMLElement} button - It takes the `block this site` button of the search result.
+ * @returns {Promise<void>}
+ */
+async function blockSite(button) {
+  let site = button.dataset.site
+  let cookie_dictionary = getAppCookie() || (await getDefaultAppCookie())
+
+  let sites = await getBlockedSites(cookie_dictionary)
+  sites.push(site)
+  await setBlockedSites(cookie_dictionary, sites)
+  saveAppCookie(cookie_dictionary)
+
+  document
+    .querySelectorAll(`.block_site[data-site="${CSS.escape(site)}"]`)
+    .forEach((block_button) => block_button.closest('.result').remove())
+}
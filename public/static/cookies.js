@@ -32,6 +32,27 @@ function setClientSettingsOnPage(cookie) {
         break
     }
   })
+  document.querySelector('textarea[name="domain_rules"]').value = (
+    cookie_value['domain_rules'] || []
+  ).join('\n')
+
+  getBlockedSites(cookie_value)
+    .then((sites) => {
+      document.querySelector('textarea[name="blocked_sites"]').value =
+        sites.join('\n')
+    })
+    .catch((error) => console.error('Error decoding blocked sites:', error))
+
+  let disabled_hostname_replacements =
+    cookie_value['disabled_hostname_replacements'] || []
+  document
+    .querySelectorAll('.hostname_replacement')
+    .forEach((replacement_checkbox) => {
+      replacement_checkbox.checked = !disabled_hostname_replacements.includes(
+        replacement_checkbox.value,
+      )
+    })
+
   let engines = document.querySelectorAll('.engine')
   let engines_cookie = cookie_value['engines']
 
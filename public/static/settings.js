@@ -37,7 +37,7 @@ function setActiveTab(current_tab) {
  * This function adds the functionality to save all the user selected preferences
  * to be saved in a cookie on the users machine.
  */
-function setClientSettings() {
+async function setClientSettings() {
   // Create an object to store the user's preferences
   let cookie_dictionary = new Object()
 
@@ -70,6 +70,32 @@ function setClientSettings() {
 
   cookie_dictionary['engines'] = engines
 
+  // Loop through all hostname replacement checkboxes and add the disabled ones to the cookie
+  // dictionary
+  cookie_dictionary['disabled_hostname_replacements'] = Array.from(
+    document.querySelectorAll('.hostname_replacement'),
+  )
+    .filter((replacement_checkbox) => !replacement_checkbox.checked)
+    .map((replacement_checkbox) => replacement_checkbox.value)
+
+  // Add the user provided domain rules (one rule per line) to the cookie dictionary
+  cookie_dictionary['domain_rules'] = document
+    .querySelector('textarea[name="domain_rules"]')
+    .value.split('\n')
+    .map((rule) => rule.trim())
+    .filter((rule) => rule.length && !rule.startsWith('#'))
+
+  // Add the user blocked sites (one site per line) to the cookie dictionary, compressed if the
+  // list is large
+  await setBlockedSites(
+    cookie_dictionary,
+    document
+      .querySelector('textarea[name="blocked_sites"]')
+      .value.split('\n')
+      .map((site) => site.trim())
+      .filter((site) => site.length && !site.startsWith('#')),
+  )
+
   // Set the expiration date for the cookie to 1 year from the current date
   let expiration_date = new Date()
   expiration_date.setFullYear(expiration_date.getFullYear() + 1)
@@ -2,11 +2,24 @@
 //!
 //! This module contains the main function which handles the logging of the application to the
 //! stdout and handles the command line arguments provided and launches the `websurfx` server.
+//!
+//! The cache of a running server can be administered with the `cache stats`, `cache clear` and
+//! `cache invalidate <query prefix>` subcommands, which call its admin routes.
+//!
+//! The config of a running server is reloaded when it receives the `SIGHUP` signal.
 #[cfg(not(feature = "dhat-heap"))]
 use mimalloc::MiMalloc;
 
-use std::{net::TcpListener, sync::OnceLock};
-use websurfx::{cache::cacher::create_cache, config::parser::Config, run};
+use std::{
+    io::{Error, ErrorKind},
+    net::TcpListener,
+    sync::OnceLock,
+};
+use websurfx::{
+    cache::cacher::create_cache,
+    config::{parser::Config, reload::SharedConfig},
+    run,
+};
 
 /// A dhat heap memory profiler
 #[cfg(feature = "dhat-heap")]
@@ -17,8 +30,8 @@ static ALLOC: dhat::Alloc = dhat::Alloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-/// A static constant for holding the parsed config.
-static CONFIG: OnceLock<Config> = OnceLock::new();
+/// A static constant for holding the parsed config, which is swapped when the config is reloaded.
+static CONFIG: OnceLock<SharedConfig> = OnceLock::new();
 
 /// The function that launches the main server and registers all the routes of the website.
 ///
@@ -32,10 +45,18 @@ async fn main() -> std::io::Result<()> {
     #[cfg(feature = "dhat-heap")]
     let _profiler = dhat::Profiler::new_heap();
 
-    // Initialize the parsed config globally.
-    let config = CONFIG.get_or_init(|| Config::parse(false).unwrap());
+    let config = Config::parse(false).unwrap();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(error) = cache_command(&config, &args).await {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let cache = create_cache(config).await;
+    let cache = create_cache(&config).await;
 
     log::info!(
         "started server on port {} and IP {}",
@@ -50,5 +71,70 @@ async fn main() -> std::io::Result<()> {
 
     let listener = TcpListener::bind((config.binding_ip.as_str(), config.port))?;
 
+    // Initialize the parsed config globally.
+    let shared_config = SharedConfig::new(config).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Failed to load the filter lists: {error}"),
+        )
+    })?;
+    let config = CONFIG.get_or_init(|| shared_config);
+
     run(listener, config, cache)?.await
 }
+
+/// Runs the cache administration subcommand against the admin routes of the running server and
+/// prints the response.
+///
+/// # Arguments
+///
+/// * `config` - It takes the parsed config as an argument.
+/// * `args` - It takes the command line arguments as an argument.
+///
+/// # Error
+///
+/// Returns an error if the subcommand is unknown, if no admin token is configured or if the
+/// server cannot be reached or rejects the request.
+async fn cache_command(config: &Config, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let admin_token = config
+        .admin_token
+        .as_deref()
+        .ok_or("The `admin_token` option should be set to administer the cache")?;
+    // The server listening on every interface is reached through the loopback interface.
+    let host = match config.binding_ip.as_str() {
+        "0.0.0.0" => "127.0.0.1",
+        "::" => "::1",
+        binding_ip => binding_ip,
+    };
+    // The IPv6 addresses are bracketed in urls, with the `%` of their zone percent encoded.
+    let host = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host.replace('%', "%25"))
+    } else {
+        host.to_owned()
+    };
+    let url = format!("http://{host}:{}/admin/cache", config.port);
+
+    let client = reqwest::Client::new();
+    let request = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["cache", "stats"] => client.get(format!("{url}/stats")),
+        ["cache", "clear"] => client.post(format!("{url}/clear")),
+        ["cache", "invalidate", prefix] => client
+            .post(format!("{url}/invalidate"))
+            .query(&[("prefix", prefix)]),
+        _ => {
+            return Err(
+                "Usage: websurfx [cache stats | cache clear | cache invalidate <query prefix>]"
+                    .into(),
+            )
+        }
+    };
+
+    let response = request.bearer_auth(admin_token).send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(format!("The server responded with {status}: {body}").into());
+    }
+    println!("{body}");
+    Ok(())
+}
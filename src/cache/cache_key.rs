@@ -0,0 +1,327 @@
+//! This module provides the key under which the aggregated search results are stored in the
+//! cache. The key is built from the normalized search parameters and user preferences which
+//! affect the results, so that it does not depend on the address the server is bound to or on
+//! the order in which the engines were selected.
+
+use std::{collections::BTreeSet, fmt};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::models::parser_models::Style;
+
+/// The version of the layout used to hash the cache keys, which is bumped whenever a field is
+/// added or its encoding changes so that the entries cached with the older layout are ignored.
+const CACHE_KEY_VERSION: u8 = 5;
+
+/// A named struct which stores the normalized parameters identifying a page of search results
+/// in the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// It stores the NFC normalized and lowercased search query with its whitespace collapsed.
+    query: String,
+    /// It stores the page number (starting from 0) of the search results.
+    page: u32,
+    /// It stores the safe search level of the search.
+    safe_search_level: u8,
+    /// It stores the names of the selected upstream search engines in sorted order.
+    engines: BTreeSet<String>,
+    /// It stores the hash of the contents of the filter lists the results were filtered with.
+    filter_list_hash: [u8; 32],
+    /// It stores the hash of the options of the server config which affect the results.
+    config_hash: [u8; 32],
+    /// It stores the user provided domain rules in the order in which they are applied.
+    domain_rules: Vec<String>,
+    /// It stores the original hostnames whose rewriting was disabled by the user in sorted
+    /// order.
+    disabled_hostname_replacements: BTreeSet<String>,
+    /// It stores the host patterns of the sites blocked by the user in sorted order.
+    blocked_sites: BTreeSet<String>,
+    /// It stores whether the key identifies the raw results of a single upstream search engine
+    /// rather than the aggregated search results.
+    engine_results: bool,
+}
+
+impl CacheKey {
+    /// Constructs a new `CacheKey` with the given search parameters and no user preferences.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - It takes the user's search query as an argument.
+    /// * `page` - It takes the page number (starting from 0) as an argument.
+    /// * `safe_search_level` - It takes the safe search level of the search as an argument.
+    /// * `engines` - It takes the names of the selected upstream search engines as an argument.
+    pub fn new<S: AsRef<str>>(
+        query: &str,
+        page: u32,
+        safe_search_level: u8,
+        engines: impl IntoIterator<Item = S>,
+    ) -> Self {
+        CacheKey {
+            query: normalize_query(query),
+            page,
+            safe_search_level,
+            engines: engines
+                .into_iter()
+                .map(|engine| engine.as_ref().to_lowercase())
+                .collect(),
+            filter_list_hash: [0; 32],
+            config_hash: [0; 32],
+            domain_rules: Vec::new(),
+            disabled_hostname_replacements: BTreeSet::new(),
+            blocked_sites: BTreeSet::new(),
+            engine_results: false,
+        }
+    }
+
+    /// Constructs a new `CacheKey` identifying the raw results of a single upstream search
+    /// engine, which are shared by every search using the engine whatever the other selected
+    /// engines and the user preferences are.
+    ///
+    /// # Arguments
+    ///
+    /// * `engine` - It takes the name of the upstream search engine as an argument.
+    /// * `query` - It takes the user's search query as an argument.
+    /// * `page` - It takes the page number (starting from 0) as an argument.
+    /// * `safe_search_level` - It takes the safe search level sent to the engine as an argument.
+    pub fn for_engine(engine: &str, query: &str, page: u32, safe_search_level: u8) -> Self {
+        CacheKey {
+            engine_results: true,
+            ..CacheKey::new(query, page, safe_search_level, [engine])
+        }
+    }
+
+    /// Sets the hash of the contents of the filter lists, so that the cached results are
+    /// refreshed once the filter lists change.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the content hash of the filter lists as an argument.
+    pub fn with_filter_list_hash(mut self, hash: blake3::Hash) -> Self {
+        self.filter_list_hash = hash.into();
+        self
+    }
+
+    /// Sets the hash of the options of the server config which affect the search results, so
+    /// that the cached results are refreshed once the config is reloaded with other options.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the results hash of the config as an argument.
+    pub fn with_config_hash(mut self, hash: blake3::Hash) -> Self {
+        self.config_hash = hash.into();
+        self
+    }
+
+    /// Sets the user preferences which affect the search results.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain_rules` - It takes the user provided domain rules as an argument.
+    /// * `disabled_hostname_replacements` - It takes the original hostnames whose rewriting was
+    /// disabled by the user as an argument.
+    /// * `blocked_sites` - It takes the host patterns of the sites blocked by the user as an
+    /// argument.
+    pub fn with_user_preferences<R: AsRef<str>, H: AsRef<str>, B: AsRef<str>>(
+        mut self,
+        domain_rules: impl IntoIterator<Item = R>,
+        disabled_hostname_replacements: impl IntoIterator<Item = H>,
+        blocked_sites: impl IntoIterator<Item = B>,
+    ) -> Self {
+        self.domain_rules = domain_rules
+            .into_iter()
+            .map(|rule| rule.as_ref().trim().to_owned())
+            .filter(|rule| !rule.is_empty())
+            .collect();
+        self.disabled_hostname_replacements = disabled_hostname_replacements
+            .into_iter()
+            .map(|hostname| hostname.as_ref().to_lowercase())
+            .collect();
+        self.blocked_sites = blocked_sites
+            .into_iter()
+            .map(|site| site.as_ref().to_owned())
+            .collect();
+        self
+    }
+
+    /// A getter function that gets the normalized search query.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// A getter function that gets the page number of the search results.
+    pub fn page(&self) -> u32 {
+        self.page
+    }
+
+    /// Computes the stable hash of the key which is used as the key of the cache backends. The
+    /// fields are length prefixed before being hashed, so that two different keys can never be
+    /// encoded the same way, and the hash does not change between runs or builds.
+    ///
+    /// # Returns
+    ///
+    /// Returns the hex encoded blake3 hash of the key.
+    pub fn hash(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[CACHE_KEY_VERSION]);
+        update_str(&mut hasher, &self.query);
+        hasher.update(&self.page.to_le_bytes());
+        hasher.update(&[self.safe_search_level]);
+        update_strs(&mut hasher, &self.engines);
+        hasher.update(&self.filter_list_hash);
+        hasher.update(&self.config_hash);
+        update_strs(&mut hasher, &self.domain_rules);
+        update_strs(&mut hasher, &self.disabled_hostname_replacements);
+        update_strs(&mut hasher, &self.blocked_sites);
+        hasher.update(&[u8::from(self.engine_results)]);
+        hasher.finalize().to_string()
+    }
+
+    /// Computes the hash under which the page rendered from the search results stored under the
+    /// key is cached. The page also depends on the time the search results were cached at, so
+    /// that refreshed results are rendered again, on the style of the website and on the search
+    /// query as typed by the user, which is shown on the page.
+    ///
+    /// # Arguments
+    ///
+    /// * `cached_at` - It takes the time at which the search results were cached as an argument.
+    /// * `style` - It takes the style of the website as an argument.
+    /// * `query` - It takes the user's search query as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the hex encoded blake3 hash of the rendered page key.
+    pub fn page_hash(&self, cached_at: u64, style: &Style, query: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        update_str(&mut hasher, &self.hash());
+        hasher.update(&cached_at.to_le_bytes());
+        update_str(&mut hasher, &style.theme);
+        update_str(&mut hasher, &style.colorscheme);
+        update_str(&mut hasher, style.animation.as_deref().unwrap_or_default());
+        update_str(&mut hasher, query);
+        hasher.finalize().to_string()
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "q={:?} page={} safesearch={} engines={}",
+            self.query,
+            self.page,
+            self.safe_search_level,
+            self.engines
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(",")
+        )
+    }
+}
+
+/// A helper function which normalizes the search query, so that queries only differing in
+/// their case, unicode composition or whitespace share the same cache entry.
+///
+/// # Arguments
+///
+/// * `query` - It takes the user's search query as an argument.
+pub(super) fn normalize_query(query: &str) -> String {
+    query
+        .nfc()
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// A helper function which feeds a length prefixed string to the hasher.
+///
+/// # Arguments
+///
+/// * `hasher` - It takes the hasher of the cache key as an argument.
+/// * `value` - It takes the string to be hashed as an argument.
+fn update_str(hasher: &mut blake3::Hasher, value: &str) {
+    hasher.update(&(value.len() as u64).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+/// A helper function which feeds a length prefixed list of strings to the hasher.
+///
+/// # Arguments
+///
+/// * `hasher` - It takes the hasher of the cache key as an argument.
+/// * `values` - It takes the strings to be hashed as an argument.
+fn update_strs<'a>(hasher: &mut blake3::Hasher, values: impl IntoIterator<Item = &'a String>) {
+    let values: Vec<&String> = values.into_iter().collect();
+    hasher.update(&(values.len() as u64).to_le_bytes());
+    for value in values {
+        update_str(hasher, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized_parameters_share_the_key() {
+        let key = CacheKey::new("Rust  Programming ", 0, 2, ["bing", "duckduckgo"]);
+        let same = CacheKey::new(" rust programming", 0, 2, ["duckduckgo", "Bing"]);
+
+        assert_eq!(key, same);
+        assert_eq!(key.hash(), same.hash());
+        assert_eq!(key.query(), "rust programming");
+    }
+
+    #[test]
+    fn test_different_parameters_change_the_key() {
+        let key = CacheKey::new("rust", 0, 2, ["bing"]);
+        let others = [
+            CacheKey::new("rust", 1, 2, ["bing"]),
+            CacheKey::new("rust", 0, 3, ["bing"]),
+            CacheKey::new("rust", 0, 2, ["bing", "brave"]),
+            key.clone()
+                .with_filter_list_hash(blake3::hash(b"filter lists")),
+            key.clone().with_config_hash(blake3::hash(b"config")),
+            key.clone()
+                .with_user_preferences(["boost 2 docs.rs"], [""; 0], [""; 0]),
+            key.clone()
+                .with_user_preferences([""; 0], ["www.youtube.com"], [""; 0]),
+            key.clone()
+                .with_user_preferences([""; 0], [""; 0], ["*.pinterest.com"]),
+            CacheKey::for_engine("bing", "rust", 0, 2),
+        ];
+
+        for other in others {
+            assert_ne!(key.hash(), other.hash(), "{other:?}");
+        }
+    }
+
+    #[test]
+    fn test_fields_cannot_be_shifted_into_each_other() {
+        let key = CacheKey::new("a", 0, 0, ["b,c"]);
+        let other = CacheKey::new("a", 0, 0, ["b", "c"]);
+
+        assert_ne!(key.hash(), other.hash());
+    }
+
+    #[test]
+    fn test_page_hash_depends_on_the_style_and_the_cache_time() {
+        let key = CacheKey::new("rust", 0, 2, ["bing"]);
+        let style = Style::new("simple".to_owned(), "catppuccin-mocha".to_owned(), None);
+        let page = key.page_hash(1, &style, "rust");
+
+        assert_eq!(page, key.page_hash(1, &style, "rust"));
+        assert_ne!(page, key.page_hash(2, &style, "rust"));
+        assert_ne!(page, key.page_hash(1, &style, "Rust"));
+        let dark = Style::new("simple".to_owned(), "monokai".to_owned(), None);
+        assert_ne!(page, key.page_hash(1, &dark, "rust"));
+        let animated = Style::new(
+            "simple".to_owned(),
+            "catppuccin-mocha".to_owned(),
+            Some("simple-frosted-glow".to_owned()),
+        );
+        assert_ne!(page, key.page_hash(1, &animated, "rust"));
+    }
+}
@@ -3,22 +3,60 @@
 
 use error_stack::Report;
 #[cfg(feature = "memory-cache")]
-use mini_moka::sync::Cache as MokaCache;
-#[cfg(feature = "memory-cache")]
-use mini_moka::sync::ConcurrentCacheExt;
+use moka::{notification::RemovalCause, sync::Cache as MokaCache};
+use serde::Serialize;
 
+#[cfg(any(feature = "memory-cache", feature = "redis-cache"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+use std::sync::{atomic::AtomicBool, RwLock};
 #[cfg(feature = "memory-cache")]
 use std::time::Duration;
-use tokio::sync::Mutex;
-
-use crate::{config::parser::Config, models::aggregation_models::SearchResults};
-
-use super::error::CacheError;
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{
+    config::parser::Config,
+    models::aggregation_models::{SearchResult, SearchResults},
+};
+
+#[cfg(feature = "disk-cache")]
+use super::disk_cacher::DiskCache;
+#[cfg(feature = "memory-cache")]
+use super::page_cache::PageCache;
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+use super::redis_cacher::is_connection_lost;
 #[cfg(feature = "redis-cache")]
-use super::redis_cacher::RedisCache;
-
-#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
-use super::encryption::*;
+use super::redis_cacher::{KeyScan, RedisCache, RedisOptions};
+use super::{
+    cache_key::{normalize_query, CacheKey},
+    codec::{unix_time, CodecPipeline},
+    error::CacheError,
+    page_cache::RenderedPage,
+    prefetch::PrefetchTracker,
+    single_flight::SingleFlight,
+};
+
+/// A named struct which stores the statistics of a cache backend, the statistics which the
+/// backend does not track being left empty.
+#[derive(Serialize, Default, Debug)]
+pub struct CacheStats {
+    /// It stores the name of the cache backend.
+    pub backend: &'static str,
+    /// It stores the number of entries stored in the cache.
+    pub entries: Option<u64>,
+    /// It stores the total size (in bytes) of the entries stored in the cache.
+    pub size: Option<u64>,
+    /// It stores the number of lookups which found the search results in the cache.
+    pub hits: Option<u64>,
+    /// It stores the number of lookups which did not find the search results in the cache.
+    pub misses: Option<u64>,
+    /// It stores the number of entries removed from the cache to keep it within its size limit.
+    pub evictions: Option<u64>,
+}
 
 /// Abstraction trait for common methods provided by a cache backend.
 #[async_trait::async_trait]
@@ -36,25 +74,25 @@ pub trait Cacher: Send + Sync {
     where
         Self: Sized;
 
-    /// A function which fetches the cached json results as json string.
+    /// A function which fetches the cached results stored under the given key.
     ///
     /// # Arguments
     ///
-    /// * `url` - It takes an url as a string.
+    /// * `key` - It takes the cache key of the search results as an argument.
     ///
     /// # Error
     ///
     /// Returns the `SearchResults` from the cache if the program executes normally otherwise
     /// returns a `CacheError` if the results cannot be retrieved from the cache.
-    async fn cached_results(&mut self, url: &str) -> Result<SearchResults, Report<CacheError>>;
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>>;
 
-    /// A function which caches the results by using the `url` as the key and
+    /// A function which caches the results by using the `key` as the key and
     /// `json results` as the value and stores it in the cache
     ///
     /// # Arguments
     ///
-    /// * `json_results` - It takes the json results string as an argument.
-    /// * `url` - It takes the url as a String.
+    /// * `search_results` - It takes the search results to be cached as an argument.
+    /// * `keys` - It takes the cache keys of the search results as an argument.
     ///
     /// # Error
     ///
@@ -62,424 +100,650 @@ pub trait Cacher: Send + Sync {
     /// otherwise it returns a `CacheError` if the search results cannot be cached due to a
     /// failure.
     async fn cache_results(
-        &mut self,
+        &self,
         search_results: &[SearchResults],
-        urls: &[String],
+        keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>>;
 
-    /// A helper function which computes the hash of the url and formats and returns it as string.
+    /// A function which fetches the statistics of the cache backend, with one entry for every
+    /// tier of the cache.
     ///
-    /// # Arguments
+    /// # Error
     ///
-    /// * `url` - It takes an url as string.
-    fn hash_url(&self, url: &str) -> String {
-        blake3::hash(url.as_bytes()).to_string()
-    }
+    /// Returns the statistics of the cache backend if the program executes normally otherwise
+    /// returns a `CacheError` if they cannot be retrieved from the cache.
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>>;
 
-    /// A helper function that returns  either encrypted or decrypted results.
-    ///  Feature flags (**encrypt-cache-results or cec-cache-results**) are required  for this to work.
+    /// A function which removes the cached results whose normalized query starts with the given
+    /// prefix. The entries which cannot be decoded are left untouched.
     ///
     /// # Arguments
     ///
-    /// * `bytes` - It takes a slice of bytes as an argument.
-    /// * `encrypt` - A boolean to choose whether to encrypt or decrypt the bytes
-
+    /// * `query_prefix` - It takes the normalized prefix of the queries to be removed as an
+    /// argument.
     ///
     /// # Error
-    /// Returns  either encrypted or decrypted bytes on success otherwise it returns a CacheError
-    /// on failure.
-    #[cfg(any(
-      //  feature = "compress-cache-results",
-        feature = "encrypt-cache-results",
-        feature = "cec-cache-results"
-    ))]
-    async fn encrypt_or_decrypt_results(
-        &mut self,
-        mut bytes: Vec<u8>,
-        encrypt: bool,
-    ) -> Result<Vec<u8>, Report<CacheError>> {
-        use chacha20poly1305::{
-            aead::{Aead, AeadCore, KeyInit, OsRng},
-            ChaCha20Poly1305,
-        };
+    ///
+    /// Returns the number of removed entries if the program executes normally otherwise returns
+    /// a `CacheError` if the entries cannot be removed from the cache.
+    async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>>;
 
-        let cipher = CIPHER.get_or_init(|| {
-            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
-            ChaCha20Poly1305::new(&key)
-        });
+    /// A function which removes all the cached results, including the ones which cannot be
+    /// decoded.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of removed entries if the program executes normally otherwise returns
+    /// a `CacheError` if the entries cannot be removed from the cache.
+    async fn clear(&self) -> Result<u64, Report<CacheError>>;
+}
 
-        let encryption_key = ENCRYPTION_KEY.get_or_init(
-            || ChaCha20Poly1305::generate_nonce(&mut OsRng), // 96-bits; unique per message
+#[cfg(feature = "redis-cache")]
+#[async_trait::async_trait]
+impl Cacher for RedisCache {
+    async fn build(config: &Config) -> Self {
+        log::info!(
+            "Initialising redis cache. Listening to {}",
+            &config.redis_url
         );
+        RedisCache::new(
+            &RedisOptions::new(config),
+            config.cache_expiry_time,
+            CodecPipeline::new(config).expect("Cache encryption keys validated by the config"),
+        )
+        .await
+        .expect("Redis cache configured")
+    }
 
-        bytes = if encrypt {
-            cipher
-                .encrypt(encryption_key, bytes.as_ref())
-                .map_err(|_| CacheError::EncryptionError)?
-        } else {
-            cipher
-                .decrypt(encryption_key, bytes.as_ref())
-                .map_err(|_| CacheError::EncryptionError)?
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
+        let bytes = match self.cached_entry(&key.hash()).await {
+            Ok(bytes) => {
+                self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+                bytes
+            }
+            Err(error) => {
+                self.metrics.misses.fetch_add(1, Ordering::Relaxed);
+                return Err(error);
+            }
         };
-
-        Ok(bytes)
+        self.codec.decode(&bytes).await
     }
 
-    /// A helper function that returns compressed results.
-    /// Feature flags (**compress-cache-results or cec-cache-results**) are required  for this to work.
-    ///
-    /// # Arguments
-    ///
-    /// * `bytes` - It takes a slice of bytes as an argument.
+    async fn cache_results(
+        &self,
+        search_results: &[SearchResults],
+        keys: &[CacheKey],
+    ) -> Result<(), Report<CacheError>> {
+        // size of search_results is expected to be equal to size of keys -> key/value pairs  for cache;
+        let search_results_len = search_results.len();
 
-    ///
-    /// # Error
-    /// Returns the compressed bytes on success otherwise it returns a CacheError
-    /// on failure.
-    #[cfg(any(feature = "compress-cache-results", feature = "cec-cache-results"))]
-    async fn compress_results(
-        &mut self,
-        mut bytes: Vec<u8>,
-    ) -> Result<Vec<u8>, Report<CacheError>> {
-        use tokio::io::AsyncWriteExt;
-        let mut writer = async_compression::tokio::write::BrotliEncoder::new(Vec::new());
-        writer
-            .write_all(&bytes)
-            .await
-            .map_err(|_| CacheError::CompressionError)?;
-        writer
-            .shutdown()
+        let mut bytes = Vec::with_capacity(search_results_len);
+
+        for (key, result) in keys.iter().zip(search_results) {
+            let encoded = self.codec.encode(key, result).await?;
+            bytes.push(encoded);
+        }
+
+        self.cache_entries(bytes.into_iter(), keys.iter().map(CacheKey::hash))
             .await
-            .map_err(|_| CacheError::CompressionError)?;
-        bytes = writer.into_inner();
-        Ok(bytes)
     }
 
-    /// A helper function that returns compressed-encrypted results.
-    /// Feature flag (**cec-cache-results**) is required  for this to work.
-    ///
-    /// # Arguments
-    ///
-    /// * `bytes` - It takes a slice of bytes as an argument.
-
-    ///
-    /// # Error
-    /// Returns the compressed and encrypted bytes on success otherwise it returns a CacheError
-    /// on failure.
-    #[cfg(feature = "cec-cache-results")]
-    async fn compress_encrypt_compress_results(
-        &mut self,
-        mut bytes: Vec<u8>,
-    ) -> Result<Vec<u8>, Report<CacheError>> {
-        // compress first
-        bytes = self.compress_results(bytes).await?;
-        // encrypt
-        bytes = self.encrypt_or_decrypt_results(bytes, true).await?;
-
-        // compress again;
-        bytes = self.compress_results(bytes).await?;
-
-        Ok(bytes)
-    }
-
-    /// A helper function that returns compressed results.
-    /// Feature flags (**compress-cache-results or cec-cache-results**) are required  for this to work.
-    /// If bytes where
-    /// # Arguments
-    ///
-    /// * `bytes` - It takes a slice of bytes as an argument.
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        Ok(vec![CacheStats {
+            backend: "redis",
+            entries: Some(self.key_count().await?),
+            size: None,
+            hits: Some(self.metrics.hits()),
+            misses: Some(self.metrics.misses()),
+            evictions: None,
+        }])
+    }
 
-    ///
-    /// # Error
-    /// Returns the uncompressed bytes on success otherwise it returns a CacheError
-    /// on failure.
+    async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        let mut deleted = 0;
+        let mut scan = KeyScan::default();
+        while let Some(keys) = self.scan_keys(&mut scan).await? {
+            let entries = self.cached_entries(&keys).await?;
+
+            let mut matching_keys = Vec::new();
+            for (key, entry) in keys.into_iter().zip(entries) {
+                let Some(bytes) = entry else {
+                    continue;
+                };
+                if let Ok(query) = self.codec.decode_query(&bytes).await {
+                    if query.starts_with(query_prefix) {
+                        matching_keys.push(key);
+                    }
+                }
+            }
+            deleted += self.delete_keys(&matching_keys).await?;
+        }
+        Ok(deleted)
+    }
 
-    #[cfg(any(feature = "compress-cache-results", feature = "cec-cache-results"))]
-    async fn decompress_results(&mut self, bytes: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
-        cfg_if::cfg_if! {
-             if #[cfg(feature = "compress-cache-results")]
-            {
-               decompress_util(bytes).await
+    async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        // The keys are removed by their prefix without being decoded, so that the entries
+        // written with other codecs or keys are removed as well.
+        let mut deleted = 0;
+        let mut scan = KeyScan::default();
+        while let Some(keys) = self.scan_keys(&mut scan).await? {
+            deleted += self.delete_keys(&keys).await?;
+        }
+        Ok(deleted)
+    }
+}
+/// A named struct which stores the counters of a cache backend, which are shared with the
+/// eviction listener of the in-memory cache.
+#[cfg(any(feature = "memory-cache", feature = "redis-cache"))]
+#[derive(Default)]
+pub struct CacheMetrics {
+    /// It stores the number of lookups which found the search results in the cache.
+    hits: AtomicU64,
+    /// It stores the number of lookups which did not find the search results in the cache.
+    misses: AtomicU64,
+    /// It stores the number of entries removed from the cache to keep it within its size limit.
+    evictions: AtomicU64,
+}
 
-            }
-            else if  #[cfg(feature = "cec-cache-results")]
-            {
-                let decompressed = decompress_util(bytes)?;
-                let decrypted = self.encrypt_or_decrypt_results(decompressed, false)?;
+#[cfg(any(feature = "memory-cache", feature = "redis-cache"))]
+impl CacheMetrics {
+    /// A getter function that gets the number of cache hits.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
 
-                decompress_util(&decrypted).await
+    /// A getter function that gets the number of cache misses.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
 
-            }
-        }
+    /// A getter function that gets the number of entries evicted because of the size limit.
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
     }
+}
 
-    /// A helper function that compresses or encrypts search results before they're inserted into a cache store
+/// Memory based cache backend.
+#[cfg(feature = "memory-cache")]
+pub struct InMemoryCache {
+    /// The backend cache which stores data.
+    cache: MokaCache<String, Vec<u8>>,
+    /// The codec pipeline used to encode the search results before they are stored.
+    codec: CodecPipeline,
+    /// The hit, miss and eviction counters of the cache.
+    metrics: Arc<CacheMetrics>,
+}
 
-    /// # Arguments
-    ///
-    /// * `search_results` - A reference to the search_Results to process.
+#[cfg(feature = "memory-cache")]
+impl InMemoryCache {
+    /// Constructs a new `InMemoryCache` with the given expiry times, size limit and codecs. The
+    /// size of an entry is the size of its key and of its encoded search results, and the least
+    /// recently used entries are evicted once the total size goes above the limit.
     ///
-
+    /// # Arguments
     ///
-    /// # Error
-    /// Returns a Vec of compressed or encrypted bytes on success otherwise it returns a CacheError
-    /// on failure.
-    async fn pre_process_search_results(
-        &mut self,
-        search_results: &SearchResults,
-    ) -> Result<Vec<u8>, Report<CacheError>> {
-        #[allow(unused_mut)] // needs to be mutable when any of the features is enabled
-        let mut bytes: Vec<u8> = search_results.try_into()?;
-        #[cfg(feature = "compress-cache-results")]
-        {
-            let compressed = self.compress_results(bytes).await?;
-            bytes = compressed;
+    /// * `cache_ttl` - It takes the time to live (in seconds) of the cached results as an
+    /// argument.
+    /// * `time_to_idle` - It takes the time (in seconds) after which the cached results which
+    /// were not looked up expire as an argument, if any.
+    /// * `max_size` - It takes the maximum size (in bytes) of the cache as an argument.
+    /// * `codec` - It takes the codec pipeline used to encode the search results as an argument.
+    pub fn new(
+        cache_ttl: u16,
+        time_to_idle: Option<u16>,
+        max_size: u64,
+        codec: CodecPipeline,
+    ) -> Self {
+        let metrics = Arc::new(CacheMetrics::default());
+        let eviction_metrics = Arc::clone(&metrics);
+
+        let mut builder = MokaCache::builder()
+            .max_capacity(max_size)
+            .weigher(|key: &String, value: &Vec<u8>| {
+                u32::try_from(key.len() + value.len()).unwrap_or(u32::MAX)
+            })
+            .time_to_live(Duration::from_secs(cache_ttl.into()))
+            .eviction_listener(move |_key, _value, cause| {
+                if cause == RemovalCause::Size {
+                    eviction_metrics.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        if let Some(time_to_idle) = time_to_idle {
+            builder = builder.time_to_idle(Duration::from_secs(time_to_idle.into()));
         }
 
-        #[cfg(feature = "encrypt-cache-results")]
-        {
-            let encrypted = self.encrypt_or_decrypt_results(bytes, true).await?;
-            bytes = encrypted;
+        InMemoryCache {
+            cache: builder.build(),
+            codec,
+            metrics,
         }
+    }
 
-        #[cfg(feature = "cec-cache-results")]
-        {
-            let compressed_encrypted_compressed =
-                self.compress_encrypt_compress_results(bytes).await?;
-            bytes = compressed_encrypted_compressed;
-        }
+    /// A getter function that gets the hit, miss and eviction counters of the cache.
+    pub fn metrics(&self) -> &CacheMetrics {
+        &self.metrics
+    }
+}
 
-        Ok(bytes)
+#[cfg(feature = "memory-cache")]
+#[async_trait::async_trait]
+impl Cacher for InMemoryCache {
+    async fn build(config: &Config) -> Self {
+        log::info!(
+            "Initialising in-memory cache with a maximum size of {} MB, a time to live of {}s and {}",
+            config.memory_cache_max_size / (1024 * 1024),
+            config.cache_expiry_time,
+            match config.cache_time_to_idle {
+                Some(time_to_idle) => format!("a time to idle of {time_to_idle}s"),
+                None => "no time to idle".to_owned(),
+            }
+        );
+        InMemoryCache::new(
+            config.cache_expiry_time,
+            config.cache_time_to_idle,
+            config.memory_cache_max_size,
+            CodecPipeline::new(config).expect("Cache encryption keys validated by the config"),
+        )
     }
 
-    /// A helper function that decompresses or decrypts search results after they're fetched from the cache-store
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
+        match self.cache.get(&key.hash()) {
+            Some(res) => {
+                self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+                self.codec.decode(&res).await
+            }
+            None => {
+                self.metrics.misses.fetch_add(1, Ordering::Relaxed);
+                Err(Report::new(CacheError::MissingValue))
+            }
+        }
+    }
 
-    /// # Arguments
-    ///
-    /// * `bytes` - A Vec of bytes stores in the cache.
-    ///
+    async fn cache_results(
+        &self,
+        search_results: &[SearchResults],
+        keys: &[CacheKey],
+    ) -> Result<(), Report<CacheError>> {
+        for (key, search_result) in keys.iter().zip(search_results.iter()) {
+            let bytes = self.codec.encode(key, search_result).await?;
+            self.cache.insert(key.hash(), bytes);
+        }
 
-    ///
-    /// # Error
-    /// Returns the SearchResults struct on success otherwise it returns a CacheError
-    /// on failure.
+        Ok(())
+    }
 
-    #[allow(unused_mut)] // needs to be mutable when any of the features is enabled
-    async fn post_process_search_results(
-        &mut self,
-        mut bytes: Vec<u8>,
-    ) -> Result<SearchResults, Report<CacheError>> {
-        #[cfg(feature = "compress-cache-results")]
-        {
-            let decompressed = self.decompress_results(&bytes).await?;
-            bytes = decompressed
-        }
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        self.cache.run_pending_tasks();
+        Ok(vec![CacheStats {
+            backend: "memory",
+            entries: Some(self.cache.entry_count()),
+            size: Some(self.cache.weighted_size()),
+            hits: Some(self.metrics.hits()),
+            misses: Some(self.metrics.misses()),
+            evictions: Some(self.metrics.evictions()),
+        }])
+    }
 
-        #[cfg(feature = "encrypt-cache-results")]
-        {
-            let decrypted = self.encrypt_or_decrypt_results(bytes, false).await?;
-            bytes = decrypted
-        }
+    async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        // The entries are copied out first, as the cache cannot be iterated across an await.
+        let entries: Vec<(Arc<String>, Vec<u8>)> = self.cache.iter().collect();
 
-        #[cfg(feature = "cec-cache-results")]
-        {
-            let decompressed_decrypted = self.decompress_results(&bytes).await?;
-            bytes = decompressed_decrypted;
+        let mut deleted = 0;
+        for (hash, bytes) in entries {
+            if let Ok(query) = self.codec.decode_query(&bytes).await {
+                if query.starts_with(query_prefix) && self.cache.remove(hash.as_str()).is_some() {
+                    deleted += 1;
+                }
+            }
         }
 
-        Ok(bytes.try_into()?)
+        self.cache.run_pending_tasks();
+        Ok(deleted)
+    }
+
+    async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        self.cache.run_pending_tasks();
+        let entries = self.cache.entry_count();
+        self.cache.invalidate_all();
+        self.cache.run_pending_tasks();
+        Ok(entries)
     }
 }
 
-/// A helper function that returns compressed results.
-/// Feature flags (**compress-cache-results or cec-cache-results**) are required  for this to work.
-/// If bytes where
-/// # Arguments
-///
-/// * `bytes` - It takes a slice of bytes as an argument.
+/// The time to wait between two attempts to reconnect to redis while it is unavailable.
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+const REDIS_RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
 
+/// Cache backend which utilises both memory and redis based caches.
 ///
-/// # Error
-/// Returns the uncompressed bytes on success otherwise it returns a CacheError
-/// on failure.
+/// The hybrid cache is a two-tier cache. The results are looked up in memory first and then in
+/// redis, and the results found in redis are copied back into memory so that the next lookups
+/// do not need a network round trip. The results are written to memory right away and to redis
+/// in the background. While redis is unavailable the cache keeps working from memory alone and
+/// keeps trying to reconnect to redis.
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+pub struct HybridCache {
+    /// The in-memory backend cache which stores data.
+    memory_cache: InMemoryCache,
+    /// The redis backend cache which stores data.
+    redis_tier: RedisTier,
+}
 
-#[cfg(any(feature = "compress-cache-results", feature = "cec-cache-results"))]
-async fn decompress_util(input: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
-    use tokio::io::AsyncWriteExt;
-    let mut writer = async_compression::tokio::write::BrotliDecoder::new(Vec::new());
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+impl HybridCache {
+    /// Constructs a new `HybridCache` from the in-memory cache and the redis connection
+    /// options. When redis cannot be reached, the cache starts in memory-only mode and connects to
+    /// redis as soon as it becomes available.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_cache` - It takes the in-memory cache used as the first tier as an argument.
+    /// * `redis_options` - It takes the options used to connect to redis as an argument.
+    /// * `cache_ttl` - It takes the time to live (in seconds) of the results stored in redis as
+    /// an argument.
+    /// * `codec` - It takes the codec pipeline used to encode the search results stored in redis
+    /// as an argument.
+    pub async fn new(
+        memory_cache: InMemoryCache,
+        redis_options: RedisOptions,
+        cache_ttl: u16,
+        codec: CodecPipeline,
+    ) -> Self {
+        let redis_tier = RedisTier {
+            redis_cache: Arc::new(RwLock::new(None)),
+            reconnecting: Arc::new(AtomicBool::new(false)),
+            redis_options: Arc::new(redis_options),
+            cache_ttl,
+            codec,
+        };
 
-    writer
-        .write_all(input)
-        .await
-        .map_err(|_| CacheError::CompressionError)?;
-    writer
-        .shutdown()
-        .await
-        .map_err(|_| CacheError::CompressionError)?;
-    let bytes = writer.into_inner();
-    Ok(bytes)
+        match redis_tier.connect().await {
+            Some(redis_cache) => redis_tier.set_redis_cache(Some(redis_cache)),
+            None => {
+                log::warn!("Redis is unavailable, falling back to the in-memory cache");
+                redis_tier.spawn_reconnect();
+            }
+        }
+
+        HybridCache {
+            memory_cache,
+            redis_tier,
+        }
+    }
 }
 
-#[cfg(feature = "redis-cache")]
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
 #[async_trait::async_trait]
-impl Cacher for RedisCache {
+impl Cacher for HybridCache {
     async fn build(config: &Config) -> Self {
-        log::info!(
-            "Initialising redis cache. Listening to {}",
-            &config.redis_url
-        );
-        RedisCache::new(&config.redis_url, 5, config.cache_expiry_time)
-            .await
-            .expect("Redis cache configured")
+        log::info!("Initialising hybrid cache");
+        HybridCache::new(
+            InMemoryCache::build(config).await,
+            RedisOptions::new(config),
+            config.cache_expiry_time,
+            CodecPipeline::new(config).expect("Cache encryption keys validated by the config"),
+        )
+        .await
     }
 
-    async fn cached_results(&mut self, url: &str) -> Result<SearchResults, Report<CacheError>> {
-        use base64::Engine;
-        let hashed_url_string: &str = &self.hash_url(url);
-        let base64_string = self.cached_json(hashed_url_string).await?;
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
+        if let Ok(results) = self.memory_cache.cached_results(key).await {
+            return Ok(results);
+        }
 
-        let bytes = base64::engine::general_purpose::STANDARD_NO_PAD
-            .decode(base64_string)
-            .map_err(|_| CacheError::Base64DecodingOrEncodingError)?;
-        self.post_process_search_results(bytes).await
+        let results = self.redis_tier.cached_results(key).await?;
+        // Promote the redis hit into memory so that the next lookups stay local.
+        if let Err(error) = self
+            .memory_cache
+            .cache_results(std::slice::from_ref(&results), std::slice::from_ref(key))
+            .await
+        {
+            log::debug!("Failed to backfill the in-memory cache: {:?}", error);
+        }
+        Ok(results)
     }
 
     async fn cache_results(
-        &mut self,
+        &self,
         search_results: &[SearchResults],
-        urls: &[String],
+        keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>> {
-        use base64::Engine;
+        self.memory_cache
+            .cache_results(search_results, keys)
+            .await?;
 
-        // size of search_results is expected to be equal to size of urls -> key/value pairs  for cache;
-        let search_results_len = search_results.len();
+        let (redis_tier, search_results, keys) = (
+            self.redis_tier.clone(),
+            search_results.to_vec(),
+            keys.to_vec(),
+        );
+        tokio::spawn(async move {
+            if let Err(error) = redis_tier.cache_results(&search_results, &keys).await {
+                log::error!("Failed to write the search results to redis: {:?}", error);
+            }
+        });
 
-        let mut bytes = Vec::with_capacity(search_results_len);
+        Ok(())
+    }
 
-        for result in search_results {
-            let processed = self.pre_process_search_results(result).await?;
-            bytes.push(processed);
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        let mut stats = self.memory_cache.stats().await?;
+        if let Some(redis_cache) = self.redis_tier.redis_cache() {
+            let redis_stats = redis_cache
+                .stats()
+                .await
+                .inspect_err(|error| self.redis_tier.handle_error(error))?;
+            stats.extend(redis_stats);
         }
+        Ok(stats)
+    }
 
-        let base64_strings = bytes
-            .iter()
-            .map(|bytes_vec| base64::engine::general_purpose::STANDARD_NO_PAD.encode(bytes_vec));
-
-        let mut hashed_url_strings = Vec::with_capacity(search_results_len);
+    async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        let mut deleted = self.memory_cache.delete(query_prefix).await?;
+        if let Some(redis_cache) = self.redis_tier.redis_cache() {
+            deleted += redis_cache
+                .delete(query_prefix)
+                .await
+                .inspect_err(|error| self.redis_tier.handle_error(error))?;
+        }
+        Ok(deleted)
+    }
 
-        for url in urls {
-            let hash = self.hash_url(url);
-            hashed_url_strings.push(hash);
+    async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        let mut deleted = self.memory_cache.clear().await?;
+        if let Some(redis_cache) = self.redis_tier.redis_cache() {
+            deleted += redis_cache
+                .clear()
+                .await
+                .inspect_err(|error| self.redis_tier.handle_error(error))?;
         }
-        self.cache_json(base64_strings, hashed_url_strings.into_iter())
-            .await
+        Ok(deleted)
     }
 }
-/// TryInto implementation for SearchResults from Vec<u8>
-use std::convert::TryInto;
 
-impl TryInto<SearchResults> for Vec<u8> {
-    type Error = CacheError;
+/// A named struct which stores the redis tier of the hybrid cache along with what is needed to
+/// reconnect to redis while it is unavailable.
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+#[derive(Clone)]
+struct RedisTier {
+    /// It stores the redis cache, or `None` while redis is unavailable. The lock is only held
+    /// to swap the connection, never while a command runs.
+    redis_cache: Arc<RwLock<Option<Arc<RedisCache>>>>,
+    /// It stores whether a task is currently trying to reconnect to redis.
+    reconnecting: Arc<AtomicBool>,
+    /// It stores the options used to connect to redis.
+    redis_options: Arc<RedisOptions>,
+    /// It stores the time to live (in seconds) of the results stored in redis.
+    cache_ttl: u16,
+    /// It stores the codec pipeline used to encode the search results stored in redis.
+    codec: CodecPipeline,
+}
 
-    fn try_into(self) -> Result<SearchResults, Self::Error> {
-        serde_json::from_slice(&self).map_err(|_| CacheError::SerializationError)
+#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+impl RedisTier {
+    /// Connects to redis.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if redis cannot be reached.
+    async fn connect(&self) -> Option<RedisCache> {
+        match RedisCache::new(&self.redis_options, self.cache_ttl, self.codec).await {
+            Ok(redis_cache) => Some(redis_cache),
+            Err(error) => {
+                log::debug!("Failed to connect to redis: {}", error);
+                None
+            }
+        }
     }
-}
 
-impl TryInto<Vec<u8>> for &SearchResults {
-    type Error = CacheError;
+    /// Starts a background task which tries to reconnect to redis until it succeeds, unless
+    /// such a task is already running.
+    fn spawn_reconnect(&self) {
+        if self.reconnecting.swap(true, Ordering::AcqRel) {
+            return;
+        }
 
-    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
-        serde_json::to_vec(self).map_err(|_| CacheError::SerializationError)
+        let redis_tier = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REDIS_RECONNECT_INTERVAL).await;
+                if let Some(redis_cache) = redis_tier.connect().await {
+                    redis_tier.set_redis_cache(Some(redis_cache));
+                    log::info!("Reconnected to redis");
+                    break;
+                }
+            }
+            redis_tier.reconnecting.store(false, Ordering::Release);
+        });
     }
-}
 
-/// Memory based cache backend.
-#[cfg(feature = "memory-cache")]
-pub struct InMemoryCache {
-    /// The backend cache which stores data.
-    cache: MokaCache<String, Vec<u8>>,
-}
+    /// A getter function that gets the redis cache, or `None` while redis is unavailable.
+    fn redis_cache(&self) -> Option<Arc<RedisCache>> {
+        self.redis_cache
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone()
+    }
 
-#[cfg(feature = "memory-cache")]
-#[async_trait::async_trait]
-impl Cacher for InMemoryCache {
-    async fn build(config: &Config) -> Self {
-        log::info!("Initialising in-memory cache");
+    /// A setter function that sets the redis cache, `None` marking redis as unavailable.
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_cache` - It takes the connected redis cache or `None` as an argument.
+    fn set_redis_cache(&self, redis_cache: Option<RedisCache>) {
+        *self
+            .redis_cache
+            .write()
+            .unwrap_or_else(|error| error.into_inner()) = redis_cache.map(Arc::new);
+    }
 
-        InMemoryCache {
-            cache: MokaCache::builder()
-                .time_to_live(Duration::from_secs(config.cache_expiry_time.into()))
-                .build(),
+    /// Marks redis as unavailable when the error shows that the connection to it was lost and
+    /// starts reconnecting in the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - It takes the error returned by redis as an argument.
+    fn handle_error(&self, error: &Report<CacheError>) {
+        if is_connection_lost(error.current_context()) {
+            log::warn!("Redis is unavailable, falling back to the in-memory cache");
+            self.set_redis_cache(None);
+            self.spawn_reconnect();
         }
     }
 
-    async fn cached_results(&mut self, url: &str) -> Result<SearchResults, Report<CacheError>> {
-        let hashed_url_string = self.hash_url(url);
-        match self.cache.get(&hashed_url_string) {
-            Some(res) => self.post_process_search_results(res).await,
-            None => Err(Report::new(CacheError::MissingValue)),
+    /// Fetches the cached results from redis.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `MissingValue` error while redis is unavailable or a `CacheError` if the results
+    /// cannot be retrieved from redis.
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
+        let result = match self.redis_cache() {
+            Some(redis_cache) => redis_cache.cached_results(key).await,
+            None => return Err(Report::new(CacheError::MissingValue)),
+        };
+        if let Err(error) = &result {
+            self.handle_error(error);
         }
+        result
     }
 
+    /// Stores the search results in redis. The results are dropped while redis is unavailable.
+    ///
+    /// # Arguments
+    ///
+    /// * `search_results` - It takes the search results to be cached as an argument.
+    /// * `keys` - It takes the cache keys of the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if the results cannot be stored in redis.
     async fn cache_results(
-        &mut self,
+        &self,
         search_results: &[SearchResults],
-        urls: &[String],
+        keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>> {
-        for (url, search_result) in urls.iter().zip(search_results.iter()) {
-            let hashed_url_string = self.hash_url(url);
-            let bytes = self.pre_process_search_results(search_result).await?;
-            self.cache.insert(hashed_url_string, bytes);
+        let result = match self.redis_cache() {
+            Some(redis_cache) => redis_cache.cache_results(search_results, keys).await,
+            None => return Ok(()),
+        };
+        if let Err(error) = &result {
+            self.handle_error(error);
         }
-
-        self.cache.sync();
-        Ok(())
+        result
     }
 }
 
-/// Cache backend which utilises both memory and redis based caches.
-///
-/// The hybrid cache system uses both the types of cache to ensure maximum availability.
-/// The set method sets the key, value pair in both the caches. Therefore in a case where redis
-/// cache becomes unavailable, the backend will retreive the value from in-memory cache.
-#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
-pub struct HybridCache {
-    /// The in-memory backend cache which stores data.
-    memory_cache: InMemoryCache,
-    /// The redis backend cache which stores data.
-    redis_cache: RedisCache,
-}
-
-#[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+#[cfg(feature = "disk-cache")]
 #[async_trait::async_trait]
-impl Cacher for HybridCache {
+impl Cacher for DiskCache {
     async fn build(config: &Config) -> Self {
-        log::info!("Initialising hybrid cache");
-        HybridCache {
-            memory_cache: InMemoryCache::build(config).await,
-            redis_cache: RedisCache::build(config).await,
-        }
+        log::info!(
+            "Initialising disk cache. Storing the results in {}",
+            config.disk_cache_directory.display()
+        );
+        DiskCache::new(
+            config.disk_cache_directory.clone(),
+            config.disk_cache_max_size,
+            config.cache_expiry_time,
+            CodecPipeline::new(config).expect("Cache encryption keys validated by the config"),
+        )
+        .await
+        .expect("Disk cache directory accessible")
     }
 
-    async fn cached_results(&mut self, url: &str) -> Result<SearchResults, Report<CacheError>> {
-        match self.redis_cache.cached_results(url).await {
-            Ok(res) => Ok(res),
-            Err(_) => self.memory_cache.cached_results(url).await,
-        }
+    async fn cached_results(&self, key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
+        DiskCache::cached_results(self, key).await
     }
 
     async fn cache_results(
-        &mut self,
+        &self,
         search_results: &[SearchResults],
-        urls: &[String],
+        keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>> {
-        self.redis_cache.cache_results(search_results, urls).await?;
-        self.memory_cache
-            .cache_results(search_results, urls)
-            .await?;
+        DiskCache::cache_results(self, search_results, keys).await
+    }
 
-        Ok(())
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        let (entries, size) = self.entry_count_and_size();
+        Ok(vec![CacheStats {
+            backend: "disk",
+            entries: Some(entries),
+            size: Some(size),
+            ..Default::default()
+        }])
+    }
+
+    async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        Ok(self.delete_matching(query_prefix).await)
+    }
+
+    async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        self.delete_all().await
     }
 }
 
@@ -493,23 +757,61 @@ impl Cacher for DisabledCache {
         DisabledCache
     }
 
-    async fn cached_results(&mut self, _url: &str) -> Result<SearchResults, Report<CacheError>> {
+    async fn cached_results(&self, _key: &CacheKey) -> Result<SearchResults, Report<CacheError>> {
         Err(Report::new(CacheError::MissingValue))
     }
 
     async fn cache_results(
-        &mut self,
+        &self,
         _search_results: &[SearchResults],
-        _urls: &[String],
+        _keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>> {
         Ok(())
     }
+
+    async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        Ok(vec![CacheStats {
+            backend: "disabled",
+            ..Default::default()
+        }])
+    }
+
+    async fn delete(&self, _query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        Ok(0)
+    }
+
+    async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        Ok(0)
+    }
 }
 
-/// A structure to efficiently share the cache between threads - as it is protected by a Mutex.
+/// The time (in seconds) during which the prefetched pages are tracked when the expiry time of
+/// the cached results is not set.
+const DEFAULT_PREFETCH_TRACKING_TIME: u64 = 600;
+
+/// A structure to efficiently share the cache between threads. The cache backends are safe to
+/// access concurrently, so the requests never wait for each other to use the cache.
+///
+/// The cached results older than the hard expiry time are never served. When
+/// stale-while-revalidate is enabled, the cached results older than the soft expiry time are
+/// still served but should be refreshed in the background.
 pub struct SharedCache {
-    /// The internal cache protected from concurrent access by a mutex
-    cache: Mutex<Box<dyn Cacher>>,
+    /// The internal cache backend.
+    cache: Box<dyn Cacher>,
+    /// The time (in seconds) after which the cached results are stale, if
+    /// stale-while-revalidate is enabled.
+    soft_ttl: Option<u64>,
+    /// The time (in seconds) after which the cached results are no longer served, if any.
+    hard_ttl: Option<u64>,
+    /// The hashes of the cache keys whose results are being refreshed in the background.
+    refreshing: Mutex<HashSet<String>>,
+    /// The aggregations in flight, which are shared by the identical concurrent searches.
+    single_flight: SingleFlight,
+    /// The tracker of the prefetched pages.
+    prefetches: PrefetchTracker,
+    /// The cache of the rendered search pages, if enabled.
+    #[cfg(feature = "memory-cache")]
+    pages: Option<PageCache>,
 }
 
 impl SharedCache {
@@ -522,35 +824,224 @@ impl SharedCache {
     /// Returns a newly constructed `SharedCache` struct.
     pub fn new(cache: impl Cacher + 'static) -> Self {
         Self {
-            cache: Mutex::new(Box::new(cache)),
+            cache: Box::new(cache),
+            soft_ttl: None,
+            hard_ttl: None,
+            refreshing: Mutex::new(HashSet::new()),
+            single_flight: SingleFlight::default(),
+            prefetches: PrefetchTracker::new(DEFAULT_PREFETCH_TRACKING_TIME),
+            #[cfg(feature = "memory-cache")]
+            pages: None,
         }
     }
 
+    /// Enables the cache of the rendered search pages, so that the pages of the cached search
+    /// results are only rendered once.
+    ///
+    /// # Arguments
+    ///
+    /// * `pages` - It takes the cache which stores the rendered pages as an argument.
+    #[cfg(feature = "memory-cache")]
+    pub fn with_page_cache(mut self, pages: PageCache) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// Sets the expiry times of the cached results, which enables stale-while-revalidate when
+    /// a soft expiry time is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `soft_ttl` - It takes the time (in seconds) after which the cached results are stale
+    /// and should be refreshed as an argument, if any.
+    /// * `hard_ttl` - It takes the time (in seconds) after which the cached results are no
+    /// longer served as an argument.
+    pub fn with_expiry_times(mut self, soft_ttl: Option<u16>, hard_ttl: u16) -> Self {
+        self.soft_ttl = soft_ttl.map(u64::from);
+        self.hard_ttl = Some(hard_ttl.into());
+        self.prefetches = PrefetchTracker::new(hard_ttl.into());
+        self
+    }
+
     /// A getter function which retrieves the cached SearchResulsts from the internal cache.
     ///
     /// # Arguments
     ///
-    /// * `url` - It takes the search url as an argument which will be used as the key to fetch the
-    /// cached results from the cache.
+    /// * `key` - It takes the cache key of the search as an argument which will be used to fetch
+    /// the cached results from the cache.
     ///
     /// # Error
     ///
     /// Returns a `SearchResults` struct containing the search results from the cache if nothing
     /// goes wrong otherwise returns a `CacheError`.
-    pub async fn cached_results(&self, url: &str) -> Result<SearchResults, Report<CacheError>> {
-        let mut mut_cache = self.cache.lock().await;
-        mut_cache.cached_results(url).await
+    pub async fn cached_results(
+        &self,
+        key: &CacheKey,
+    ) -> Result<SearchResults, Report<CacheError>> {
+        let search_results = self.cache.cached_results(key).await?;
+        match (self.hard_ttl, search_results.cached_at()) {
+            // The backends reset the expiry time of the results whenever they are stored again,
+            // so the hard expiry time is checked against the time they were first cached.
+            (Some(hard_ttl), Some(cached_at)) if cached_at + hard_ttl <= unix_time() => {
+                Err(Report::new(CacheError::MissingValue))
+            }
+            _ => Ok(search_results),
+        }
     }
 
-    /// A setter function which caches the results by using the `url` as the key and
+    /// A getter function which retrieves the cached raw results of an upstream search engine.
+    /// The results are keyed by their url, the same way the engines key them. The results past
+    /// the soft expiry time are treated as missing, so that refreshing the stale aggregated
+    /// results queries the upstream engines again.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the engine cache key of the results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the results of the engine from the cache along with the time (in seconds since
+    /// the unix epoch) at which they were first cached if nothing goes wrong otherwise returns a
+    /// `CacheError`.
+    pub async fn cached_engine_results(
+        &self,
+        key: &CacheKey,
+    ) -> Result<(Vec<(String, SearchResult)>, Option<u64>), Report<CacheError>> {
+        let search_results = self.cached_results(key).await?;
+        if self.is_stale(&search_results) {
+            return Err(Report::new(CacheError::MissingValue));
+        }
+
+        let cached_at = search_results.cached_at();
+        let results = search_results
+            .results
+            .into_iter()
+            .map(|result| (result.url.clone(), result))
+            .collect();
+        Ok((results, cached_at))
+    }
+
+    /// A setter function which caches the raw results of the upstream search engines.
+    ///
+    /// # Arguments
+    ///
+    /// * `engine_results` - It takes the results of the engines as an argument.
+    /// * `keys` - It takes the engine cache keys of the results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an unit type if the results are cached succesfully otherwise returns a `CacheError`
+    /// on a failure.
+    pub async fn cache_engine_results(
+        &self,
+        engine_results: &[Vec<(String, SearchResult)>],
+        keys: &[CacheKey],
+    ) -> Result<(), Report<CacheError>> {
+        let search_results: Vec<SearchResults> = engine_results
+            .iter()
+            .map(|results| {
+                SearchResults::new(
+                    results.iter().map(|(_, result)| result.clone()).collect(),
+                    &[],
+                )
+            })
+            .collect();
+        self.cache_results(&search_results, keys).await
+    }
+
+    /// Checks whether the cached results are past the soft expiry time and should be refreshed.
+    ///
+    /// # Arguments
+    ///
+    /// * `search_results` - It takes the search results fetched from the cache as an argument.
+    pub fn is_stale(&self, search_results: &SearchResults) -> bool {
+        match (self.soft_ttl, search_results.cached_at()) {
+            (Some(soft_ttl), Some(cached_at)) => cached_at + soft_ttl <= unix_time(),
+            _ => false,
+        }
+    }
+
+    /// Marks the results stored under the given key as being refreshed, so that only one
+    /// refresh runs per key at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the stale search results as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns a guard which ends the refresh once it is dropped, or `None` if the results are
+    /// already being refreshed.
+    pub fn start_refresh(&self, key: &CacheKey) -> Option<RefreshGuard<'_>> {
+        let hash = key.hash();
+        self.refreshing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(hash.clone())
+            .then_some(RefreshGuard { cache: self, hash })
+    }
+
+    /// Aggregates the search results stored under the given key, unless an identical search is
+    /// already aggregating them, in which case its search results are awaited and shared.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the search results as an argument.
+    /// * `fetch` - It takes the function which aggregates the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the error of the aggregation if it fails.
+    pub async fn coalesce<F, Fut>(
+        &self,
+        key: &CacheKey,
+        fetch: F,
+    ) -> Result<SearchResults, Box<dyn std::error::Error>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<SearchResults, Box<dyn std::error::Error>>>,
+    {
+        self.single_flight.run(key, fetch).await
+    }
+
+    /// Renders the search page, reusing the page rendered earlier under the same key if the
+    /// cache of the rendered pages is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the page hash of the cache key of the search results as an argument,
+    /// or `None` if the page should not be cached.
+    /// * `render` - It takes the function which renders the html of the page as an argument.
+    pub fn rendered_page(
+        &self,
+        key: Option<String>,
+        render: impl FnOnce() -> String,
+    ) -> Arc<RenderedPage> {
+        #[cfg(feature = "memory-cache")]
+        if let (Some(pages), Some(key)) = (&self.pages, key) {
+            return pages.get_or_render(key, render);
+        }
+
+        #[cfg(not(feature = "memory-cache"))]
+        let _ = key;
+
+        Arc::new(RenderedPage::new(render()))
+    }
+
+    /// A getter function that gets the tracker of the prefetched pages.
+    pub fn prefetches(&self) -> &PrefetchTracker {
+        &self.prefetches
+    }
+
+    /// A setter function which caches the results by using the `key` as the key and
     /// `SearchResults` as the value.
     ///
     /// # Arguments
     ///
     /// * `search_results` - It takes the `SearchResults` as an argument which are results that
     /// needs to be cached.
-    /// * `url` - It takes the search url as an argument which will be used as the key for storing
-    /// results in the cache.
+    /// * `keys` - It takes the cache keys of the searches as an argument which will be used for
+    /// storing results in the cache.
     ///
     /// # Error
     ///
@@ -559,26 +1050,315 @@ impl SharedCache {
     pub async fn cache_results(
         &self,
         search_results: &[SearchResults],
-        urls: &[String],
+        keys: &[CacheKey],
     ) -> Result<(), Report<CacheError>> {
-        let mut mut_cache = self.cache.lock().await;
-        mut_cache.cache_results(search_results, urls).await
+        self.cache.cache_results(search_results, keys).await
+    }
+
+    /// A getter function which retrieves the statistics of the internal cache.
+    ///
+    /// # Error
+    ///
+    /// Returns the statistics of every tier of the cache if nothing goes wrong otherwise returns
+    /// a `CacheError`.
+    pub async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+        self.cache.stats().await
+    }
+
+    /// Removes the cached results whose query starts with the given prefix. The prefix is
+    /// normalized the same way as the queries of the cache keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_prefix` - It takes the prefix of the queries to be removed as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of removed entries if nothing goes wrong otherwise returns a
+    /// `CacheError`.
+    pub async fn delete(&self, query_prefix: &str) -> Result<u64, Report<CacheError>> {
+        // The rendered pages are keyed by a hash which does not reveal their query, so all of
+        // them are dropped and rendered again from the remaining search results.
+        self.clear_pages();
+        self.cache.delete(&normalize_query(query_prefix)).await
+    }
+
+    /// Removes all the cached results.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of removed entries if nothing goes wrong otherwise returns a
+    /// `CacheError`.
+    pub async fn clear(&self) -> Result<u64, Report<CacheError>> {
+        self.clear_pages();
+        self.cache.clear().await
+    }
+
+    /// Removes all the rendered pages, if the cache of the rendered pages is enabled.
+    fn clear_pages(&self) {
+        #[cfg(feature = "memory-cache")]
+        if let Some(pages) = &self.pages {
+            pages.clear();
+        }
+    }
+}
+
+/// A guard which marks the results stored under a cache key as being refreshed until it is
+/// dropped.
+pub struct RefreshGuard<'a> {
+    /// The shared cache which tracks the refreshes.
+    cache: &'a SharedCache,
+    /// The hash of the cache key whose results are being refreshed.
+    hash: String,
+}
+
+impl Drop for RefreshGuard<'_> {
+    fn drop(&mut self) {
+        self.cache
+            .refreshing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.hash);
     }
 }
 
-/// A function to initialise the cache backend.
+/// A function to initialise the cache backend. The disk cache takes precedence over the
+/// in-memory cache when the app is compiled with the `disk-cache` feature without redis.
 pub async fn create_cache(config: &Config) -> impl Cacher {
     #[cfg(all(feature = "redis-cache", feature = "memory-cache"))]
     return HybridCache::build(config).await;
 
-    #[cfg(all(feature = "memory-cache", not(feature = "redis-cache")))]
+    #[cfg(all(feature = "disk-cache", not(feature = "redis-cache")))]
+    return DiskCache::build(config).await;
+
+    #[cfg(all(
+        feature = "memory-cache",
+        not(feature = "redis-cache"),
+        not(feature = "disk-cache")
+    ))]
     return InMemoryCache::build(config).await;
 
     #[cfg(all(feature = "redis-cache", not(feature = "memory-cache")))]
     return RedisCache::build(config).await;
 
-    #[cfg(not(any(feature = "memory-cache", feature = "redis-cache")))]
+    #[cfg(not(any(
+        feature = "memory-cache",
+        feature = "redis-cache",
+        feature = "disk-cache"
+    )))]
     return DisabledCache::build(config).await;
 }
 
-//#[cfg(feature = "Compress-cache-results")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_results() -> SearchResults {
+        SearchResults::new(
+            vec![SearchResult::new(
+                "Rust",
+                "https://www.rust-lang.org/",
+                "A language empowering everyone to build reliable and efficient software.",
+                &["bing"],
+            )],
+            &[],
+        )
+    }
+
+    #[cfg(feature = "memory-cache")]
+    #[tokio::test]
+    async fn test_in_memory_cache() {
+        let cache = InMemoryCache::new(
+            60,
+            None,
+            1024 * 1024,
+            CodecPipeline::with_codecs(false, false),
+        );
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+
+        assert!(cache.cached_results(&key).await.is_err());
+        cache
+            .cache_results(&[search_results()], &[key.clone()])
+            .await
+            .unwrap();
+        let cached = cache.cached_results(&key).await.unwrap();
+        assert_eq!(cached.results[0].url, "https://www.rust-lang.org/");
+        assert_eq!(cache.metrics().hits(), 1);
+        assert_eq!(cache.metrics().misses(), 1);
+    }
+
+    #[cfg(feature = "memory-cache")]
+    #[tokio::test]
+    async fn test_in_memory_cache_evicts_above_max_size() {
+        let codec = CodecPipeline::with_codecs(false, false);
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+        let entry_size =
+            (codec.encode(&key, &search_results()).await.unwrap().len() + key.hash().len()) as u64;
+        let cache = InMemoryCache::new(60, None, entry_size * 4, codec);
+
+        for page in 0..16 {
+            cache
+                .cache_results(
+                    &[search_results()],
+                    &[CacheKey::new("rust", page, 0, ["bing"])],
+                )
+                .await
+                .unwrap();
+        }
+
+        // The eviction runs with the maintenance of the cache, which is not done on insert.
+        cache.cache.run_pending_tasks();
+        assert!(cache.cache.weighted_size() <= entry_size * 4);
+        assert!(cache.metrics().evictions() > 0);
+    }
+
+    #[cfg(feature = "memory-cache")]
+    #[tokio::test]
+    async fn test_entries_are_deleted_by_query_prefix() {
+        let cache = SharedCache::new(InMemoryCache::new(
+            60,
+            None,
+            1024 * 1024,
+            CodecPipeline::with_codecs(false, false),
+        ));
+        let keys = ["rust book", "rust", "Rustacean", "go"]
+            .map(|query| CacheKey::new(query, 0, 0, ["bing"]));
+        cache
+            .cache_results(&vec![search_results(); keys.len()], &keys)
+            .await
+            .unwrap();
+
+        assert_eq!(cache.delete("RUST ").await.unwrap(), 3);
+        assert!(cache.cached_results(&keys[0]).await.is_err());
+        assert!(cache.cached_results(&keys[3]).await.is_ok());
+        assert_eq!(cache.stats().await.unwrap()[0].entries, Some(1));
+
+        assert_eq!(cache.clear().await.unwrap(), 1);
+        assert!(cache.cached_results(&keys[3]).await.is_err());
+    }
+
+    #[cfg(feature = "memory-cache")]
+    #[tokio::test]
+    async fn test_engine_results_are_shared_between_searches() {
+        let cache = SharedCache::new(InMemoryCache::new(
+            60,
+            None,
+            1024 * 1024,
+            CodecPipeline::with_codecs(false, false),
+        ));
+        let engine_results = search_results()
+            .results
+            .into_iter()
+            .map(|result| (result.url.clone(), result))
+            .collect::<Vec<_>>();
+
+        cache
+            .cache_engine_results(
+                &[engine_results],
+                &[CacheKey::for_engine("bing", "Rust", 0, 2)],
+            )
+            .await
+            .unwrap();
+
+        let (cached, cached_at) = cache
+            .cached_engine_results(&CacheKey::for_engine("Bing", "rust", 0, 2))
+            .await
+            .unwrap();
+        assert_eq!(cached[0].0, "https://www.rust-lang.org/");
+        assert!(cached_at.is_some());
+        assert!(cache
+            .cached_results(&CacheKey::new("rust", 0, 2, ["bing"]))
+            .await
+            .is_err());
+    }
+
+    #[cfg(all(feature = "memory-cache", feature = "redis-cache"))]
+    #[tokio::test]
+    async fn test_hybrid_cache_degrades_to_memory_only() {
+        let codec = CodecPipeline::with_codecs(false, false);
+        // Nothing listens on port 1, so the cache starts without redis.
+        let cache = HybridCache::new(
+            InMemoryCache::new(60, None, 1024 * 1024, codec),
+            RedisOptions {
+                url: "redis://127.0.0.1:1".to_owned(),
+                pool_size: 5,
+                key_prefix: "websurfx:".to_owned(),
+            },
+            60,
+            codec,
+        )
+        .await;
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+
+        assert!(cache.cached_results(&key).await.is_err());
+        cache
+            .cache_results(&[search_results()], &[key.clone()])
+            .await
+            .unwrap();
+        assert!(cache.cached_results(&key).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        struct StaticCache(SearchResults);
+
+        #[async_trait::async_trait]
+        impl Cacher for StaticCache {
+            async fn build(_config: &Config) -> Self {
+                unreachable!()
+            }
+
+            async fn cached_results(
+                &self,
+                _key: &CacheKey,
+            ) -> Result<SearchResults, Report<CacheError>> {
+                Ok(self.0.clone())
+            }
+
+            async fn cache_results(
+                &self,
+                _search_results: &[SearchResults],
+                _keys: &[CacheKey],
+            ) -> Result<(), Report<CacheError>> {
+                Ok(())
+            }
+
+            async fn stats(&self) -> Result<Vec<CacheStats>, Report<CacheError>> {
+                Ok(Vec::new())
+            }
+
+            async fn delete(&self, _query_prefix: &str) -> Result<u64, Report<CacheError>> {
+                Ok(0)
+            }
+
+            async fn clear(&self) -> Result<u64, Report<CacheError>> {
+                Ok(0)
+            }
+        }
+
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+        let shared_cache = |age: u64| {
+            let mut results = search_results();
+            results.set_cached_at(unix_time() - age);
+            SharedCache::new(StaticCache(results)).with_expiry_times(Some(60), 600)
+        };
+
+        let fresh = shared_cache(10);
+        assert!(!fresh.is_stale(&fresh.cached_results(&key).await.unwrap()));
+
+        let stale = shared_cache(120);
+        assert!(stale.is_stale(&stale.cached_results(&key).await.unwrap()));
+        // The stale results of an engine are fetched again instead of being merged.
+        assert!(fresh.cached_engine_results(&key).await.is_ok());
+        assert!(stale.cached_engine_results(&key).await.is_err());
+
+        let expired = shared_cache(700);
+        assert!(expired.cached_results(&key).await.is_err());
+
+        let refresh = stale.start_refresh(&key);
+        assert!(refresh.is_some());
+        assert!(stale.start_refresh(&key).is_none());
+        drop(refresh);
+        assert!(stale.start_refresh(&key).is_some());
+    }
+}
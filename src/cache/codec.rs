@@ -0,0 +1,680 @@
+//! This module provides the codec pipeline which turns the search results into the bytes stored
+//! in the cache and back. The results are serialized, then optionally compressed and then
+//! optionally encrypted, and the bytes are prefixed with an envelope header recording which codecs
+//! were applied, so that the entries can still be decoded after the codecs are changed in the
+//! config.
+//!
+//! The envelope is laid out as
+//! `[magic][envelope version][serializer id][codec flags][cached at][payload]`, where the time at
+//! which the results were first cached is stored as little endian seconds since the unix epoch.
+//! Before being compressed and encrypted, the payload is laid out as
+//! `[query length][query][serialized search results]`, so that the entries can be found by their
+//! query. The query is only hidden when the results are encrypted, as it is otherwise stored in
+//! the clear, or only compressed, along with the results. When the results are encrypted, the
+//! envelope header is authenticated along with the payload, so that neither the recorded codecs
+//! nor the time at which the results were cached can be changed without the decryption failing.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use error_stack::Report;
+
+use crate::{config::parser::Config, models::aggregation_models::SearchResults};
+
+#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+use super::encryption::{CacheCipher, CIPHER};
+use super::{cache_key::CacheKey, error::CacheError};
+
+/// The bytes every cache entry starts with.
+const MAGIC: [u8; 2] = *b"WX";
+
+/// The version of the envelope layout, which is bumped whenever the layout changes.
+const ENVELOPE_VERSION: u8 = 4;
+
+/// The size (in bytes) of the envelope header.
+const HEADER_SIZE: usize = MAGIC.len() + 3 + 8;
+
+/// The codec flag recording that the payload was compressed with brotli.
+const COMPRESSED: u8 = 0b01;
+
+/// The codec flag recording that the payload was encrypted with the cache cipher.
+const ENCRYPTED: u8 = 0b10;
+
+/// An enum type which provides the formats in which the search results can be serialized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Serializer {
+    /// Serializes the search results as json.
+    Json,
+    /// Serializes the search results as MessagePack, keeping the field names so that the fields
+    /// which are left out when empty can still be told apart.
+    #[cfg(feature = "msgpack-cache-results")]
+    MessagePack,
+}
+
+impl Default for Serializer {
+    /// Uses MessagePack when the app was compiled with the `msgpack-cache-results` feature and
+    /// json otherwise.
+    fn default() -> Self {
+        #[cfg(feature = "msgpack-cache-results")]
+        return Serializer::MessagePack;
+
+        #[cfg(not(feature = "msgpack-cache-results"))]
+        return Serializer::Json;
+    }
+}
+
+impl Serializer {
+    /// Parses the serializer from its name in the config.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - It takes the name of the serializer as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the name is unknown or the serializer was not compiled in.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Serializer::Json),
+            #[cfg(feature = "msgpack-cache-results")]
+            "messagepack" => Some(Serializer::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Returns the id recorded in the envelope header for the serializer.
+    fn id(self) -> u8 {
+        match self {
+            Serializer::Json => 0,
+            #[cfg(feature = "msgpack-cache-results")]
+            Serializer::MessagePack => 1,
+        }
+    }
+
+    /// Returns the serializer recorded in the envelope header with the given id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - It takes the serializer id read from the envelope header as an argument.
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Serializer::Json),
+            #[cfg(feature = "msgpack-cache-results")]
+            1 => Some(Serializer::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Serializes the search results.
+    ///
+    /// # Arguments
+    ///
+    /// * `search_results` - It takes the search results to be serialized as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `SerializationError` if the search results cannot be serialized.
+    fn serialize(self, search_results: &SearchResults) -> Result<Vec<u8>, Report<CacheError>> {
+        match self {
+            Serializer::Json => {
+                Ok(serde_json::to_vec(search_results)
+                    .map_err(|_| CacheError::SerializationError)?)
+            }
+            #[cfg(feature = "msgpack-cache-results")]
+            Serializer::MessagePack => Ok(rmp_serde::to_vec_named(search_results)
+                .map_err(|_| CacheError::SerializationError)?),
+        }
+    }
+
+    /// Deserializes the search results.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the serialized search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `SerializationError` if the bytes are not valid serialized search results.
+    fn deserialize(self, bytes: &[u8]) -> Result<SearchResults, Report<CacheError>> {
+        match self {
+            Serializer::Json => {
+                Ok(serde_json::from_slice(bytes).map_err(|_| CacheError::SerializationError)?)
+            }
+            #[cfg(feature = "msgpack-cache-results")]
+            Serializer::MessagePack => {
+                Ok(rmp_serde::from_slice(bytes).map_err(|_| CacheError::SerializationError)?)
+            }
+        }
+    }
+}
+
+/// A named struct which stores the codecs applied to the search results before they are stored
+/// in the cache.
+#[derive(Clone, Copy, Debug)]
+pub struct CodecPipeline {
+    /// It stores the format in which the search results are serialized.
+    serializer: Serializer,
+    /// It stores whether the serialized search results are compressed.
+    compress: bool,
+    /// It stores whether the serialized (and compressed) search results are encrypted.
+    encrypt: bool,
+}
+
+impl CodecPipeline {
+    /// Builds the codec pipeline from the compression and encryption options of the config. The
+    /// cache cipher is initialized with the configured encryption keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes the parsed config as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an `EncryptionKeyError` if any of the configured encryption keys is malformed.
+    pub fn new(config: &Config) -> Result<Self, Report<CacheError>> {
+        #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+        if config.encrypt_cache_results && !config.cache_encryption_keys.is_empty() {
+            let cipher = CacheCipher::from_keys(&config.cache_encryption_keys)?;
+            // The cipher is only set once, the first cache built wins.
+            let _ = CIPHER.set(cipher);
+        }
+
+        Ok(
+            Self::with_codecs(config.compress_cache_results, config.encrypt_cache_results)
+                .with_serializer(config.cache_serializer),
+        )
+    }
+
+    /// Builds the codec pipeline with the given codecs. A codec whose feature was not compiled
+    /// in is reported in the logs and left out of the pipeline.
+    ///
+    /// # Arguments
+    ///
+    /// * `compress` - It takes whether the search results should be compressed as an argument.
+    /// * `encrypt` - It takes whether the search results should be encrypted as an argument.
+    pub fn with_codecs(compress: bool, encrypt: bool) -> Self {
+        let compression_available = cfg!(any(
+            feature = "compress-cache-results",
+            feature = "cec-cache-results"
+        ));
+        if compress && !compression_available {
+            log::error!("Config Error: The cache results cannot be compressed as the app was compiled without the `compress-cache-results` feature");
+        }
+
+        let encryption_available = cfg!(any(
+            feature = "encrypt-cache-results",
+            feature = "cec-cache-results"
+        ));
+        if encrypt && !encryption_available {
+            log::error!("Config Error: The cache results cannot be encrypted as the app was compiled without the `encrypt-cache-results` feature");
+        }
+
+        #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+        if encrypt {
+            CIPHER.get_or_init(|| {
+                log::warn!("No cache encryption key was configured, using a key which only lives as long as the process");
+                CacheCipher::ephemeral()
+            });
+        }
+
+        CodecPipeline {
+            serializer: Serializer::default(),
+            compress: compress && compression_available,
+            encrypt: encrypt && encryption_available,
+        }
+    }
+
+    /// Sets the format in which the search results are serialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `serializer` - It takes the serializer of the search results as an argument.
+    pub fn with_serializer(mut self, serializer: Serializer) -> Self {
+        self.serializer = serializer;
+        self
+    }
+
+    /// A getter function that gets the format in which the search results are serialized.
+    pub fn serializer(&self) -> Serializer {
+        self.serializer
+    }
+
+    /// A getter function that gets whether the search results are compressed.
+    pub fn compresses(&self) -> bool {
+        self.compress
+    }
+
+    /// A getter function that gets whether the search results are encrypted.
+    pub fn encrypts(&self) -> bool {
+        self.encrypt
+    }
+
+    /// Serializes, compresses and encrypts the search results according to the pipeline and
+    /// wraps them in the envelope. The time at which the search results were first cached is
+    /// kept when they were fetched from the cache, so that copying them between the caches does
+    /// not make them look fresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the search results as an argument.
+    /// * `search_results` - It takes the search results to be cached as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if any of the codecs fails.
+    pub async fn encode(
+        &self,
+        key: &CacheKey,
+        search_results: &SearchResults,
+    ) -> Result<Vec<u8>, Report<CacheError>> {
+        let flags = u8::from(self.compress) * COMPRESSED + u8::from(self.encrypt) * ENCRYPTED;
+        let mut envelope = Vec::with_capacity(HEADER_SIZE);
+        envelope.extend_from_slice(&MAGIC);
+        envelope.extend_from_slice(&[ENVELOPE_VERSION, self.serializer.id(), flags]);
+        envelope.extend_from_slice(
+            &search_results
+                .cached_at()
+                .unwrap_or_else(unix_time)
+                .to_le_bytes(),
+        );
+
+        let query = key.query().as_bytes();
+        let mut bytes = (query.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(query);
+        bytes.extend(self.serializer.serialize(search_results)?);
+
+        if self.compress {
+            bytes = compress(&bytes).await?;
+        }
+
+        if self.encrypt {
+            bytes = encrypt(&bytes, &envelope)?;
+        }
+
+        envelope.extend_from_slice(&bytes);
+        Ok(envelope)
+    }
+
+    /// Unwraps the envelope and reverts the codecs recorded in its header, whatever the codecs
+    /// of the pipeline are. The decoded search results carry the time at which they were first
+    /// cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the bytes stored in the cache as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if the envelope is malformed, if it records a codec which was not
+    /// compiled in or if any of the codecs fails.
+    pub async fn decode(&self, bytes: &[u8]) -> Result<SearchResults, Report<CacheError>> {
+        let (serializer, cached_at, payload) = self.open(bytes).await?;
+        let (_, serialized) = split_query(&payload)?;
+        let mut search_results = serializer.deserialize(serialized)?;
+        search_results.set_cached_at(cached_at);
+        Ok(search_results)
+    }
+
+    /// Unwraps the envelope and returns the normalized query the search results were cached
+    /// for, without deserializing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the bytes stored in the cache as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if the envelope is malformed, if it records a codec which was not
+    /// compiled in or if any of the codecs fails.
+    pub async fn decode_query(&self, bytes: &[u8]) -> Result<String, Report<CacheError>> {
+        let (_, _, payload) = self.open(bytes).await?;
+        let (query, _) = split_query(&payload)?;
+        Ok(query.to_owned())
+    }
+
+    /// A helper function which unwraps the envelope and reverts the codecs recorded in its
+    /// header.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the bytes stored in the cache as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the serializer of the search results, the time at which they were first cached
+    /// and the payload holding the query and the serialized search results.
+    async fn open(&self, bytes: &[u8]) -> Result<(Serializer, u64, Vec<u8>), Report<CacheError>> {
+        if bytes.len() < HEADER_SIZE || bytes[..MAGIC.len()] != MAGIC {
+            return Err(Report::new(CacheError::SerializationError)
+                .attach_printable("the cache entry has no envelope header"));
+        }
+        let (version, serializer_id, flags) = (bytes[2], bytes[3], bytes[4]);
+        if version != ENVELOPE_VERSION {
+            return Err(
+                Report::new(CacheError::SerializationError).attach_printable(format!(
+                    "the cache entry has the unknown envelope version {version}"
+                )),
+            );
+        }
+        let serializer = Serializer::from_id(serializer_id).ok_or_else(|| {
+            Report::new(CacheError::SerializationError).attach_printable(format!(
+                "the cache entry has the unknown serializer {serializer_id}"
+            ))
+        })?;
+        if flags & !(COMPRESSED | ENCRYPTED) != 0 {
+            return Err(
+                Report::new(CacheError::SerializationError).attach_printable(format!(
+                    "the cache entry has the unknown codec flags {flags:#010b}"
+                )),
+            );
+        }
+
+        let mut cached_at = [0; 8];
+        cached_at.copy_from_slice(&bytes[HEADER_SIZE - 8..HEADER_SIZE]);
+
+        let (header, payload) = bytes.split_at(HEADER_SIZE);
+        let mut payload = payload.to_vec();
+        if flags & ENCRYPTED != 0 {
+            payload = decrypt(&payload, header)?;
+        }
+        if flags & COMPRESSED != 0 {
+            payload = decompress(&payload).await?;
+        }
+        Ok((serializer, u64::from_le_bytes(cached_at), payload))
+    }
+}
+
+/// A helper function which splits the payload into the query and the serialized search
+/// results.
+///
+/// # Arguments
+///
+/// * `payload` - It takes the payload of the cache entry as an argument.
+///
+/// # Error
+///
+/// Returns a `SerializationError` if the payload is truncated or the query is not valid utf-8.
+fn split_query(payload: &[u8]) -> Result<(&str, &[u8]), Report<CacheError>> {
+    let truncated = || {
+        Report::new(CacheError::SerializationError)
+            .attach_printable("the cache entry payload is truncated")
+    };
+    let (length, rest) = payload.split_at_checked(4).ok_or_else(truncated)?;
+    let length = u32::from_le_bytes(length.try_into().unwrap_or_default()) as usize;
+    let (query, serialized) = rest.split_at_checked(length).ok_or_else(truncated)?;
+    let query = std::str::from_utf8(query).map_err(|_| CacheError::SerializationError)?;
+    Ok((query, serialized))
+}
+
+/// A helper function which returns the current unix time in seconds.
+pub(super) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A helper function which compresses the bytes with brotli.
+///
+/// # Arguments
+///
+/// * `bytes` - It takes the bytes to be compressed as an argument.
+///
+/// # Error
+///
+/// Returns a `CompressionError` if the compression fails.
+#[cfg(any(feature = "compress-cache-results", feature = "cec-cache-results"))]
+async fn compress(bytes: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    use tokio::io::AsyncWriteExt;
+    let mut writer = async_compression::tokio::write::BrotliEncoder::new(Vec::new());
+    writer
+        .write_all(bytes)
+        .await
+        .map_err(|_| CacheError::CompressionError)?;
+    writer
+        .shutdown()
+        .await
+        .map_err(|_| CacheError::CompressionError)?;
+    Ok(writer.into_inner())
+}
+
+/// A helper function which decompresses the bytes compressed with brotli.
+///
+/// # Arguments
+///
+/// * `bytes` - It takes the compressed bytes as an argument.
+///
+/// # Error
+///
+/// Returns a `CompressionError` if the bytes are not valid brotli.
+#[cfg(any(feature = "compress-cache-results", feature = "cec-cache-results"))]
+async fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    use tokio::io::AsyncWriteExt;
+    let mut writer = async_compression::tokio::write::BrotliDecoder::new(Vec::new());
+    writer
+        .write_all(bytes)
+        .await
+        .map_err(|_| CacheError::CompressionError)?;
+    writer
+        .shutdown()
+        .await
+        .map_err(|_| CacheError::CompressionError)?;
+    Ok(writer.into_inner())
+}
+
+/// A stand-in for the brotli compression when the compression feature was not compiled in.
+#[cfg(not(any(feature = "compress-cache-results", feature = "cec-cache-results")))]
+async fn compress(_bytes: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    Err(Report::new(CacheError::CompressionError)
+        .attach_printable("the app was compiled without the `compress-cache-results` feature"))
+}
+
+/// A stand-in for the brotli decompression when the compression feature was not compiled in.
+#[cfg(not(any(feature = "compress-cache-results", feature = "cec-cache-results")))]
+async fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    compress(bytes).await
+}
+
+/// A helper function which encrypts the bytes with the cache cipher.
+///
+/// # Arguments
+///
+/// * `bytes` - It takes the bytes to be encrypted as an argument.
+/// * `header` - It takes the envelope header to be authenticated along with the bytes as an
+///   argument.
+///
+/// # Error
+///
+/// Returns an `EncryptionError` if the encryption fails.
+#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+fn encrypt(bytes: &[u8], header: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    CIPHER
+        .get_or_init(CacheCipher::ephemeral)
+        .encrypt(bytes, header)
+}
+
+/// A helper function which decrypts the bytes encrypted with the cache cipher.
+///
+/// # Arguments
+///
+/// * `bytes` - It takes the encrypted bytes as an argument.
+/// * `header` - It takes the envelope header the bytes were encrypted with as an argument.
+///
+/// # Error
+///
+/// Returns an `EncryptionError` if no cipher was initialized or if the decryption fails.
+#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+fn decrypt(bytes: &[u8], header: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    CIPHER
+        .get()
+        .ok_or_else(|| {
+            Report::new(CacheError::EncryptionError)
+                .attach_printable("the cache encryption is disabled")
+        })?
+        .decrypt(bytes, header)
+}
+
+/// A stand-in for the encryption when the encryption feature was not compiled in.
+#[cfg(not(any(feature = "encrypt-cache-results", feature = "cec-cache-results")))]
+fn encrypt(_bytes: &[u8], _header: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    Err(Report::new(CacheError::EncryptionError)
+        .attach_printable("the app was compiled without the `encrypt-cache-results` feature"))
+}
+
+/// A stand-in for the decryption when the encryption feature was not compiled in.
+#[cfg(not(any(feature = "encrypt-cache-results", feature = "cec-cache-results")))]
+fn decrypt(bytes: &[u8], header: &[u8]) -> Result<Vec<u8>, Report<CacheError>> {
+    encrypt(bytes, header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::aggregation_models::SearchResult;
+
+    const COMBINATIONS: [(bool, bool); 4] =
+        [(false, false), (true, false), (false, true), (true, true)];
+
+    fn search_results() -> SearchResults {
+        SearchResults::new(
+            vec![SearchResult::new(
+                "Rust",
+                "https://www.rust-lang.org/",
+                "A language empowering everyone to build reliable and efficient software.",
+                &["bing", "brave"],
+            )],
+            &[],
+        )
+    }
+
+    fn key() -> CacheKey {
+        CacheKey::new("Rust  Programming", 0, 0, ["bing"])
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_for_every_combination() {
+        let compression_available = cfg!(any(
+            feature = "compress-cache-results",
+            feature = "cec-cache-results"
+        ));
+        let encryption_available = cfg!(any(
+            feature = "encrypt-cache-results",
+            feature = "cec-cache-results"
+        ));
+
+        for (compress, encrypt) in COMBINATIONS {
+            let pipeline = CodecPipeline::with_codecs(compress, encrypt);
+            assert_eq!(pipeline.compresses(), compress && compression_available);
+            assert_eq!(pipeline.encrypts(), encrypt && encryption_available);
+
+            let encoded = pipeline.encode(&key(), &search_results()).await.unwrap();
+            let expected_flags = u8::from(pipeline.compresses()) * COMPRESSED
+                + u8::from(pipeline.encrypts()) * ENCRYPTED;
+            assert_eq!(
+                encoded[..5],
+                [b'W', b'X', 4, pipeline.serializer().id(), expected_flags]
+            );
+
+            let query = pipeline.decode_query(&encoded).await.unwrap();
+            assert_eq!(query, "rust programming");
+            let decoded = pipeline.decode(&encoded).await.unwrap();
+            assert_eq!(decoded.results[0].url, "https://www.rust-lang.org/");
+            assert_eq!(decoded.results[0].engine.len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_decode_after_the_codecs_change() {
+        for (compress, encrypt) in COMBINATIONS {
+            let encoded = CodecPipeline::with_codecs(compress, encrypt)
+                .encode(&key(), &search_results())
+                .await
+                .unwrap();
+
+            for (other_compress, other_encrypt) in COMBINATIONS {
+                let decoded = CodecPipeline::with_codecs(other_compress, other_encrypt)
+                    .decode(&encoded)
+                    .await
+                    .unwrap();
+                assert_eq!(decoded.results.len(), 1);
+            }
+        }
+    }
+
+    #[cfg(feature = "msgpack-cache-results")]
+    #[tokio::test]
+    async fn test_entries_decode_after_the_serializer_changes() {
+        let mut results = search_results();
+        results.results[0].original_url = Some("https://rust-lang.org/".to_owned());
+        let (json, message_pack) = (
+            CodecPipeline::with_codecs(false, false).with_serializer(Serializer::Json),
+            CodecPipeline::with_codecs(false, false).with_serializer(Serializer::MessagePack),
+        );
+
+        let json_entry = json.encode(&key(), &results).await.unwrap();
+        let message_pack_entry = message_pack.encode(&key(), &results).await.unwrap();
+        assert!(message_pack_entry.len() < json_entry.len());
+
+        for entry in [&json_entry, &message_pack_entry] {
+            for pipeline in [json, message_pack] {
+                let decoded = pipeline.decode(entry).await.unwrap();
+                assert_eq!(
+                    decoded.results[0].original_url,
+                    results.results[0].original_url
+                );
+                assert!(!decoded.results[0].blurred);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_envelopes() {
+        let pipeline = CodecPipeline::with_codecs(false, false);
+        let encoded = pipeline.encode(&key(), &search_results()).await.unwrap();
+
+        // Entries written before the envelope was introduced are plain json.
+        let legacy = serde_json::to_vec(&search_results()).unwrap();
+        assert!(pipeline.decode(&legacy).await.is_err());
+        assert!(pipeline.decode(&encoded[..3]).await.is_err());
+
+        for (index, value) in [(2, 1), (3, 9), (4, 0b100)] {
+            let mut malformed = encoded.clone();
+            malformed[index] = value;
+            assert!(pipeline.decode(&malformed).await.is_err(), "byte {index}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_at_is_kept_across_copies() {
+        let pipeline = CodecPipeline::with_codecs(false, false);
+        let mut results = search_results();
+        results.set_cached_at(1_700_000_000);
+
+        let decoded = pipeline
+            .decode(&pipeline.encode(&key(), &results).await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(decoded.cached_at(), Some(1_700_000_000));
+
+        let fresh = pipeline
+            .decode(&pipeline.encode(&key(), &search_results()).await.unwrap())
+            .await
+            .unwrap();
+        assert!(fresh.cached_at().unwrap() > 1_700_000_000);
+    }
+
+    #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+    #[tokio::test]
+    async fn test_tampered_encrypted_entries() {
+        for compress in [false, true] {
+            let pipeline = CodecPipeline::with_codecs(compress, true);
+            let encoded = pipeline.encode(&key(), &search_results()).await.unwrap();
+
+            // The compression flag, the cached at time and the last byte of the ciphertext.
+            for index in [4, HEADER_SIZE - 1, encoded.len() - 1] {
+                let mut tampered = encoded.clone();
+                tampered[index] ^= 1;
+                assert!(pipeline.decode(&tampered).await.is_err(), "byte {index}");
+            }
+        }
+    }
+}
@@ -0,0 +1,579 @@
+//! This module provides a cache backend which stores the aggregated search results as files in a
+//! directory, so that the cache survives restarts without needing a redis server. The entries
+//! expire after the cache expiry time and the least recently used entries are evicted once the
+//! directory grows above its size limit.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::UNIX_EPOCH,
+};
+
+use error_stack::Report;
+use tokio::{fs, io::AsyncReadExt};
+
+use super::{
+    cache_key::CacheKey,
+    codec::{unix_time, CodecPipeline},
+    error::CacheError,
+};
+use crate::models::aggregation_models::SearchResults;
+
+/// The extension of the files storing the cache entries.
+const ENTRY_EXTENSION: &str = ".entry";
+
+/// The extension of the temporary files the entries are written to before being renamed.
+const TEMPORARY_EXTENSION: &str = ".tmp";
+
+/// The size (in bytes) of the expiry timestamp stored in front of every entry.
+const EXPIRY_SIZE: usize = 8;
+
+/// A counter used to give unique names to the temporary files written concurrently.
+static TEMPORARY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A named struct which stores the information of a cache entry needed for the eviction.
+struct IndexEntry {
+    /// It stores the size (in bytes) of the entry file.
+    size: u64,
+    /// It stores the tick of the last access to the entry, the lowest tick being the least
+    /// recently used entry.
+    last_access: u64,
+}
+
+/// A named struct which stores the index of the entries present in the cache directory.
+#[derive(Default)]
+struct DiskIndex {
+    /// It stores the entries mapped to the hashes of their keys.
+    entries: HashMap<String, IndexEntry>,
+    /// It stores the hashes of the entries ordered by the tick of their last access, the first
+    /// one being the least recently used entry.
+    by_last_access: BTreeMap<u64, String>,
+    /// It stores the total size (in bytes) of the entry files.
+    total_size: u64,
+    /// It stores the tick given to the next accessed entry.
+    clock: u64,
+}
+
+impl DiskIndex {
+    /// Records an access to the entry, inserting it or updating its size when it was written.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the hash of the key of the entry as an argument.
+    /// * `size` - It takes the size of the written entry file, or `None` when the entry was
+    /// only read, as an argument.
+    fn touch(&mut self, hash: &str, size: Option<u64>) {
+        self.clock += 1;
+        let clock = self.clock;
+        match (self.entries.get_mut(hash), size) {
+            (Some(entry), size) => {
+                if let Some(hash) = self.by_last_access.remove(&entry.last_access) {
+                    self.by_last_access.insert(clock, hash);
+                }
+                entry.last_access = clock;
+                if let Some(size) = size {
+                    self.total_size = self.total_size - entry.size + size;
+                    entry.size = size;
+                }
+            }
+            (None, Some(size)) => {
+                self.total_size += size;
+                self.by_last_access.insert(clock, hash.to_owned());
+                self.entries.insert(
+                    hash.to_owned(),
+                    IndexEntry {
+                        size,
+                        last_access: clock,
+                    },
+                );
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Removes the entry from the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the hash of the key of the entry as an argument.
+    fn remove(&mut self, hash: &str) {
+        if let Some(entry) = self.entries.remove(hash) {
+            self.total_size -= entry.size;
+            self.by_last_access.remove(&entry.last_access);
+        }
+    }
+
+    /// Removes the least recently used entries from the index until the total size fits in the
+    /// given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - It takes the maximum total size (in bytes) of the entries as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the hashes of the removed entries, whose files should be deleted.
+    fn evict(&mut self, max_size: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_size > max_size {
+            match self.by_last_access.pop_first() {
+                Some((_, hash)) => {
+                    self.remove(&hash);
+                    evicted.push(hash);
+                }
+                None => break,
+            }
+        }
+        evicted
+    }
+}
+
+/// Disk based cache backend.
+pub struct DiskCache {
+    /// It stores the directory the entry files are stored in.
+    directory: PathBuf,
+    /// It stores the maximum total size (in bytes) of the entry files.
+    max_size: u64,
+    /// It stores the time to live (in seconds) of the entries.
+    cache_ttl: u16,
+    /// It stores the codec pipeline used to encode the search results before they are stored.
+    codec: CodecPipeline,
+    /// It stores the index of the entries, which is never locked while a file is accessed.
+    index: Mutex<DiskIndex>,
+}
+
+impl DiskCache {
+    /// Opens the disk cache stored in the given directory, creating the directory if needed.
+    /// The expired entries and the temporary files left over by an interrupted write are
+    /// removed, and the entries written last are considered the most recently used ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - It takes the directory the entries are stored in as an argument.
+    /// * `max_size` - It takes the maximum total size (in bytes) of the entries as an argument.
+    /// * `cache_ttl` - It takes the time to live (in seconds) of the entries as an argument.
+    /// * `codec` - It takes the codec pipeline used to encode the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an io error if the directory cannot be created or read.
+    pub async fn new(
+        directory: PathBuf,
+        max_size: u64,
+        cache_ttl: u16,
+        codec: CodecPipeline,
+    ) -> Result<Self, std::io::Error> {
+        fs::create_dir_all(&directory).await?;
+
+        let now = unix_time();
+        let mut entries = Vec::new();
+        let mut read_dir = fs::read_dir(&directory).await?;
+        while let Some(dir_entry) = read_dir.next_entry().await? {
+            let path = dir_entry.path();
+            let file_name = dir_entry.file_name().to_string_lossy().into_owned();
+
+            if file_name.ends_with(TEMPORARY_EXTENSION) {
+                remove_file(&path).await;
+                continue;
+            }
+            let Some(hash) = file_name.strip_suffix(ENTRY_EXTENSION) else {
+                continue;
+            };
+            match read_expiry(&path).await {
+                Some(expires_at) if expires_at > now => (),
+                _ => {
+                    remove_file(&path).await;
+                    continue;
+                }
+            }
+
+            let metadata = dir_entry.metadata().await?;
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            entries.push((modified, hash.to_owned(), metadata.len()));
+        }
+
+        // The entries written last are the most recently used ones.
+        entries.sort_unstable();
+        let mut index = DiskIndex::default();
+        for (_, hash, size) in entries {
+            index.touch(&hash, Some(size));
+        }
+
+        let disk_cache = DiskCache {
+            directory,
+            max_size,
+            cache_ttl,
+            codec,
+            index: Mutex::new(index),
+        };
+        disk_cache.evict().await;
+        Ok(disk_cache)
+    }
+
+    /// Returns the path of the file storing the entry with the given hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the hash of the key of the entry as an argument.
+    fn entry_path(&self, hash: &str) -> PathBuf {
+        self.directory.join(format!("{hash}{ENTRY_EXTENSION}"))
+    }
+
+    /// A helper function which locks the index of the entries.
+    fn index(&self) -> std::sync::MutexGuard<'_, DiskIndex> {
+        self.index.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Removes the entry with the given hash from the index and the disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - It takes the hash of the key of the entry as an argument.
+    async fn remove(&self, hash: &str) {
+        self.index().remove(hash);
+        remove_file(&self.entry_path(hash)).await;
+    }
+
+    /// Removes the least recently used entries until the cache fits in its size limit.
+    async fn evict(&self) {
+        let evicted = self.index().evict(self.max_size);
+        for hash in evicted {
+            log::debug!("Evicted the disk cache entry {}", hash);
+            remove_file(&self.entry_path(&hash)).await;
+        }
+    }
+
+    /// Fetches the cached results stored under the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `MissingValue` error if the entry does not exist or has expired, or a
+    /// `CacheError` if it cannot be read or decoded.
+    pub async fn cached_results(
+        &self,
+        key: &CacheKey,
+    ) -> Result<SearchResults, Report<CacheError>> {
+        let hash = key.hash();
+        let bytes = match fs::read(self.entry_path(&hash)).await {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                self.index().remove(&hash);
+                return Err(Report::new(CacheError::MissingValue));
+            }
+            Err(error) => return Err(Report::new(CacheError::DiskError(error))),
+        };
+
+        let expires_at = match bytes.get(..EXPIRY_SIZE) {
+            Some(expiry) => u64::from_le_bytes(expiry.try_into().unwrap_or_default()),
+            None => 0,
+        };
+        if expires_at <= unix_time() {
+            self.remove(&hash).await;
+            return Err(Report::new(CacheError::MissingValue));
+        }
+
+        self.index().touch(&hash, None);
+        self.codec.decode(&bytes[EXPIRY_SIZE..]).await
+    }
+
+    /// Stores the search results under the given keys. Every entry is written to a temporary
+    /// file which is then renamed, so that a crash never leaves a partially written entry
+    /// behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `search_results` - It takes the search results to be cached as an argument.
+    /// * `keys` - It takes the cache keys of the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if the search results cannot be encoded or written.
+    pub async fn cache_results(
+        &self,
+        search_results: &[SearchResults],
+        keys: &[CacheKey],
+    ) -> Result<(), Report<CacheError>> {
+        let expires_at = unix_time() + u64::from(self.cache_ttl);
+
+        for (key, search_result) in keys.iter().zip(search_results.iter()) {
+            let hash = key.hash();
+            let mut bytes = expires_at.to_le_bytes().to_vec();
+            bytes.extend(self.codec.encode(key, search_result).await?);
+
+            let temporary_path = self.directory.join(format!(
+                "{hash}.{}.{}{TEMPORARY_EXTENSION}",
+                std::process::id(),
+                TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            if let Err(error) = fs::write(&temporary_path, &bytes).await {
+                remove_file(&temporary_path).await;
+                return Err(Report::new(CacheError::DiskError(error)));
+            }
+            if let Err(error) = fs::rename(&temporary_path, self.entry_path(&hash)).await {
+                remove_file(&temporary_path).await;
+                return Err(Report::new(CacheError::DiskError(error)));
+            }
+
+            self.index().touch(&hash, Some(bytes.len() as u64));
+        }
+
+        self.evict().await;
+        Ok(())
+    }
+
+    /// A getter function that gets the number of entries and their total size (in bytes).
+    pub fn entry_count_and_size(&self) -> (u64, u64) {
+        let index = self.index();
+        (index.entries.len() as u64, index.total_size)
+    }
+
+    /// Removes the entries whose normalized query starts with the given prefix. The entries
+    /// which cannot be read or decoded are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_prefix` - It takes the normalized prefix of the queries to be removed as an
+    /// argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed entries.
+    pub async fn delete_matching(&self, query_prefix: &str) -> u64 {
+        let hashes: Vec<String> = self.index().entries.keys().cloned().collect();
+
+        let mut deleted = 0;
+        for hash in hashes {
+            let Ok(bytes) = fs::read(self.entry_path(&hash)).await else {
+                continue;
+            };
+            let envelope = bytes.get(EXPIRY_SIZE..).unwrap_or_default();
+            match self.codec.decode_query(envelope).await {
+                Ok(query) if query.starts_with(query_prefix) => (),
+                _ => continue,
+            }
+            self.remove(&hash).await;
+            deleted += 1;
+        }
+        deleted
+    }
+
+    /// Removes all the entries, including the ones which cannot be read or decoded, by removing
+    /// the cache directory and creating it again.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of removed entries if nothing goes wrong otherwise returns a
+    /// `DiskError` if the directory cannot be removed or created again.
+    pub async fn delete_all(&self) -> Result<u64, Report<CacheError>> {
+        let index = std::mem::take(&mut *self.index());
+        match fs::remove_dir_all(&self.directory).await {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                return Err(Report::new(CacheError::DiskError(error)))
+            }
+            _ => (),
+        }
+        fs::create_dir_all(&self.directory)
+            .await
+            .map_err(|error| Report::new(CacheError::DiskError(error)))?;
+        Ok(index.entries.len() as u64)
+    }
+}
+
+/// A helper function which reads the expiry timestamp of the entry file.
+///
+/// # Arguments
+///
+/// * `path` - It takes the path of the entry file as an argument.
+///
+/// # Returns
+///
+/// Returns `None` if the file cannot be read or is too short.
+async fn read_expiry(path: &Path) -> Option<u64> {
+    let mut expiry = [0; EXPIRY_SIZE];
+    fs::File::open(path)
+        .await
+        .ok()?
+        .read_exact(&mut expiry)
+        .await
+        .ok()?;
+    Some(u64::from_le_bytes(expiry))
+}
+
+/// A helper function which removes a file, ignoring the file being already removed.
+///
+/// # Arguments
+///
+/// * `path` - It takes the path of the file to be removed as an argument.
+async fn remove_file(path: &Path) {
+    if let Err(error) = fs::remove_file(path).await {
+        if error.kind() != ErrorKind::NotFound {
+            log::error!("Failed to remove {}: {}", path.display(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::aggregation_models::SearchResult;
+
+    fn search_results(title: &str) -> SearchResults {
+        SearchResults::new(
+            vec![SearchResult::new(
+                title,
+                "https://www.rust-lang.org/",
+                "A language empowering everyone to build reliable and efficient software.",
+                &["bing"],
+            )],
+            &[],
+        )
+    }
+
+    fn key(query: &str) -> CacheKey {
+        CacheKey::new(query, 0, 0, ["bing"])
+    }
+
+    async fn disk_cache(directory: &Path, max_size: u64, cache_ttl: u16) -> DiskCache {
+        DiskCache::new(
+            directory.to_path_buf(),
+            max_size,
+            cache_ttl,
+            CodecPipeline::with_codecs(true, true),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_entries_survive_restarts() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = disk_cache(directory.path(), u64::MAX, 60).await;
+        cache
+            .cache_results(&[search_results("Rust")], &[key("rust")])
+            .await
+            .unwrap();
+        drop(cache);
+
+        let cache = disk_cache(directory.path(), u64::MAX, 60).await;
+        let cached = cache.cached_results(&key("rust")).await.unwrap();
+        assert_eq!(cached.results[0].title, "Rust");
+        assert!(cache.cached_results(&key("go")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = disk_cache(directory.path(), u64::MAX, 0).await;
+        cache
+            .cache_results(&[search_results("Rust")], &[key("rust")])
+            .await
+            .unwrap();
+
+        assert!(cache.cached_results(&key("rust")).await.is_err());
+        assert!(!cache.entry_path(&key("rust").hash()).exists());
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_entries_are_evicted() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = disk_cache(directory.path(), u64::MAX, 60).await;
+        cache
+            .cache_results(&[search_results("Rust")], &[key("rust")])
+            .await
+            .unwrap();
+        let entry_size = cache.index().total_size;
+
+        // Leave room for two entries and a half.
+        let cache = disk_cache(directory.path(), entry_size * 5 / 2, 60).await;
+        cache
+            .cache_results(&[search_results("Rust")], &[key("go")])
+            .await
+            .unwrap();
+        assert!(cache.cached_results(&key("rust")).await.is_ok());
+        cache
+            .cache_results(&[search_results("Rust")], &[key("zig")])
+            .await
+            .unwrap();
+
+        assert!(cache.cached_results(&key("rust")).await.is_ok());
+        assert!(cache.cached_results(&key("go")).await.is_err());
+        assert!(cache.cached_results(&key("zig")).await.is_ok());
+        assert!(cache.index().total_size <= entry_size * 5 / 2);
+    }
+
+    #[test]
+    fn test_index_evicts_in_access_order() {
+        let mut index = DiskIndex::default();
+        for hash in ["a", "b", "c", "d"] {
+            index.touch(hash, Some(10));
+        }
+        index.touch("a", None);
+        index.touch("c", Some(20));
+        index.remove("d");
+
+        assert_eq!(index.evict(20), ["b", "a"]);
+        assert_eq!(index.total_size, 20);
+        assert_eq!(index.evict(0), ["c"]);
+        assert!(index.entries.is_empty() && index.by_last_access.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_deleted_by_query_prefix() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = disk_cache(directory.path(), u64::MAX, 60).await;
+        cache
+            .cache_results(
+                &[search_results("Rust"), search_results("Go")],
+                &[key("rust"), key("go")],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(cache.delete_matching("ru").await, 1);
+        assert!(!cache.entry_path(&key("rust").hash()).exists());
+        assert!(cache.cached_results(&key("go")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_clear_removes_the_undecodable_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let corrupted_path = directory
+            .path()
+            .join(format!("{}{ENTRY_EXTENSION}", key("go").hash()));
+        std::fs::write(&corrupted_path, b"corrupted entry").unwrap();
+        let cache = disk_cache(directory.path(), u64::MAX, 60).await;
+        cache
+            .cache_results(&[search_results("Rust")], &[key("rust")])
+            .await
+            .unwrap();
+
+        assert_eq!(cache.delete_matching("").await, 1);
+        assert!(corrupted_path.exists());
+
+        assert_eq!(cache.delete_all().await.unwrap(), 1);
+        assert!(!corrupted_path.exists());
+        assert!(directory.path().exists());
+        assert_eq!(cache.entry_count_and_size(), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_leftover_temporary_files_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let temporary_path = directory
+            .path()
+            .join(format!("abc.1.0{TEMPORARY_EXTENSION}"));
+        std::fs::write(&temporary_path, b"partial").unwrap();
+
+        disk_cache(directory.path(), u64::MAX, 60).await;
+        assert!(!temporary_path.exists());
+    }
+}
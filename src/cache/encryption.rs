@@ -1,25 +1,265 @@
+//! This module provides the cipher used to encrypt the cached search results with
+//! ChaCha20-Poly1305. Every entry is encrypted with a fresh random nonce which is stored
+//! alongside the ciphertext together with the id of the key used, so that the entries can be
+//! decrypted by every websurfx instance sharing the keys, across restarts and key rotations.
+//! The caller can also authenticate some associated data which is stored in the clear, such as
+//! the envelope header of the cache entries, so that it cannot be changed without the decryption
+//! failing.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
 use chacha20poly1305::{
-    consts::{B0, B1},
-    ChaChaPoly1305,
-};
-use std::sync::OnceLock;
-
-use chacha20::{
-    cipher::{
-        generic_array::GenericArray,
-        typenum::{UInt, UTerm},
-        StreamCipherCoreWrapper,
-    },
-    ChaChaCore,
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
 };
+use error_stack::Report;
+
+use super::error::CacheError;
+
+/// The size (in bytes) of the nonce stored in front of every encrypted entry.
+const NONCE_SIZE: usize = 12;
+
+/// The id of the random key generated on startup when no encryption key is configured.
+const EPHEMERAL_KEY_ID: &str = "ephemeral";
+
+/// Our cache cipher instance, initialized once from the configured keys.
+pub static CIPHER: OnceLock<CacheCipher> = OnceLock::new();
+
+/// A named struct which stores the keys used to encrypt and decrypt the cached search results.
+///
+/// The encrypted entries are laid out as `[key id length][key id][nonce][ciphertext and tag]`.
+pub struct CacheCipher {
+    /// It stores the id of the key used to encrypt the new entries.
+    current_key_id: String,
+    /// It stores the ciphers of all the known keys mapped to their ids, so that the entries
+    /// encrypted with a previous key can still be decrypted after a key rotation.
+    ciphers: HashMap<String, ChaCha20Poly1305>,
+}
+
+impl CacheCipher {
+    /// Constructs a new `CacheCipher` from a list of keys written as `<key id>:<base64 key>`,
+    /// where the key is 32 bytes long once decoded. The first key is used to encrypt the new
+    /// entries and all of the keys are used to decrypt the existing ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - It takes the list of keys as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an `EncryptionKeyError` if no key is provided, if a key is malformed or if two keys
+    /// share the same id.
+    pub fn from_keys<S: AsRef<str>>(keys: &[S]) -> Result<Self, Report<CacheError>> {
+        let mut ciphers = HashMap::new();
+        let mut current_key_id = None;
+
+        for key in keys {
+            let (id, encoded_key) = key.as_ref().trim().split_once(':').ok_or_else(|| {
+                Report::new(CacheError::EncryptionKeyError)
+                    .attach_printable("the key should be written as `<key id>:<base64 key>`")
+            })?;
+            if id.is_empty() || id.len() > u8::MAX as usize {
+                return Err(Report::new(CacheError::EncryptionKeyError)
+                    .attach_printable("the key id should be 1 to 255 bytes long"));
+            }
+
+            let key_bytes = STANDARD.decode(encoded_key).map_err(|_| {
+                Report::new(CacheError::EncryptionKeyError)
+                    .attach_printable(format!("the key `{id}` is not valid base64"))
+            })?;
+            if key_bytes.len() != 32 {
+                return Err(Report::new(CacheError::EncryptionKeyError)
+                    .attach_printable(format!("the key `{id}` should be 32 bytes long")));
+            }
+
+            let cipher = ChaCha20Poly1305::new(Key::from_slice(&key_bytes));
+            if ciphers.insert(id.to_owned(), cipher).is_some() {
+                return Err(Report::new(CacheError::EncryptionKeyError)
+                    .attach_printable(format!("the key id `{id}` is used more than once")));
+            }
+            current_key_id.get_or_insert_with(|| id.to_owned());
+        }
+
+        match current_key_id {
+            Some(current_key_id) => Ok(CacheCipher {
+                current_key_id,
+                ciphers,
+            }),
+            None => Err(Report::new(CacheError::EncryptionKeyError)
+                .attach_printable("no encryption key was provided")),
+        }
+    }
+
+    /// Constructs a new `CacheCipher` with a random key which only lives as long as the
+    /// process, which means that the cached entries cannot be shared with other instances or
+    /// decrypted after a restart.
+    pub fn ephemeral() -> Self {
+        CacheCipher {
+            current_key_id: EPHEMERAL_KEY_ID.to_owned(),
+            ciphers: HashMap::from([(
+                EPHEMERAL_KEY_ID.to_owned(),
+                ChaCha20Poly1305::new(&ChaCha20Poly1305::generate_key(&mut OsRng)),
+            )]),
+        }
+    }
+
+    /// Encrypts the bytes with the current key and a fresh random nonce, authenticating the
+    /// associated data along with them.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the bytes to be encrypted as an argument.
+    /// * `associated_data` - It takes the data which is stored in the clear but should not be
+    ///   changed without the decryption failing as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an `EncryptionError` if the encryption fails.
+    pub fn encrypt(
+        &self,
+        bytes: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, Report<CacheError>> {
+        let cipher = &self.ciphers[&self.current_key_id];
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng); // 96-bits; unique per message
+        let payload = Payload {
+            msg: bytes,
+            aad: associated_data,
+        };
+        let ciphertext = cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| CacheError::EncryptionError)?;
+
+        let mut encrypted =
+            Vec::with_capacity(1 + self.current_key_id.len() + NONCE_SIZE + ciphertext.len());
+        encrypted.push(self.current_key_id.len() as u8);
+        encrypted.extend_from_slice(self.current_key_id.as_bytes());
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    /// Decrypts the bytes encrypted by the `encrypt` function with any of the known keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - It takes the encrypted bytes as an argument.
+    /// * `associated_data` - It takes the associated data the bytes were encrypted with as an
+    ///   argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an `EncryptionError` if the bytes are truncated, were encrypted with an unknown
+    /// key or if either the bytes or the associated data were tampered with.
+    pub fn decrypt(
+        &self,
+        bytes: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, Report<CacheError>> {
+        let (&id_length, rest) = bytes.split_first().ok_or(CacheError::EncryptionError)?;
+        if rest.len() < id_length as usize + NONCE_SIZE {
+            return Err(Report::new(CacheError::EncryptionError)
+                .attach_printable("the encrypted entry is truncated"));
+        }
+        let (id, rest) = rest.split_at(id_length as usize);
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+
+        let cipher = std::str::from_utf8(id)
+            .ok()
+            .and_then(|id| self.ciphers.get(id))
+            .ok_or_else(|| {
+                Report::new(CacheError::EncryptionError).attach_printable(format!(
+                    "the entry was encrypted with the unknown key `{}`",
+                    String::from_utf8_lossy(id)
+                ))
+            })?;
+
+        let payload = Payload {
+            msg: ciphertext,
+            aad: associated_data,
+        };
+        Ok(cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| CacheError::EncryptionError)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> String {
+        format!("{id}:{}", STANDARD.encode([byte; 32]))
+    }
+
+    #[test]
+    fn test_round_trip_with_fresh_nonces() {
+        let cipher = CacheCipher::from_keys(&[key("2024", 1)]).unwrap();
+
+        let first = cipher.encrypt(b"search results", b"header").unwrap();
+        let second = cipher.encrypt(b"search results", b"header").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            cipher.decrypt(&first, b"header").unwrap(),
+            b"search results"
+        );
+        assert_eq!(
+            cipher.decrypt(&second, b"header").unwrap(),
+            b"search results"
+        );
+    }
+
+    #[test]
+    fn test_shared_keys_decrypt_other_instances_entries() {
+        let instance = CacheCipher::from_keys(&[key("2024", 1)]).unwrap();
+        let other_instance = CacheCipher::from_keys(&[key("2024", 1)]).unwrap();
+
+        let encrypted = instance.encrypt(b"search results", b"header").unwrap();
+        assert_eq!(
+            other_instance.decrypt(&encrypted, b"header").unwrap(),
+            b"search results"
+        );
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = CacheCipher::from_keys(&[key("2024", 1)]).unwrap();
+        let rotated = CacheCipher::from_keys(&[key("2025", 2), key("2024", 1)]).unwrap();
+
+        let old_entry = old.encrypt(b"old", b"").unwrap();
+        let new_entry = rotated.encrypt(b"new", b"").unwrap();
+        assert_eq!(&new_entry[1..5], b"2025");
+        assert_eq!(rotated.decrypt(&old_entry, b"").unwrap(), b"old");
+        assert_eq!(rotated.decrypt(&new_entry, b"").unwrap(), b"new");
+        assert!(old.decrypt(&new_entry, b"").is_err());
+    }
+
+    #[test]
+    fn test_tamper_detection() {
+        let cipher = CacheCipher::from_keys(&[key("2024", 1)]).unwrap();
+        let encrypted = cipher.encrypt(b"search results", b"header").unwrap();
 
-/// The ChaCha20 core wrapped in a stream cipher for use in ChaCha20-Poly1305 authenticated encryption.
-type StreamCipherCoreWrapperType =
-    StreamCipherCoreWrapper<ChaChaCore<UInt<UInt<UInt<UInt<UTerm, B1>, B0>, B1>, B0>>>;
-/// Our ChaCha20-Poly1305 cipher instance, lazily initialized.
-pub static CIPHER: OnceLock<ChaChaPoly1305<StreamCipherCoreWrapperType>> = OnceLock::new();
+        for index in [0, 3, 1 + 4 + 2, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[index] ^= 1;
+            assert!(
+                cipher.decrypt(&tampered, b"header").is_err(),
+                "byte {index}"
+            );
+        }
+        assert!(cipher.decrypt(&encrypted[..10], b"header").is_err());
+        assert!(cipher.decrypt(&[], b"header").is_err());
+        assert!(cipher.decrypt(&encrypted, b"headex").is_err());
+        assert!(cipher.decrypt(&encrypted, b"").is_err());
+    }
 
-/// The type alias for our encryption key, a 32-byte array.
-type GenericArrayType = GenericArray<u8, UInt<UInt<UInt<UInt<UTerm, B1>, B1>, B0>, B0>>;
-/// Our encryption key, lazily initialized.
-pub static ENCRYPTION_KEY: OnceLock<GenericArrayType> = OnceLock::new();
+    #[test]
+    fn test_invalid_keys() {
+        assert!(CacheCipher::from_keys::<&str>(&[]).is_err());
+        assert!(CacheCipher::from_keys(&["missing separator"]).is_err());
+        assert!(CacheCipher::from_keys(&["2024:not base64!"]).is_err());
+        assert!(CacheCipher::from_keys(&[format!("2024:{}", STANDARD.encode([1; 16]))]).is_err());
+        assert!(CacheCipher::from_keys(&[key("2024", 1), key("2024", 2)]).is_err());
+    }
+}
@@ -20,10 +20,13 @@ pub enum CacheError {
     MissingValue,
     /// whenever encryption or decryption of cache results fails
     EncryptionError,
+    /// Whenever the configured cache encryption keys are missing or malformed
+    EncryptionKeyError,
     /// Whenever compression of  the cache results fails
     CompressionError,
-    /// Whenever base64 decoding failed
-    Base64DecodingOrEncodingError,
+    /// This variant handles all the io errors of the disk cache.
+    #[cfg(feature = "disk-cache")]
+    DiskError(std::io::Error),
 }
 
 impl fmt::Display for CacheError {
@@ -54,12 +57,17 @@ impl fmt::Display for CacheError {
                 write!(f, "Failed to encrypt or decrypt cache-results")
             }
 
+            CacheError::EncryptionKeyError => {
+                write!(f, "Invalid cache encryption key")
+            }
+
             CacheError::CompressionError => {
                 write!(f, "failed to compress or uncompress cache results")
             }
 
-            CacheError::Base64DecodingOrEncodingError => {
-                write!(f, "base64 encoding or decoding failed")
+            #[cfg(feature = "disk-cache")]
+            CacheError::DiskError(io_error) => {
+                write!(f, "Failed to access the disk cache: {}", io_error)
             }
         }
     }
@@ -1,11 +1,18 @@
 //! This module provides the modules which provide the functionality to cache the aggregated
 //! results fetched and aggregated from the upstream search engines in a json format.
+pub mod cache_key;
 pub mod cacher;
+pub mod codec;
 
+#[cfg(feature = "disk-cache")]
+pub mod disk_cacher;
 #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
 /// encryption module contains encryption utils such the cipher and key
 pub mod encryption;
 pub mod error;
+pub mod page_cache;
+pub mod prefetch;
 
 #[cfg(feature = "redis-cache")]
 pub mod redis_cacher;
+pub mod single_flight;
@@ -0,0 +1,124 @@
+//! This module provides the cache of the rendered search pages, which lets the cached search
+//! results be served without rendering the page template again, along with the entity tags used
+//! to answer the conditional requests of the browsers.
+
+#[cfg(feature = "memory-cache")]
+use std::{sync::Arc, time::Duration};
+
+#[cfg(feature = "memory-cache")]
+use moka::sync::Cache as MokaCache;
+
+/// A named struct which stores a rendered search page along with its entity tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPage {
+    /// It stores the quoted entity tag of the page, which is derived from its html.
+    pub etag: String,
+    /// It stores the html of the page.
+    pub html: String,
+}
+
+impl RenderedPage {
+    /// Constructs a new `RenderedPage` from its html, the entity tag being the hash of the html
+    /// so that identical pages share the same tag.
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - It takes the html of the rendered page as an argument.
+    pub fn new(html: String) -> Self {
+        RenderedPage {
+            etag: format!("\"{}\"", blake3::hash(html.as_bytes())),
+            html,
+        }
+    }
+
+    /// Checks whether the page matches the value of an `If-None-Match` header, in which case the
+    /// copy held by the browser is still up to date. The weak entity tags are compared weakly as
+    /// required for the `If-None-Match` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `if_none_match` - It takes the value of the `If-None-Match` header as an argument.
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|etag| etag == "*" || etag.strip_prefix("W/").unwrap_or(etag) == self.etag)
+    }
+}
+
+/// Memory based cache of the rendered search pages, keyed by the page hash of their cache key.
+#[cfg(feature = "memory-cache")]
+pub struct PageCache {
+    /// The backend cache which stores the rendered pages.
+    pages: MokaCache<String, Arc<RenderedPage>>,
+}
+
+#[cfg(feature = "memory-cache")]
+impl PageCache {
+    /// Constructs a new `PageCache` with the given expiry time and size limit. The size of a page
+    /// is the size of its key and of its html, and the least recently used pages are evicted
+    /// once the total size goes above the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_ttl` - It takes the time to live (in seconds) of the rendered pages as an
+    /// argument.
+    /// * `max_size` - It takes the maximum size (in bytes) of the cache as an argument.
+    pub fn new(page_ttl: u16, max_size: u64) -> Self {
+        PageCache {
+            pages: MokaCache::builder()
+                .max_capacity(max_size)
+                .weigher(|key: &String, page: &Arc<RenderedPage>| {
+                    u32::try_from(key.len() + page.etag.len() + page.html.len()).unwrap_or(u32::MAX)
+                })
+                .time_to_live(Duration::from_secs(page_ttl.into()))
+                .build(),
+        }
+    }
+
+    /// Retrieves the page stored under the given key, rendering and storing it if it is not
+    /// cached yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the page hash of the cache key of the search results as an argument.
+    /// * `render` - It takes the function which renders the html of the page as an argument.
+    pub fn get_or_render(&self, key: String, render: impl FnOnce() -> String) -> Arc<RenderedPage> {
+        self.pages
+            .get_with(key, || Arc::new(RenderedPage::new(render())))
+    }
+
+    /// Removes all the rendered pages.
+    pub fn clear(&self) {
+        self.pages.invalidate_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_none_match_is_compared_weakly() {
+        let page = RenderedPage::new("<html></html>".to_owned());
+
+        assert!(page.matches(&page.etag));
+        assert!(page.matches(&format!("\"other\", W/{}", page.etag)));
+        assert!(page.matches("*"));
+        assert!(!page.matches("\"other\""));
+        assert!(!page.matches(""));
+    }
+
+    #[cfg(feature = "memory-cache")]
+    #[test]
+    fn test_pages_are_rendered_once() {
+        let cache = PageCache::new(60, 1024 * 1024);
+        let page = cache.get_or_render("page".to_owned(), || "<html></html>".to_owned());
+        let cached = cache.get_or_render("page".to_owned(), || unreachable!());
+
+        assert_eq!(page, cached);
+        cache.clear();
+        let rendered = cache.get_or_render("page".to_owned(), || "<body></body>".to_owned());
+        assert_ne!(page.etag, rendered.etag);
+    }
+}
@@ -0,0 +1,127 @@
+//! This module provides the tracking of the prefetched pages of search results, which records
+//! how many of the pages fetched ahead of time were actually viewed by the users so that the
+//! prefetch policy can be tuned.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use super::{cache_key::CacheKey, codec::unix_time};
+
+/// A named struct which stores the prefetched pages which were not viewed yet along with the
+/// prefetch counters.
+pub struct PrefetchTracker {
+    /// It stores the time (in seconds since the unix epoch) at which the pages were prefetched,
+    /// keyed by the hash of their cache key.
+    pages: Mutex<HashMap<String, u64>>,
+    /// It stores the time (in seconds) after which a prefetched page is no longer tracked, as
+    /// its search results have expired from the cache.
+    tracking_time: u64,
+    /// It stores the number of pages prefetched.
+    prefetched: AtomicU64,
+    /// It stores the number of prefetched pages which were viewed.
+    used: AtomicU64,
+}
+
+impl PrefetchTracker {
+    /// Constructs a new `PrefetchTracker` tracking the prefetched pages for the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracking_time` - It takes the time (in seconds) after which a prefetched page is no
+    /// longer tracked as an argument.
+    pub fn new(tracking_time: u64) -> Self {
+        PrefetchTracker {
+            pages: Mutex::new(HashMap::new()),
+            tracking_time,
+            prefetched: AtomicU64::new(0),
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that the page stored under the given key was prefetched. The pages whose
+    /// tracking time has passed are dropped at the same time.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the prefetched page as an argument.
+    pub fn record_prefetch(&self, key: &CacheKey) {
+        let now = unix_time();
+        let mut pages = self.pages.lock().unwrap_or_else(PoisonError::into_inner);
+        pages.retain(|_, prefetched_at| *prefetched_at + self.tracking_time > now);
+        pages.insert(key.hash(), now);
+        self.prefetched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the page stored under the given key was viewed, which counts it as used if
+    /// it was prefetched. The counters are logged whenever a prefetched page is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the viewed page as an argument.
+    pub fn record_view(&self, key: &CacheKey) {
+        let prefetched_at = self
+            .pages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&key.hash());
+        if prefetched_at
+            .is_some_and(|prefetched_at| prefetched_at + self.tracking_time > unix_time())
+        {
+            let used = self.used.fetch_add(1, Ordering::Relaxed) + 1;
+            log::debug!(
+                "Served a prefetched page, {} of the {} prefetched pages were used",
+                used,
+                self.prefetched()
+            );
+        }
+    }
+
+    /// A getter function that gets the number of pages prefetched.
+    pub fn prefetched(&self) -> u64 {
+        self.prefetched.load(Ordering::Relaxed)
+    }
+
+    /// A getter function that gets the number of prefetched pages which were viewed.
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewed_prefetched_pages_are_counted_once() {
+        let tracker = PrefetchTracker::new(600);
+        let (next_page, other_page) = (
+            CacheKey::new("rust", 1, 0, ["bing"]),
+            CacheKey::new("rust", 2, 0, ["bing"]),
+        );
+
+        tracker.record_prefetch(&next_page);
+        tracker.record_view(&other_page);
+        tracker.record_view(&next_page);
+        tracker.record_view(&next_page);
+
+        assert_eq!(tracker.prefetched(), 1);
+        assert_eq!(tracker.used(), 1);
+    }
+
+    #[test]
+    fn test_expired_prefetched_pages_are_not_counted() {
+        let tracker = PrefetchTracker::new(0);
+        let next_page = CacheKey::new("rust", 1, 0, ["bing"]);
+
+        tracker.record_prefetch(&next_page);
+        tracker.record_view(&next_page);
+
+        assert_eq!(tracker.prefetched(), 1);
+        assert_eq!(tracker.used(), 0);
+    }
+}
@@ -1,182 +1,851 @@
 //! This module provides the functionality to cache the aggregated results fetched and aggregated
-//! from the upstream search engines in a json format.
+//! from the upstream search engines in redis, where the encoded results are stored as is.
+//!
+//! The cache connects to a standalone redis server (`redis://` or `rediss://` for TLS), to a
+//! redis cluster (`redis+cluster://` or `rediss+cluster://` followed by the comma separated
+//! nodes) or to the master of a redis sentinel deployment (`redis+sentinel://` or
+//! `rediss+sentinel://` followed by the comma separated sentinels and the service name). The
+//! master of a sentinel deployment is looked up again whenever it stops answering or turns out
+//! to be a replica, so that the cache follows the failovers.
 
-use super::error::CacheError;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use super::{cacher::CacheMetrics, codec::CodecPipeline, error::CacheError};
+use crate::config::parser::Config;
+use arc_swap::ArcSwap;
 use error_stack::Report;
-use futures::stream::FuturesUnordered;
-use redis::{aio::ConnectionManager, AsyncCommands, Client, RedisError};
+use redis::{
+    aio::{ConnectionLike, ConnectionManager},
+    cluster::ClusterClient,
+    cluster_async::ClusterConnection,
+    cluster_routing::{RoutingInfo, SingleNodeRoutingInfo},
+    sentinel::{Sentinel, SentinelNodeConnectionInfo},
+    Client, Cmd, ConnectionAddr, ErrorKind, FromRedisValue, IntoConnectionInfo, Pipeline,
+    RedisFuture, RedisResult, TlsMode, Value,
+};
 
 /// A constant holding the redis pipeline size.
 const REDIS_PIPELINE_SIZE: usize = 3;
 
+/// A constant holding the number of keys asked for by every `SCAN` command.
+const REDIS_SCAN_COUNT: usize = 100;
+
+/// A named struct which stores the options used to connect to redis.
+#[derive(Clone)]
+pub struct RedisOptions {
+    /// It stores the redis connection url address of the standalone server, the cluster or the
+    /// sentinels.
+    pub url: String,
+    /// It stores the number of connections opened to redis.
+    pub pool_size: u8,
+    /// It stores the prefix of the keys written by the cache, so that several instances can
+    /// share the same redis.
+    pub key_prefix: String,
+}
+
+impl RedisOptions {
+    /// Constructs the `RedisOptions` from the parsed config.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes the config struct as an argument.
+    pub fn new(config: &Config) -> Self {
+        RedisOptions {
+            url: config.redis_url.clone(),
+            pool_size: config.redis_pool_size,
+            key_prefix: config.redis_key_prefix.clone(),
+        }
+    }
+}
+
+/// The deployment of the redis servers the cache connects to, as given by the redis url.
+#[derive(Debug, PartialEq)]
+enum RedisTopology {
+    /// A standalone redis server, reached through the given url.
+    Standalone(String),
+    /// A redis cluster, reached through the given urls of its nodes.
+    Cluster(Vec<String>),
+    /// A redis sentinel deployment.
+    Sentinel {
+        /// The urls of the sentinels.
+        sentinels: Vec<String>,
+        /// The name of the service whose master is used.
+        service_name: String,
+        /// The url carrying the credentials and the TLS mode used to connect to the master.
+        master: String,
+    },
+}
+
+impl RedisTopology {
+    /// Parses the redis url into the deployment it points to.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - It takes the redis connection url address as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error message if the url has no scheme, no node or, for sentinels, no service
+    /// name.
+    fn parse(url: &str) -> Result<Self, String> {
+        let (scheme, address) = url
+            .split_once("://")
+            .ok_or_else(|| format!("the redis url `{url}` has no scheme"))?;
+        let (scheme, deployment) = match scheme.split_once('+') {
+            Some((scheme, deployment @ ("cluster" | "sentinel"))) => (scheme, deployment),
+            _ => return Ok(RedisTopology::Standalone(url.to_owned())),
+        };
+
+        let (credentials, address) = match address.rsplit_once('@') {
+            Some((credentials, address)) => (format!("{credentials}@"), address),
+            None => (String::new(), address),
+        };
+        let (hosts, path) = address.split_once('/').unwrap_or((address, ""));
+        let hosts: Vec<&str> = hosts
+            .split(',')
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .collect();
+        if hosts.is_empty() {
+            return Err(format!("the redis url `{url}` has no node"));
+        }
+
+        if deployment == "cluster" {
+            return Ok(RedisTopology::Cluster(
+                hosts
+                    .iter()
+                    .map(|host| format!("{scheme}://{credentials}{host}"))
+                    .collect(),
+            ));
+        }
+
+        if path.is_empty() {
+            return Err(format!(
+                "the redis url `{url}` has no sentinel service name"
+            ));
+        }
+        // The credentials are those of the master, the sentinels being reached without them.
+        Ok(RedisTopology::Sentinel {
+            sentinels: hosts
+                .iter()
+                .map(|host| format!("{scheme}://{host}"))
+                .collect(),
+            service_name: path.to_owned(),
+            master: format!("{scheme}://{credentials}{}", hosts[0]),
+        })
+    }
+}
+
+/// A named struct which stores what is needed to look up the master of a sentinel deployment.
+struct SentinelMaster {
+    /// It stores the urls of the sentinels.
+    sentinels: Vec<String>,
+    /// It stores the name of the service whose master is used.
+    service_name: String,
+    /// It stores the credentials and the TLS mode used to connect to the master.
+    node_connection_info: SentinelNodeConnectionInfo,
+}
+
+impl SentinelMaster {
+    /// Asks the sentinels for the current master of the service.
+    ///
+    /// # Error
+    ///
+    /// Returns a redis error if no sentinel knows the master.
+    async fn resolve(&self) -> RedisResult<Client> {
+        Sentinel::build(self.sentinels.clone())?
+            .async_master_for(&self.service_name, Some(&self.node_connection_info))
+            .await
+    }
+}
+
+/// A client of the redis deployment, which opens the connections of the pool.
+enum RedisClient {
+    /// A client of a standalone redis server.
+    Standalone(Client),
+    /// A client of a redis cluster.
+    Cluster(ClusterClient),
+    /// A client of the master of a sentinel deployment, which is looked up whenever the pool is
+    /// connected.
+    Sentinel(SentinelMaster),
+}
+
+impl RedisClient {
+    /// Creates the client of the redis deployment the url points to.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - It takes the redis connection url address as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the url is malformed.
+    fn open(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match RedisTopology::parse(url)? {
+            RedisTopology::Standalone(url) => RedisClient::Standalone(Client::open(url)?),
+            RedisTopology::Cluster(urls) => RedisClient::Cluster(ClusterClient::new(urls)?),
+            RedisTopology::Sentinel {
+                sentinels,
+                service_name,
+                master,
+            } => {
+                let master = master.into_connection_info()?;
+                RedisClient::Sentinel(SentinelMaster {
+                    sentinels,
+                    service_name,
+                    node_connection_info: SentinelNodeConnectionInfo {
+                        tls_mode: match master.addr {
+                            ConnectionAddr::TcpTls { insecure: true, .. } => {
+                                Some(TlsMode::Insecure)
+                            }
+                            ConnectionAddr::TcpTls { .. } => Some(TlsMode::Secure),
+                            _ => None,
+                        },
+                        redis_connection_info: Some(master.redis),
+                    },
+                })
+            }
+        })
+    }
+
+    /// Opens the connections of the pool to the redis deployment, the master of a sentinel
+    /// deployment being looked up from the sentinels first.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_size` - It takes the number of connections to be opened as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a redis error if no sentinel knows the master or if a connection cannot be
+    /// established.
+    async fn connect_pool(&self, pool_size: usize) -> RedisResult<Vec<RedisConnection>> {
+        let master;
+        let client = match self {
+            RedisClient::Standalone(client) => client,
+            RedisClient::Cluster(client) => {
+                return futures::future::try_join_all(
+                    (0..pool_size).map(|_| client.get_async_connection()),
+                )
+                .await
+                .map(|connections| {
+                    connections
+                        .into_iter()
+                        .map(RedisConnection::Cluster)
+                        .collect()
+                })
+            }
+            RedisClient::Sentinel(sentinel) => {
+                master = sentinel.resolve().await?;
+                &master
+            }
+        };
+        futures::future::try_join_all((0..pool_size).map(|_| client.get_connection_manager()))
+            .await
+            .map(|connections| {
+                connections
+                    .into_iter()
+                    .map(RedisConnection::Standalone)
+                    .collect()
+            })
+    }
+}
+
+/// A connection of the pool, which reconnects on its own once it is dropped.
+#[derive(Clone)]
+enum RedisConnection {
+    /// A connection to a standalone redis server.
+    Standalone(ConnectionManager),
+    /// A connection to a redis cluster, which routes every command to the node owning its key.
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnection {
+    /// Runs the command on the node of a cluster with the given address. A standalone connection
+    /// runs the command on its server.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - It takes the redis command as an argument.
+    /// * `host` - It takes the host of the node as an argument.
+    /// * `port` - It takes the port of the node as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a redis error if the command fails or if its response has an unexpected type.
+    async fn query_node<T: FromRedisValue>(
+        mut self,
+        command: &Cmd,
+        host: String,
+        port: u16,
+    ) -> RedisResult<T> {
+        match &mut self {
+            RedisConnection::Standalone(connection) => command.query_async(connection).await,
+            RedisConnection::Cluster(connection) => T::from_redis_value(
+                &connection
+                    .route_command(
+                        command,
+                        RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress { host, port }),
+                    )
+                    .await?,
+            ),
+        }
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Standalone(connection) => connection.req_packed_command(cmd),
+            RedisConnection::Cluster(connection) => connection.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Standalone(connection) => {
+                connection.req_packed_commands(cmd, offset, count)
+            }
+            RedisConnection::Cluster(connection) => {
+                connection.req_packed_commands(cmd, offset, count)
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Standalone(connection) => connection.get_db(),
+            RedisConnection::Cluster(connection) => connection.get_db(),
+        }
+    }
+}
+
+/// A named struct which stores the position of a scan over the keys written by the cache. The
+/// masters of a cluster are scanned one after the other.
+#[derive(Default)]
+pub struct KeyScan {
+    /// It stores the cursor returned by the node being scanned, which is `0` for a new scan.
+    cursor: u64,
+    /// It stores the addresses of the masters of a cluster left to be scanned, the last one
+    /// being scanned, or `None` until they are looked up.
+    masters: Option<Vec<(String, u16)>>,
+    /// It stores whether all the keys were scanned.
+    finished: bool,
+}
+
 /// A named struct which stores the redis Connection url address to which the client will
 /// connect to.
 pub struct RedisCache {
-    /// It stores a pool of connections ready to be used.
-    connection_pool: Vec<ConnectionManager>,
-    /// It stores the size of the connection pool (in other words the number of
-    /// connections that should be stored in the pool).
-    pool_size: u8,
-    /// It stores the index of which connection is being used at the moment.
-    current_connection: u8,
+    /// It stores the client used to connect the pool again once the master of a sentinel
+    /// deployment changed.
+    client: RedisClient,
+    /// It stores a pool of connections ready to be used, which is swapped for a new one once the
+    /// master of a sentinel deployment changed.
+    connection_pool: ArcSwap<Vec<RedisConnection>>,
+    /// It is held while the pool is connected again, so that a failover is handled once.
+    reconnecting: tokio::sync::Mutex<()>,
+    /// It stores the index of the connection used by the next command, the connections being
+    /// used in turn.
+    next_connection: AtomicUsize,
+    /// It stores whether the cache is connected to a redis cluster.
+    cluster: bool,
+    /// It stores the prefix of the keys written by the cache.
+    key_prefix: String,
     /// It stores the max TTL for keys.
     cache_ttl: u16,
-    /// It stores the redis pipeline struct of size 3.
-    pipeline: redis::Pipeline,
+    /// It stores the codec pipeline used to encode the search results before they are stored.
+    pub(super) codec: CodecPipeline,
+    /// It stores the hit and miss counters of the cache.
+    pub(super) metrics: CacheMetrics,
 }
 
 impl RedisCache {
-    /// A function which fetches the cached json results as json string.
+    /// A function which connects the pool of connections to redis.
     ///
     /// # Arguments
     ///
-    /// * `redis_connection_url` - It takes the redis Connection url address.
-    /// * `pool_size` - It takes the size of the connection pool (in other words the number of
-    /// connections that should be stored in the pool).
+    /// * `options` - It takes the options used to connect to redis as an argument.
     /// * `cache_ttl` - It takes the the time to live for cached results to live in the redis
     /// server.
+    /// * `codec` - It takes the codec pipeline used to encode the search results as an argument.
     ///
     /// # Error
     ///
     /// Returns a newly constructed `RedisCache` struct on success otherwise returns a standard
     /// error type.
     pub async fn new(
-        redis_connection_url: &str,
-        pool_size: u8,
+        options: &RedisOptions,
         cache_ttl: u16,
+        codec: CodecPipeline,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = Client::open(redis_connection_url)?;
-        let tasks: FuturesUnordered<_> = FuturesUnordered::new();
+        let client = RedisClient::open(&options.url)?;
+        let connection_pool = client.connect_pool(options.pool_size.max(1).into()).await?;
+
+        Ok(RedisCache {
+            cluster: matches!(client, RedisClient::Cluster(_)),
+            client,
+            connection_pool: ArcSwap::from_pointee(connection_pool),
+            reconnecting: tokio::sync::Mutex::new(()),
+            next_connection: AtomicUsize::new(0),
+            key_prefix: options.key_prefix.clone(),
+            cache_ttl,
+            codec,
+            metrics: CacheMetrics::default(),
+        })
+    }
+
+    /// A function which fetches the cached entry from the redis server.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes a string as key.
+    ///
+    /// # Error
+    ///
+    /// Returns the bytes of the entry from the cache on success otherwise returns a
+    /// `MissingValue` error if the key does not exist or a `CacheError` on a failure.
+    pub async fn cached_entry(&self, key: &str) -> Result<Vec<u8>, Report<CacheError>> {
+        self.query::<Option<Vec<u8>>>(&Cmd::get(self.prefixed(key)))
+            .await?
+            .ok_or_else(|| Report::new(CacheError::MissingValue))
+    }
 
-        for _ in 0..pool_size {
-            let client_partially_cloned = client.clone();
-            tasks.push(tokio::spawn(async move {
-                client_partially_cloned.get_connection_manager().await
-            }));
+    /// A function which caches the entries by using the keys as the keys and the entries as
+    /// the values and stores them in redis server with ttl(time to live) set to the cache
+    /// expiry time.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - It takes the bytes of the entries as an argument.
+    /// * `keys` - It takes the keys of the entries as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an unit type if the results are cached succesfully otherwise returns a `CacheError`
+    /// on a failure.
+    pub async fn cache_entries(
+        &self,
+        entries: impl Iterator<Item = Vec<u8>>,
+        keys: impl Iterator<Item = String>,
+    ) -> Result<(), Report<CacheError>> {
+        // A cluster sends a whole pipeline to the node owning its first key, so the keys which
+        // may belong to other nodes are set one by one.
+        if self.cluster {
+            for (key, entry) in keys.zip(entries) {
+                self.query::<()>(&Cmd::set_ex(
+                    self.prefixed(&key),
+                    entry,
+                    self.cache_ttl.into(),
+                ))
+                .await?;
+            }
+            return Ok(());
         }
 
-        let mut outputs = Vec::new();
-        for task in tasks {
-            outputs.push(task.await??);
+        let mut pipeline = redis::Pipeline::with_capacity(REDIS_PIPELINE_SIZE);
+        for (key, entry) in keys.zip(entries) {
+            pipeline.set_ex(self.prefixed(&key), entry, self.cache_ttl.into());
         }
 
-        let redis_cache = RedisCache {
-            connection_pool: outputs,
-            pool_size,
-            current_connection: Default::default(),
-            cache_ttl,
-            pipeline: redis::Pipeline::with_capacity(REDIS_PIPELINE_SIZE),
-        };
+        self.execute(|mut connection| {
+            let pipeline = &pipeline;
+            async move { pipeline.query_async::<_, ()>(&mut connection).await }
+        })
+        .await
+    }
 
-        Ok(redis_cache)
+    /// A function which counts the keys written by the cache.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of keys on success otherwise returns a `CacheError` on a failure.
+    pub async fn key_count(&self) -> Result<u64, Report<CacheError>> {
+        let mut key_count = 0;
+        let mut scan = KeyScan::default();
+        while let Some(keys) = self.scan_keys(&mut scan).await? {
+            key_count += keys.len() as u64;
+        }
+        Ok(key_count)
     }
 
-    /// A function which fetches the cached json as json string from the redis server.
+    /// A function which fetches the next batch of the keys written by the cache, without their
+    /// prefix. The keys of a cluster are scanned on every master.
     ///
     /// # Arguments
     ///
-    /// * `key` - It takes a string as key.
+    /// * `scan` - It takes the position of the scan, which is advanced to the next batch, as an
+    /// argument.
     ///
     /// # Error
     ///
-    /// Returns the json as a String from the cache on success otherwise returns a `CacheError`
+    /// Returns the keys of the batch, or `None` once all the keys were fetched, on success
+    /// otherwise returns a `CacheError` on a failure.
+    pub async fn scan_keys(
+        &self,
+        scan: &mut KeyScan,
+    ) -> Result<Option<Vec<String>>, Report<CacheError>> {
+        if scan.finished {
+            return Ok(None);
+        }
+
+        let mut command = redis::cmd("SCAN");
+        command
+            .arg(scan.cursor)
+            .arg("MATCH")
+            .arg(format!("{}*", escape_pattern(&self.key_prefix)))
+            .arg("COUNT")
+            .arg(REDIS_SCAN_COUNT);
+
+        let (next_cursor, keys): (u64, Vec<String>) = if self.cluster {
+            if scan.masters.is_none() {
+                scan.masters = Some(self.cluster_masters().await?);
+            }
+            let masters = scan.masters.get_or_insert_with(Vec::new);
+            let Some((host, port)) = masters.last().cloned() else {
+                scan.finished = true;
+                return Ok(None);
+            };
+            let response: (u64, Vec<String>) = self
+                .execute(|connection| connection.query_node(&command, host.clone(), port))
+                .await?;
+            if response.0 == 0 {
+                masters.pop();
+                scan.finished = masters.is_empty();
+            }
+            response
+        } else {
+            let response: (u64, Vec<String>) = self.query(&command).await?;
+            scan.finished = response.0 == 0;
+            response
+        };
+        scan.cursor = next_cursor;
+
+        Ok(Some(
+            keys.into_iter()
+                .filter_map(|key| key.strip_prefix(&self.key_prefix).map(str::to_owned))
+                .collect(),
+        ))
+    }
+
+    /// A helper function which looks up the addresses of the masters of a redis cluster.
+    ///
+    /// # Error
+    ///
+    /// Returns the host and port of every master on success otherwise returns a `CacheError`
     /// on a failure.
-    pub async fn cached_json(&mut self, key: &str) -> Result<String, Report<CacheError>> {
-        self.current_connection = Default::default();
-
-        let mut result: Result<String, RedisError> = self.connection_pool
-            [self.current_connection as usize]
-            .get(key)
-            .await;
-
-        // Code to check whether the current connection being used is dropped with connection error
-        // or not. if it drops with the connection error then the current connection is replaced
-        // with a new connection from the pool which is then used to run the redis command then
-        // that connection is also checked whether it is dropped or not if it is not then the
-        // result is passed as a `Result` or else the same process repeats again and if all of the
-        // connections in the pool result in connection drop error then a custom pool error is
-        // returned.
-        loop {
-            match result {
-                Err(error) => match error.is_connection_dropped() {
-                    true => {
-                        self.current_connection += 1;
-                        if self.current_connection == self.pool_size {
-                            return Err(Report::new(
-                                CacheError::PoolExhaustionWithConnectionDropError,
-                            ));
-                        }
-                        result = self.connection_pool[self.current_connection as usize]
-                            .get(key)
-                            .await;
-                        continue;
-                    }
-                    false => return Err(Report::new(CacheError::RedisError(error))),
-                },
-                Ok(res) => return Ok(res),
+    async fn cluster_masters(&self) -> Result<Vec<(String, u16)>, Report<CacheError>> {
+        // Every slot range is listed along with its master, followed by its replicas.
+        let slots: Vec<Vec<Value>> = self.query(&redis::cmd("CLUSTER").arg("SLOTS")).await?;
+        let mut masters = Vec::new();
+        for slot in slots {
+            let Some(Value::Bulk(master)) = slot.get(2) else {
+                continue;
+            };
+            if let [host, port, ..] = master.as_slice() {
+                let host = String::from_redis_value(host)
+                    .map_err(|error| Report::new(CacheError::RedisError(error)))?;
+                let port = u16::from_redis_value(port)
+                    .map_err(|error| Report::new(CacheError::RedisError(error)))?;
+                masters.push((host, port));
             }
         }
+        masters.sort_unstable();
+        masters.dedup();
+        Ok(masters)
     }
 
-    /// A function which caches the json by using the key and
-    /// `json results` as the value and stores it in redis server with ttl(time to live)
-    /// set to 60 seconds.
+    /// A function which fetches the cached entries stored under the given keys.
     ///
     /// # Arguments
     ///
-    /// * `json_results` - It takes the json results string as an argument.
-    /// * `key` - It takes the key as a String.
+    /// * `keys` - It takes the keys as an argument.
     ///
     /// # Error
     ///
-    /// Returns an unit type if the results are cached succesfully otherwise returns a `CacheError`
-    /// on a failure.
-    pub async fn cache_json(
-        &mut self,
-        json_results: impl Iterator<Item = String>,
-        keys: impl Iterator<Item = String>,
+    /// Returns the bytes of the entries on success, `None` standing for the keys which do not
+    /// hold a string, otherwise returns a `CacheError` on a failure.
+    pub async fn cached_entries(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<Option<Vec<u8>>>, Report<CacheError>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        // The keys of a cluster may belong to different slots, so they are fetched one by one.
+        if self.cluster {
+            let mut entries = Vec::with_capacity(keys.len());
+            for key in keys {
+                entries.push(self.query(&Cmd::get(self.prefixed(key))).await?);
+            }
+            return Ok(entries);
+        }
+        let keys: Vec<String> = keys.iter().map(|key| self.prefixed(key)).collect();
+        self.query(redis::cmd("MGET").arg(keys)).await
+    }
+
+    /// A function which removes the given keys from the redis server.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - It takes the keys as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the number of removed keys on success otherwise returns a `CacheError` on a
+    /// failure.
+    pub async fn delete_keys(&self, keys: &[String]) -> Result<u64, Report<CacheError>> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        // The keys of a cluster may belong to different slots, so they are removed one by one.
+        if self.cluster {
+            let mut deleted = 0;
+            for key in keys {
+                deleted += self.query::<u64>(&Cmd::del(self.prefixed(key))).await?;
+            }
+            return Ok(deleted);
+        }
+        let keys: Vec<String> = keys.iter().map(|key| self.prefixed(key)).collect();
+        self.query(redis::cmd("DEL").arg(keys)).await
+    }
+
+    /// A helper function which prepends the key prefix to the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the key as an argument.
+    fn prefixed(&self, key: &str) -> String {
+        format!("{}{key}", self.key_prefix)
+    }
+
+    /// A helper function which runs the redis command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - It takes the redis command as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the response of the redis server on success otherwise returns a `CacheError` on
+    /// a failure.
+    async fn query<T: FromRedisValue>(&self, command: &Cmd) -> Result<T, Report<CacheError>> {
+        self.execute(|mut connection| async move { command.query_async(&mut connection).await })
+            .await
+    }
+
+    /// A helper function which runs the request on the connections of the pool. The master of a
+    /// sentinel deployment is looked up again and the request is retried once when the
+    /// connection to the master was lost or when it turned out to be a replica.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - It takes the function sending the request over a connection as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the response of the redis server on success otherwise returns a `CacheError` on
+    /// a failure, which is a `PoolExhaustionWithConnectionDropError` if all the connections of
+    /// the pool were dropped.
+    async fn execute<T, F, Fut>(&self, request: F) -> Result<T, Report<CacheError>>
+    where
+        F: Fn(RedisConnection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let connection_pool = self.connection_pool.load_full();
+        match self.execute_on(&connection_pool, &request).await {
+            Err(error)
+                if matches!(self.client, RedisClient::Sentinel(_))
+                    && is_connection_lost(error.current_context()) =>
+            {
+                self.reconnect(&connection_pool).await?;
+                self.execute_on(&self.connection_pool.load_full(), &request)
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    /// A helper function which connects the pool to the master of the sentinel deployment
+    /// again, unless another request already did since the given pool failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `failed_pool` - It takes the pool whose connection to the master was lost as an
+    /// argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a `CacheError` if no sentinel knows the master or if it cannot be connected to.
+    async fn reconnect(
+        &self,
+        failed_pool: &Arc<Vec<RedisConnection>>,
     ) -> Result<(), Report<CacheError>> {
-        self.current_connection = Default::default();
-
-        for (key, json_result) in keys.zip(json_results) {
-            self.pipeline
-                .set_ex(key, json_result, self.cache_ttl.into());
-        }
-
-        let mut result: Result<(), RedisError> = self
-            .pipeline
-            .query_async(&mut self.connection_pool[self.current_connection as usize])
-            .await;
-
-        // Code to check whether the current connection being used is dropped with connection error
-        // or not. if it drops with the connection error then the current connection is replaced
-        // with a new connection from the pool which is then used to run the redis command then
-        // that connection is also checked whether it is dropped or not if it is not then the
-        // result is passed as a `Result` or else the same process repeats again and if all of the
-        // connections in the pool result in connection drop error then a custom pool error is
-        // returned.
-        loop {
-            match result {
-                Err(error) => match error.is_connection_dropped() {
-                    true => {
-                        self.current_connection += 1;
-                        if self.current_connection == self.pool_size {
-                            return Err(Report::new(
-                                CacheError::PoolExhaustionWithConnectionDropError,
-                            ));
-                        }
-                        result = self
-                            .pipeline
-                            .query_async(
-                                &mut self.connection_pool[self.current_connection as usize],
-                            )
-                            .await;
-                        continue;
-                    }
-                    false => return Err(Report::new(CacheError::RedisError(error))),
-                },
-                Ok(_) => return Ok(()),
+        let _reconnecting = self.reconnecting.lock().await;
+        if !Arc::ptr_eq(&self.connection_pool.load(), failed_pool) {
+            return Ok(());
+        }
+
+        log::warn!("Lost the redis master, looking it up from the sentinels again");
+        let connection_pool = self
+            .client
+            .connect_pool(failed_pool.len())
+            .await
+            .map_err(|error| Report::new(CacheError::RedisError(error)))?;
+        self.connection_pool.store(Arc::new(connection_pool));
+        log::info!("Reconnected to the redis master");
+        Ok(())
+    }
+
+    /// A helper function which runs the request on the connections of the given pool. The
+    /// connections are used in turn so that the requests are spread over the whole pool, and
+    /// the request is retried on the next connection whenever the current one was dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool` - It takes the pool of connections as an argument.
+    /// * `request` - It takes the function sending the request over a connection as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the response of the redis server on success otherwise returns a `CacheError` on
+    /// a failure, which is a `PoolExhaustionWithConnectionDropError` if all the connections of
+    /// the pool were dropped.
+    async fn execute_on<T, F, Fut>(
+        &self,
+        connection_pool: &[RedisConnection],
+        request: &F,
+    ) -> Result<T, Report<CacheError>>
+    where
+        F: Fn(RedisConnection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let pool_size = connection_pool.len();
+        let first_connection = self.next_connection.fetch_add(1, Ordering::Relaxed);
+
+        for offset in 0..pool_size {
+            let connection = connection_pool[(first_connection + offset) % pool_size].clone();
+            match request(connection).await {
+                Ok(response) => return Ok(response),
+                Err(error) if error.is_connection_dropped() => continue,
+                Err(error) => return Err(Report::new(CacheError::RedisError(error))),
             }
         }
+        Err(Report::new(
+            CacheError::PoolExhaustionWithConnectionDropError,
+        ))
+    }
+}
+
+/// Checks whether the error shows that the connection to redis was lost or that the server is
+/// a replica, which no longer accepts the writes after a failover.
+///
+/// # Arguments
+///
+/// * `error` - It takes the error returned by redis as an argument.
+pub fn is_connection_lost(error: &CacheError) -> bool {
+    match error {
+        CacheError::PoolExhaustionWithConnectionDropError => true,
+        CacheError::RedisError(error) => {
+            error.kind() == ErrorKind::ReadOnly
+                || error.is_connection_dropped()
+                || error.is_connection_refusal()
+                || error.is_io_error()
+                || error.is_timeout()
+        }
+        _ => false,
+    }
+}
+
+/// A helper function which escapes the characters of the key prefix which have a meaning in a
+/// redis glob-style pattern.
+///
+/// # Arguments
+///
+/// * `prefix` - It takes the key prefix as an argument.
+fn escape_pattern(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len());
+    for character in prefix.chars() {
+        if matches!(character, '*' | '?' | '[' | ']' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(character);
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standalone_urls() {
+        for url in [
+            "redis://127.0.0.1:6379",
+            "rediss://:secret@example.com:6380/1",
+        ] {
+            assert_eq!(
+                RedisTopology::parse(url),
+                Ok(RedisTopology::Standalone(url.to_owned()))
+            );
+        }
+        assert!(RedisTopology::parse("127.0.0.1:6379").is_err());
+    }
+
+    #[test]
+    fn test_cluster_urls() {
+        assert_eq!(
+            RedisTopology::parse("rediss+cluster://:secret@node1:6379, node2:6379"),
+            Ok(RedisTopology::Cluster(vec![
+                "rediss://:secret@node1:6379".to_owned(),
+                "rediss://:secret@node2:6379".to_owned(),
+            ]))
+        );
+        assert!(RedisTopology::parse("redis+cluster://").is_err());
+    }
+
+    #[test]
+    fn test_sentinel_urls() {
+        assert_eq!(
+            RedisTopology::parse("redis+sentinel://:secret@sentinel1:26379,sentinel2:26379/main"),
+            Ok(RedisTopology::Sentinel {
+                sentinels: vec![
+                    "redis://sentinel1:26379".to_owned(),
+                    "redis://sentinel2:26379".to_owned(),
+                ],
+                service_name: "main".to_owned(),
+                master: "redis://:secret@sentinel1:26379".to_owned(),
+            })
+        );
+        assert!(RedisTopology::parse("redis+sentinel://sentinel1:26379").is_err());
+    }
+
+    #[test]
+    fn test_read_only_replicas_count_as_lost_connections() {
+        let read_only = redis::RedisError::from((ErrorKind::ReadOnly, "READONLY"));
+        assert!(is_connection_lost(&CacheError::RedisError(read_only)));
+        assert!(is_connection_lost(
+            &CacheError::PoolExhaustionWithConnectionDropError
+        ));
+
+        let wrong_type = redis::RedisError::from((ErrorKind::TypeError, "WRONGTYPE"));
+        assert!(!is_connection_lost(&CacheError::RedisError(wrong_type)));
+        assert!(!is_connection_lost(&CacheError::MissingValue));
+    }
+
+    #[test]
+    fn test_key_prefix_is_escaped() {
+        assert_eq!(escape_pattern("websurfx:"), "websurfx:");
+        assert_eq!(escape_pattern("a*b?[c]\\"), "a\\*b\\?\\[c\\]\\\\");
     }
 }
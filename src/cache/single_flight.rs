@@ -0,0 +1,211 @@
+//! This module provides the request coalescing of the searches, so that identical searches
+//! running at the same time wait for a single aggregation from the upstream search engines and
+//! share its results instead of each querying every upstream search engine.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Mutex, PoisonError},
+};
+
+use tokio::sync::watch;
+
+use super::cache_key::CacheKey;
+use crate::models::aggregation_models::SearchResults;
+
+/// The outcome of an aggregation shared with the waiting searches, the error being shared as
+/// its message.
+type Outcome = Option<Result<SearchResults, String>>;
+
+/// A named struct which stores the aggregations in flight, keyed by the hash of the cache key of
+/// their search results.
+#[derive(Default)]
+pub struct SingleFlight {
+    /// It stores the receivers of the outcome of the aggregations in flight.
+    flights: Mutex<HashMap<String, watch::Receiver<Outcome>>>,
+}
+
+impl SingleFlight {
+    /// Runs the given aggregation unless an identical one is already in flight, in which case
+    /// its outcome is awaited and shared instead. When the aggregation in flight is cancelled,
+    /// one of the waiting searches runs its own aggregation in its place.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - It takes the cache key of the search results as an argument.
+    /// * `fetch` - It takes the function which aggregates the search results as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns the error of the aggregation, which is only carried as its message by the
+    /// waiting searches.
+    pub async fn run<F, Fut>(
+        &self,
+        key: &CacheKey,
+        fetch: F,
+    ) -> Result<SearchResults, Box<dyn std::error::Error>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<SearchResults, Box<dyn std::error::Error>>>,
+    {
+        let hash = key.hash();
+
+        let sender = loop {
+            let mut receiver = {
+                let mut flights = self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+                match flights.get(&hash) {
+                    Some(receiver) => receiver.clone(),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        flights.insert(hash.clone(), receiver);
+                        break sender;
+                    }
+                }
+            };
+
+            if let Ok(outcome) = receiver.wait_for(Option::is_some).await {
+                return match outcome.as_ref() {
+                    Some(Ok(search_results)) => Ok(search_results.clone()),
+                    Some(Err(error)) => Err(error.clone().into()),
+                    None => unreachable!("the outcome was awaited"),
+                };
+            }
+            // The aggregation in flight was cancelled before it completed.
+        };
+
+        // The flight is removed before the sender is dropped, so that the searches woken up by a
+        // cancellation never find the cancelled flight again.
+        let _flight = Flight {
+            single_flight: self,
+            hash,
+        };
+        let search_results = fetch().await;
+        sender.send_replace(Some(match &search_results {
+            Ok(search_results) => Ok(search_results.clone()),
+            Err(error) => Err(error.to_string()),
+        }));
+        search_results
+    }
+
+    /// A getter function that gets the number of aggregations in flight.
+    pub fn len(&self) -> usize {
+        self.flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Checks whether no aggregation is in flight.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A guard which removes the flight from the aggregations in flight once it completes or is
+/// cancelled.
+struct Flight<'a> {
+    /// The aggregations in flight.
+    single_flight: &'a SingleFlight,
+    /// The hash of the cache key of the search results being aggregated.
+    hash: String,
+}
+
+impl Drop for Flight<'_> {
+    fn drop(&mut self) {
+        self.single_flight
+            .flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+
+    async fn fetch(calls: &AtomicUsize) -> Result<SearchResults, Box<dyn std::error::Error>> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(SearchResults::default())
+    }
+
+    #[tokio::test]
+    async fn test_identical_searches_share_one_aggregation() {
+        let single_flight = Arc::new(SingleFlight::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+
+        let searches = (0..10).map(|_| {
+            let (single_flight, calls, key) = (single_flight.clone(), calls.clone(), key.clone());
+            tokio::spawn(async move { single_flight.run(&key, || fetch(&calls)).await.is_ok() })
+        });
+        for search in searches.collect::<Vec<_>>() {
+            assert!(search.await.unwrap());
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(single_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_different_searches_are_not_coalesced() {
+        let single_flight = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+
+        let (first, second) = tokio::join!(
+            single_flight.run(&CacheKey::new("rust", 0, 0, ["bing"]), || fetch(&calls)),
+            single_flight.run(&CacheKey::new("rust", 1, 0, ["bing"]), || fetch(&calls)),
+        );
+
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_search_takes_over_a_cancelled_aggregation() {
+        let single_flight = Arc::new(SingleFlight::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+
+        let leader = {
+            let (single_flight, calls, key) = (single_flight.clone(), calls.clone(), key.clone());
+            tokio::spawn(async move { single_flight.run(&key, || fetch(&calls)).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let follower = {
+            let (single_flight, calls, key) = (single_flight.clone(), calls.clone(), key.clone());
+            tokio::spawn(async move { single_flight.run(&key, || fetch(&calls)).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        leader.abort();
+
+        assert!(follower.await.unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_shared() {
+        let single_flight = SingleFlight::default();
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+
+        let (first, second) = tokio::join!(
+            single_flight.run(&key, || async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Err("upstream failure".into())
+            }),
+            single_flight.run(&key, || async { Ok(SearchResults::default()) }),
+        );
+
+        assert_eq!(first.err().unwrap().to_string(), "upstream failure");
+        assert_eq!(second.err().unwrap().to_string(), "upstream failure");
+    }
+}
@@ -2,3 +2,4 @@
 //! and convert the config options into rust readable form.
 
 pub mod parser;
+pub mod reload;
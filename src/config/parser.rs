@@ -1,12 +1,21 @@
 //! This module provides the functionality to parse the lua config and convert the config options
 //! into rust readable form.
 
+use crate::cache::codec::Serializer;
+#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+use crate::cache::encryption::CacheCipher;
 use crate::handler::{file_path, FileType};
 
-use crate::models::parser_models::{AggregatorConfig, RateLimiter, Style};
+use crate::models::parser_models::{AggregatorConfig, PrefetchPolicy, RateLimiter, Style};
+use crate::results::{
+    domain_rules::DomainRules, query_classifier::QueryClassifier,
+    safe_search_classifier::SafeSearchClassifier,
+};
 use log::LevelFilter;
 use mlua::Lua;
-use std::{collections::HashMap, fs, thread::available_parallelism};
+#[cfg(feature = "disk-cache")]
+use std::path::PathBuf;
+use std::{collections::HashMap, fs, path::Path, thread::available_parallelism};
 
 /// A named struct which stores the parsed config file options.
 pub struct Config {
@@ -20,9 +29,54 @@ pub struct Config {
     /// It stores the redis connection url address on which the redis
     /// client should connect.
     pub redis_url: String,
-    #[cfg(any(feature = "redis-cache", feature = "memory-cache"))]
+    #[cfg(feature = "redis-cache")]
+    /// It stores the number of connections opened to redis.
+    pub redis_pool_size: u8,
+    #[cfg(feature = "redis-cache")]
+    /// It stores the prefix of the keys written to redis.
+    pub redis_key_prefix: String,
+    #[cfg(any(
+        feature = "redis-cache",
+        feature = "memory-cache",
+        feature = "disk-cache"
+    ))]
     /// It stores the max TTL for search results in cache.
     pub cache_expiry_time: u16,
+    #[cfg(any(
+        feature = "redis-cache",
+        feature = "memory-cache",
+        feature = "disk-cache"
+    ))]
+    /// It stores the time (in seconds) after which the cached search results are refreshed in
+    /// the background while still being served, if stale-while-revalidate is enabled.
+    pub cache_soft_expiry_time: Option<u16>,
+    #[cfg(feature = "memory-cache")]
+    /// It stores the maximum size (in bytes) of the in-memory cache.
+    pub memory_cache_max_size: u64,
+    #[cfg(feature = "memory-cache")]
+    /// It stores the time (in seconds) after which the search results which were not looked up
+    /// expire from the in-memory cache, if any.
+    pub cache_time_to_idle: Option<u16>,
+    #[cfg(feature = "memory-cache")]
+    /// It stores the maximum size (in bytes) of the cache of the rendered search pages, if it is
+    /// enabled.
+    pub rendered_page_cache_size: Option<u64>,
+    #[cfg(feature = "disk-cache")]
+    /// It stores the directory in which the disk cache stores the search results.
+    pub disk_cache_directory: PathBuf,
+    #[cfg(feature = "disk-cache")]
+    /// It stores the maximum size (in bytes) of the disk cache.
+    pub disk_cache_max_size: u64,
+    /// It stores the format in which the cached search results are serialized.
+    pub cache_serializer: Serializer,
+    /// It stores whether the cached search results are compressed.
+    pub compress_cache_results: bool,
+    /// It stores whether the cached search results are encrypted.
+    pub encrypt_cache_results: bool,
+    #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+    /// It stores the keys (written as `<key id>:<base64 key>`) used to encrypt the cached search
+    /// results, the first one being used to encrypt the new entries.
+    pub cache_encryption_keys: Vec<String>,
     /// It stores the option to whether enable or disable production use.
     pub aggregator: AggregatorConfig,
     /// It stores the option to whether enable or disable logs.
@@ -44,10 +98,32 @@ pub struct Config {
     /// It stores the level of safe search to be used for restricting content in the
     /// search results.
     pub safe_search: u8,
+    /// It stores the policy used to prefetch the neighbouring pages of search results.
+    pub prefetch_pages: PrefetchPolicy,
     /// It stores the TCP connection keepalive duration in seconds.
     pub tcp_connection_keep_alive: u8,
     /// It stores the pool idle connection timeout in seconds.
     pub pool_idle_connection_timeout: u8,
+    /// It stores the domain rules used to boost, lower, pin or remove search results, parsed
+    /// from the domain rules file.
+    pub domain_rules: DomainRules,
+    /// It stores the weighted word and domain lists used to classify the search results for the
+    /// low and moderate safe search levels, parsed from the safe search file.
+    pub safe_search_classifier: SafeSearchClassifier,
+    /// It stores the query categories, loaded from the category list files, which are
+    /// disallowed from the safe search levels configured for them.
+    pub query_classifier: QueryClassifier,
+    /// It stores the hostnames of the result urls mapped to the hostnames of the privacy
+    /// respecting front-ends they should be rewritten to.
+    pub hostname_replacements: HashMap<String, String>,
+    /// It stores the urls of the remote filter lists (in the uBlacklist format) which are used
+    /// as additional blocklists.
+    pub filter_subscriptions: Vec<String>,
+    /// It stores the time (hours) after which the subscribed filter lists are downloaded again.
+    pub filter_subscription_update_interval: u16,
+    /// It stores the token which authenticates the requests to the admin routes, which are
+    /// disabled when it is not set.
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -104,9 +180,26 @@ impl Config {
             }
         };
 
-        #[cfg(any(feature = "redis-cache", feature = "memory-cache"))]
+        let prefetch_pages = match globals.get::<_, Option<String>>("prefetch_pages")? {
+            Some(name) => PrefetchPolicy::parse(&name).unwrap_or_else(|| {
+                log::error!("Config Error: The value of `prefetch_pages` should be one of `off`, `next` or `background`");
+                log::error!("Falling back to using the value `next` for the option");
+                PrefetchPolicy::NextPage
+            }),
+            None => PrefetchPolicy::NextPage,
+        };
+
+        #[cfg(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        ))]
         let parsed_cet = globals.get::<_, u16>("cache_expiry_time")?;
-        #[cfg(any(feature = "redis-cache", feature = "memory-cache"))]
+        #[cfg(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        ))]
         let cache_expiry_time = match parsed_cet {
             0..=59 => {
                 log::error!(
@@ -117,6 +210,163 @@ impl Config {
             }
             _ => parsed_cet,
         };
+        #[cfg(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        ))]
+        let cache_soft_expiry_time = match globals
+            .get::<_, Option<u16>>("cache_soft_expiry_time")?
+        {
+            Some(soft_expiry_time)
+                if soft_expiry_time == 0 || soft_expiry_time >= cache_expiry_time =>
+            {
+                log::error!("Config Error: The value of `cache_soft_expiry_time` should be a non zero positive integer less than `cache_expiry_time`");
+                log::error!("Falling back to disabling the option");
+                None
+            }
+            soft_expiry_time => soft_expiry_time,
+        };
+
+        let cache_serializer = match globals.get::<_, Option<String>>("cache_serializer")? {
+            Some(name) => Serializer::parse(&name).unwrap_or_else(|| {
+                log::error!("Config Error: The value of `cache_serializer` should be `json`, or `messagepack` when the app is compiled with the `msgpack-cache-results` feature");
+                log::error!("Falling back to using the default serializer");
+                Serializer::default()
+            }),
+            None => Serializer::default(),
+        };
+
+        #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+        let cache_encryption_keys = cache_encryption_keys(&globals)?;
+        // The keys are checked here so that malformed keys are reported as a config error
+        // instead of failing once the cache is built.
+        #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+        if !cache_encryption_keys.is_empty() {
+            CacheCipher::from_keys(&cache_encryption_keys)
+                .map_err(|error| format!("Invalid cache encryption keys: {error:?}"))?;
+        }
+
+        #[cfg(feature = "redis-cache")]
+        let redis_pool_size = match globals.get::<_, Option<u8>>("redis_pool_size")? {
+            Some(0) => {
+                log::error!("Config Error: The value of `redis_pool_size` should be a non zero positive integer");
+                log::error!("Falling back to using the value `5` for the option");
+                5
+            }
+            Some(pool_size) => pool_size,
+            None => 5,
+        };
+
+        #[cfg(feature = "memory-cache")]
+        let memory_cache_max_size_megabytes: u64 = match globals
+            .get::<_, Option<u64>>("memory_cache_max_size")?
+        {
+            Some(0) => {
+                log::error!("Config Error: The value of `memory_cache_max_size` should be a non zero positive integer");
+                log::error!("Falling back to using the value `64` for the option");
+                64
+            }
+            Some(megabytes) => megabytes,
+            None => 64,
+        };
+        #[cfg(feature = "memory-cache")]
+        let cache_time_to_idle = match globals.get::<_, Option<u16>>("cache_time_to_idle")? {
+            Some(0) => {
+                log::error!("Config Error: The value of `cache_time_to_idle` should be a non zero positive integer");
+                log::error!("Falling back to disabling the option");
+                None
+            }
+            time_to_idle => time_to_idle,
+        };
+        #[cfg(feature = "memory-cache")]
+        let rendered_page_cache_size = match globals
+            .get::<_, Option<u64>>("rendered_page_cache_size")?
+        {
+            Some(0) => {
+                log::error!("Config Error: The value of `rendered_page_cache_size` should be a non zero positive integer");
+                log::error!("Falling back to disabling the option");
+                None
+            }
+            Some(megabytes) => Some(megabytes_to_bytes("rendered_page_cache_size", megabytes)?),
+            None => None,
+        };
+
+        #[cfg(feature = "disk-cache")]
+        let disk_cache_directory = globals
+            .get::<_, Option<String>>("disk_cache_directory")?
+            .map(PathBuf::from)
+            .unwrap_or_else(default_disk_cache_directory);
+        #[cfg(feature = "disk-cache")]
+        let disk_cache_max_size_megabytes: u64 = match globals
+            .get::<_, Option<u64>>("disk_cache_max_size")?
+        {
+            Some(0) => {
+                log::error!("Config Error: The value of `disk_cache_max_size` should be a non zero positive integer");
+                log::error!("Falling back to using the value `256` for the option");
+                256
+            }
+            Some(megabytes) => megabytes,
+            None => 256,
+        };
+
+        // The domain rules file is optional, so a missing file results in no rules being applied.
+        let domain_rules = match file_path(FileType::DomainRules) {
+            Ok(path) => DomainRules::from_file(path)?,
+            Err(_) => DomainRules::default(),
+        };
+
+        // The safe search file is optional as well, so a missing file disables the classifier.
+        let safe_search_classifier = match file_path(FileType::SafeSearch) {
+            Ok(path) => SafeSearchClassifier::from_file(path)?,
+            Err(_) => SafeSearchClassifier::default(),
+        };
+
+        let blocked_query_categories: HashMap<String, u8> = globals
+            .get::<_, Option<HashMap<String, u8>>>("blocked_query_categories")?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(category, level)| match level {
+                1..=4 => (category, level),
+                _ => {
+                    log::error!("Config Error: The safe search level of the `{category}` query category should be a non zero positive integer from 1 to 4.");
+                    log::error!("Falling back to using the value `4` for the category");
+                    (category, 4)
+                }
+            })
+            .collect();
+        let query_classifier = match file_path(FileType::QueryCategories) {
+            _ if blocked_query_categories.is_empty() => QueryClassifier::default(),
+            Ok(path) => QueryClassifier::from_directory(Path::new(path), &blocked_query_categories),
+            Err(error) => {
+                log::error!("Config Error: The query categories cannot be loaded: {error}");
+                QueryClassifier::default()
+            }
+        };
+
+        let filter_subscription_update_interval = match globals
+            .get::<_, Option<u16>>("filter_subscription_update_interval")?
+        {
+            Some(0) => {
+                log::error!("Config Error: The value of `filter_subscription_update_interval` should be a non zero positive integer");
+                log::error!("Falling back to using the value `1` for the option");
+                1
+            }
+            Some(hours) => hours,
+            None => 24,
+        };
+
+        let admin_token = match std::env::var("WEBSURFX_ADMIN_TOKEN")
+            .ok()
+            .or(globals.get::<_, Option<String>>("admin_token")?)
+        {
+            Some(token) if token.trim().is_empty() => {
+                log::error!("Config Error: The value of `admin_token` should not be empty");
+                log::error!("Falling back to disabling the admin routes");
+                None
+            }
+            token => token,
+        };
 
         Ok(Config {
             port: globals.get::<_, u16>("port")?,
@@ -128,6 +378,12 @@ impl Config {
             ),
             #[cfg(feature = "redis-cache")]
             redis_url: globals.get::<_, String>("redis_url")?,
+            #[cfg(feature = "redis-cache")]
+            redis_pool_size,
+            #[cfg(feature = "redis-cache")]
+            redis_key_prefix: globals
+                .get::<_, Option<String>>("redis_key_prefix")?
+                .unwrap_or_else(|| "websurfx:".to_owned()),
             aggregator: AggregatorConfig {
                 random_delay: globals.get::<_, bool>("production_use")?,
             },
@@ -146,10 +402,152 @@ impl Config {
                 time_limit: rate_limiter["time_limit"],
             },
             safe_search,
-            #[cfg(any(feature = "redis-cache", feature = "memory-cache"))]
+            prefetch_pages,
+            #[cfg(any(
+                feature = "redis-cache",
+                feature = "memory-cache",
+                feature = "disk-cache"
+            ))]
             cache_expiry_time,
+            #[cfg(any(
+                feature = "redis-cache",
+                feature = "memory-cache",
+                feature = "disk-cache"
+            ))]
+            cache_soft_expiry_time,
+            cache_serializer,
+            compress_cache_results: globals
+                .get::<_, Option<bool>>("compress_cache_results")?
+                .unwrap_or(cfg!(any(
+                    feature = "compress-cache-results",
+                    feature = "cec-cache-results"
+                ))),
+            encrypt_cache_results: globals
+                .get::<_, Option<bool>>("encrypt_cache_results")?
+                .unwrap_or(cfg!(any(
+                    feature = "encrypt-cache-results",
+                    feature = "cec-cache-results"
+                ))),
+            #[cfg(feature = "memory-cache")]
+            memory_cache_max_size: megabytes_to_bytes(
+                "memory_cache_max_size",
+                memory_cache_max_size_megabytes,
+            )?,
+            #[cfg(feature = "memory-cache")]
+            cache_time_to_idle,
+            #[cfg(feature = "memory-cache")]
+            rendered_page_cache_size,
+            #[cfg(feature = "disk-cache")]
+            disk_cache_directory,
+            #[cfg(feature = "disk-cache")]
+            disk_cache_max_size: megabytes_to_bytes(
+                "disk_cache_max_size",
+                disk_cache_max_size_megabytes,
+            )?,
+            #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+            cache_encryption_keys,
+            domain_rules,
+            safe_search_classifier,
+            query_classifier,
+            hostname_replacements: globals
+                .get::<_, Option<HashMap<String, String>>>("hostname_replacements")?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(from, to)| (from.to_lowercase(), to.to_lowercase()))
+                .collect(),
+            filter_subscriptions: globals
+                .get::<_, Option<Vec<String>>>("filter_subscriptions")?
+                .unwrap_or_default(),
+            filter_subscription_update_interval,
+            admin_token,
         })
     }
+
+    /// Returns the hash of the options which affect the search results without being part of
+    /// the cache key, which are the domain rules, the safe search and query classifiers and the
+    /// hostname replacements, so that the results cached with the previous options are not
+    /// served once the config is reloaded. As it only depends on the contents of the options, it
+    /// stays the same across restarts and between the servers sharing a cache.
+    pub fn results_hash(&self) -> blake3::Hash {
+        let mut hostname_replacements: Vec<(&String, &String)> =
+            self.hostname_replacements.iter().collect();
+        hostname_replacements.sort_unstable();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.domain_rules.content_hash().as_bytes());
+        hasher.update(self.safe_search_classifier.content_hash().as_bytes());
+        hasher.update(self.query_classifier.content_hash().as_bytes());
+        hasher.update(format!("{hostname_replacements:?}").as_bytes());
+        hasher.finalize()
+    }
+}
+
+/// A helper function which collects the cache encryption keys from the
+/// `WEBSURFX_CACHE_ENCRYPTION_KEYS` environment variable (comma separated), the key file set by the
+/// `cache_encryption_key_file` option (one key per line) or the `cache_encryption_keys` option,
+/// in that order of precedence.
+///
+/// # Arguments
+///
+/// * `globals` - It takes the globals of the parsed config file as an argument.
+///
+/// # Error
+///
+/// Returns a lua error if the options have the wrong type or an io error if the key file cannot be
+/// read.
+#[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+fn cache_encryption_keys(
+    globals: &mlua::Table<'_>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if let Ok(keys) = std::env::var("WEBSURFX_CACHE_ENCRYPTION_KEYS") {
+        return Ok(keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_owned)
+            .collect());
+    }
+
+    if let Some(key_file) = globals.get::<_, Option<String>>("cache_encryption_key_file")? {
+        return Ok(fs::read_to_string(key_file)?
+            .lines()
+            .map(str::trim)
+            .filter(|key| !key.is_empty() && !key.starts_with('#'))
+            .map(str::to_owned)
+            .collect());
+    }
+
+    Ok(globals
+        .get::<_, Option<Vec<String>>>("cache_encryption_keys")?
+        .unwrap_or_default())
+}
+
+/// A helper function which converts the size given in megabytes by a config option to bytes.
+///
+/// # Arguments
+///
+/// * `option` - It takes the name of the config option as an argument.
+/// * `megabytes` - It takes the size in megabytes as an argument.
+///
+/// # Error
+///
+/// Returns an error if the size in bytes does not fit in a `u64`.
+#[cfg(any(feature = "memory-cache", feature = "disk-cache"))]
+fn megabytes_to_bytes(option: &str, megabytes: u64) -> Result<u64, Box<dyn std::error::Error>> {
+    megabytes
+        .checked_mul(1024 * 1024)
+        .ok_or_else(|| format!("The value of `{option}` is too large").into())
+}
+
+/// A helper function which returns the default directory of the disk cache, which is the
+/// `websurfx` folder under the user's cache directory (`$XDG_CACHE_HOME` or `~/.cache`).
+#[cfg(feature = "disk-cache")]
+fn default_disk_cache_directory() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("websurfx")
 }
 
 /// a helper function that sets the proper logging level
@@ -178,3 +576,80 @@ fn set_logging_level(debug: bool, logging: bool) {
 
     env_logger::Builder::new().filter(None, log_level).init();
 }
+
+/// This module provides the config shared by the tests of the other modules.
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+
+    /// Builds a config holding the default options of the config file, without reading any file.
+    pub fn config() -> Config {
+        Config {
+            port: 8080,
+            binding_ip: "127.0.0.1".to_owned(),
+            style: Style::new(
+                "simple".to_owned(),
+                "catppuccin-mocha".to_owned(),
+                Some("simple-frosted-glow".to_owned()),
+            ),
+            #[cfg(feature = "redis-cache")]
+            redis_url: "redis://127.0.0.1:8082".to_owned(),
+            #[cfg(feature = "redis-cache")]
+            redis_pool_size: 5,
+            #[cfg(feature = "redis-cache")]
+            redis_key_prefix: "websurfx:".to_owned(),
+            #[cfg(any(
+                feature = "redis-cache",
+                feature = "memory-cache",
+                feature = "disk-cache"
+            ))]
+            cache_expiry_time: 600,
+            #[cfg(any(
+                feature = "redis-cache",
+                feature = "memory-cache",
+                feature = "disk-cache"
+            ))]
+            cache_soft_expiry_time: None,
+            #[cfg(feature = "memory-cache")]
+            memory_cache_max_size: 64 * 1024 * 1024,
+            #[cfg(feature = "memory-cache")]
+            cache_time_to_idle: None,
+            #[cfg(feature = "memory-cache")]
+            rendered_page_cache_size: None,
+            #[cfg(feature = "disk-cache")]
+            disk_cache_directory: default_disk_cache_directory(),
+            #[cfg(feature = "disk-cache")]
+            disk_cache_max_size: 256 * 1024 * 1024,
+            cache_serializer: Serializer::default(),
+            compress_cache_results: false,
+            encrypt_cache_results: false,
+            #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+            cache_encryption_keys: Vec::new(),
+            aggregator: AggregatorConfig {
+                random_delay: false,
+            },
+            logging: true,
+            debug: false,
+            adaptive_window: false,
+            upstream_search_engines: HashMap::from([("DuckDuckGo".to_owned(), true)]),
+            request_timeout: 30,
+            threads: 10,
+            client_connection_keep_alive: 120,
+            rate_limiter: RateLimiter {
+                number_of_requests: 20,
+                time_limit: 3,
+            },
+            safe_search: 2,
+            prefetch_pages: PrefetchPolicy::NextPage,
+            tcp_connection_keep_alive: 30,
+            pool_idle_connection_timeout: 30,
+            domain_rules: DomainRules::default(),
+            safe_search_classifier: SafeSearchClassifier::default(),
+            query_classifier: QueryClassifier::default(),
+            hostname_replacements: HashMap::new(),
+            filter_subscriptions: Vec::new(),
+            filter_subscription_update_interval: 24,
+            admin_token: None,
+        }
+    }
+}
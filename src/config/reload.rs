@@ -0,0 +1,540 @@
+//! This module provides the functionality to reload the config file while the server is running.
+//! The config is reloaded when the server receives the `SIGHUP` signal or when the config file or
+//! one of the list files parsed along with it changes, and the new config is swapped in for all
+//! the request handlers once it has been validated. The options which are only read on startup
+//! keep their current values until the server is restarted.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::watch;
+
+use crate::{
+    handler::{file_path, FileType},
+    results::filter_engine::FilterEngine,
+};
+
+use super::parser::Config;
+
+/// The time between two checks of whether the config file or the list files parsed along with it
+/// have changed.
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The files which are parsed along with the config file, whose changes also reload the config.
+/// The query category lists are watched separately, as the whole categories folder is read.
+const WATCHED_FILES: [FileType; 3] = [
+    FileType::Config,
+    FileType::DomainRules,
+    FileType::SafeSearch,
+];
+
+/// A named struct which stores a config along with the filter engine built from it, so that they
+/// are always loaded together.
+pub struct ConfigSnapshot {
+    /// It stores the config.
+    pub config: Arc<Config>,
+    /// It stores the filter engine built from the config.
+    pub filter_engine: Arc<FilterEngine>,
+    /// It stores the hash of the options of the config which affect the search results, which
+    /// is part of the cache key.
+    pub results_hash: blake3::Hash,
+}
+
+/// A named struct which stores the snapshot of the current config, which is swapped for a new
+/// one whenever the config is reloaded. The request handlers load the snapshot once per request,
+/// so a request keeps using the same config and filter engine until it completes.
+pub struct SharedConfig {
+    /// It stores the snapshot of the current config.
+    snapshot: ArcSwap<ConfigSnapshot>,
+    /// It notifies the background tasks which depend on the config that it was reloaded.
+    reloaded: watch::Sender<()>,
+}
+
+impl SharedConfig {
+    /// Constructs a new `SharedConfig` from the parsed config, building the filter engine from
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes the parsed config as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the filter lists cannot be found, read or compiled.
+    pub fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let filter_engine = FilterEngine::new(&config)?;
+        Ok(SharedConfig {
+            snapshot: ArcSwap::from_pointee(ConfigSnapshot {
+                results_hash: config.results_hash(),
+                config: Arc::new(config),
+                filter_engine: Arc::new(filter_engine),
+            }),
+            reloaded: watch::channel(()).0,
+        })
+    }
+
+    /// A getter function that gets the snapshot of the current config, holding the config along
+    /// with the filter engine built from it.
+    pub fn snapshot(&self) -> Arc<ConfigSnapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// A getter function that gets the current config.
+    pub fn load(&self) -> Arc<Config> {
+        Arc::clone(&self.snapshot.load().config)
+    }
+
+    /// Subscribes to the reloads of the config. The receiver is told about every reload which
+    /// happens after this call, including the ones happening while it is not waiting for them.
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.reloaded.subscribe()
+    }
+
+    /// Parses the config file again and swaps the new config in if it is valid.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the config file cannot be parsed, if the theme, colorscheme or
+    /// animation does not exist or if the filter lists cannot be loaded, in which case the
+    /// current config is kept.
+    ///
+    /// # Returns
+    ///
+    /// Returns the names of the changed options which only take effect once the server is
+    /// restarted.
+    pub fn reload(&self) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+        self.replace(Config::parse(true)?)
+    }
+
+    /// Swaps the given config in if it is valid, keeping the current values of the options
+    /// which are only read on startup. The filter engine is only rebuilt when the subscription
+    /// lists changed, as the filter lists themselves are reloaded as soon as their files change.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes the reloaded config as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the theme, colorscheme or animation does not exist or if the filter
+    /// lists cannot be loaded, in which case the current config is kept.
+    ///
+    /// # Returns
+    ///
+    /// Returns the names of the changed options which only take effect once the server is
+    /// restarted.
+    fn replace(&self, mut config: Config) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+        validate_style(&config)?;
+
+        let current = self.snapshot();
+        let filter_engine = if config.filter_subscriptions != current.config.filter_subscriptions {
+            Arc::new(FilterEngine::new(&config)?)
+        } else {
+            Arc::clone(&current.filter_engine)
+        };
+
+        let restart_required = keep_startup_options(&current.config, &mut config);
+        self.snapshot.store(Arc::new(ConfigSnapshot {
+            results_hash: config.results_hash(),
+            config: Arc::new(config),
+            filter_engine,
+        }));
+        self.reloaded.send_replace(());
+        Ok(restart_required)
+    }
+}
+
+/// Reloads the config whenever the server receives the `SIGHUP` signal or the config file or one
+/// of the list files parsed along with it changes. This function runs until the server is stopped
+/// and it is meant to be spawned as a background task on startup.
+///
+/// # Arguments
+///
+/// * `config` - It takes the shared config to be reloaded as an argument.
+pub async fn watch_config(config: &'static SharedConfig) {
+    #[cfg(unix)]
+    let mut hangup = signal(SignalKind::hangup())
+        .map_err(|error| {
+            log::error!("Config Error: Failed to listen to the SIGHUP signal: {error}")
+        })
+        .ok();
+    #[cfg(not(unix))]
+    let mut hangup = None;
+
+    let mut modified = modified_times();
+    loop {
+        let reason = tokio::select! {
+            _ = tokio::time::sleep(CONFIG_CHECK_INTERVAL) => {
+                let current = modified_times();
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+                "the config files changed"
+            }
+            _ = next_hangup(&mut hangup) => "the SIGHUP signal was received",
+        };
+
+        log::info!("Reloading the config as {reason}");
+        // The config and the lists are parsed on a blocking thread to keep serving the requests.
+        let reloaded =
+            tokio::task::spawn_blocking(move || config.reload().map_err(|error| error.to_string()))
+                .await;
+        match reloaded {
+            Ok(Ok(restart_required)) if restart_required.is_empty() => {
+                log::info!("Reloaded the config")
+            }
+            Ok(Ok(restart_required)) => log::warn!(
+                "Reloaded the config, restart the server to apply the changes to: {}",
+                restart_required.join(", ")
+            ),
+            Ok(Err(error)) => log::error!(
+                "Config Error: Failed to reload the config, keeping the current one: {error}"
+            ),
+            Err(error) => log::error!("Config Error: Failed to reload the config: {error}"),
+        }
+    }
+}
+
+/// Waits for the next `SIGHUP` signal, or forever if the signal cannot be listened to.
+///
+/// # Arguments
+///
+/// * `hangup` - It takes the listener of the `SIGHUP` signal as an argument, if any.
+#[cfg(unix)]
+async fn next_hangup(hangup: &mut Option<Signal>) {
+    if let Some(hangup) = hangup {
+        if hangup.recv().await.is_some() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// Waits forever, as the `SIGHUP` signal does not exist on this platform.
+///
+/// # Arguments
+///
+/// * `hangup` - It takes the missing listener of the signal as an argument.
+#[cfg(not(unix))]
+async fn next_hangup(_hangup: &mut Option<()>) {
+    std::future::pending().await
+}
+
+/// A helper function which returns the modification times of the config file and of the list
+/// files parsed along with it, including the query category lists (`<category>.txt`) of the
+/// categories folder, the missing files having no modification time.
+fn modified_times() -> Vec<Option<SystemTime>> {
+    let modified = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut times: Vec<Option<SystemTime>> = WATCHED_FILES
+        .into_iter()
+        .map(|file_type| {
+            file_path(file_type)
+                .ok()
+                .and_then(|path| modified(Path::new(path)))
+        })
+        .collect();
+
+    if let Ok(directory) = file_path(FileType::QueryCategories) {
+        // The folder itself changes whenever a category list is added, removed or renamed.
+        times.push(modified(Path::new(directory)));
+        let mut lists: Vec<PathBuf> = fs::read_dir(directory)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect();
+        lists.sort_unstable();
+        times.extend(lists.iter().map(PathBuf::as_path).map(modified));
+    }
+    times
+}
+
+/// A helper function which checks that the theme, colorscheme and animation selected in the
+/// config exist in the public folder.
+///
+/// # Arguments
+///
+/// * `config` - It takes the parsed config as an argument.
+///
+/// # Error
+///
+/// Returns an error if the public folder or any of the selected styles cannot be found.
+fn validate_style(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let public_folder_path = file_path(FileType::Theme)?;
+    let styles = [
+        ("themes", Some(&config.style.theme)),
+        ("colorschemes", Some(&config.style.colorscheme)),
+        ("animations", config.style.animation.as_ref()),
+    ];
+
+    for (folder, name) in styles {
+        let Some(name) = name else { continue };
+        let path = format!("{public_folder_path}/static/{folder}/{name}.css");
+        if !Path::new(&path).exists() {
+            return Err(format!("The style `{name}` was not found in the {folder} folder").into());
+        }
+    }
+    Ok(())
+}
+
+/// A helper function which copies the options which are only read on startup from the current
+/// config into the reloaded one, so that the server keeps running with the options it was
+/// started with.
+///
+/// # Arguments
+///
+/// * `current` - It takes the config in use as an argument.
+/// * `new` - It takes the reloaded config as an argument.
+///
+/// # Returns
+///
+/// Returns the names of the startup options which changed, which only take effect after a
+/// restart.
+fn keep_startup_options(current: &Config, new: &mut Config) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    keep_option("port", &current.port, &mut new.port, &mut changed);
+    keep_option(
+        "binding_ip",
+        &current.binding_ip,
+        &mut new.binding_ip,
+        &mut changed,
+    );
+    keep_option("threads", &current.threads, &mut new.threads, &mut changed);
+    keep_option(
+        "client_connection_keep_alive",
+        &current.client_connection_keep_alive,
+        &mut new.client_connection_keep_alive,
+        &mut changed,
+    );
+    keep_option(
+        "rate_limiter",
+        &current.rate_limiter,
+        &mut new.rate_limiter,
+        &mut changed,
+    );
+    keep_option("logging", &current.logging, &mut new.logging, &mut changed);
+    keep_option("debug", &current.debug, &mut new.debug, &mut changed);
+    keep_option(
+        "request_timeout",
+        &current.request_timeout,
+        &mut new.request_timeout,
+        &mut changed,
+    );
+    keep_option(
+        "tcp_connection_keep_alive",
+        &current.tcp_connection_keep_alive,
+        &mut new.tcp_connection_keep_alive,
+        &mut changed,
+    );
+    keep_option(
+        "pool_idle_connection_timeout",
+        &current.pool_idle_connection_timeout,
+        &mut new.pool_idle_connection_timeout,
+        &mut changed,
+    );
+    keep_option(
+        "adaptive_window",
+        &current.adaptive_window,
+        &mut new.adaptive_window,
+        &mut changed,
+    );
+    keep_option(
+        "cache_serializer",
+        &current.cache_serializer,
+        &mut new.cache_serializer,
+        &mut changed,
+    );
+    keep_option(
+        "compress_cache_results",
+        &current.compress_cache_results,
+        &mut new.compress_cache_results,
+        &mut changed,
+    );
+    keep_option(
+        "encrypt_cache_results",
+        &current.encrypt_cache_results,
+        &mut new.encrypt_cache_results,
+        &mut changed,
+    );
+
+    #[cfg(any(
+        feature = "redis-cache",
+        feature = "memory-cache",
+        feature = "disk-cache"
+    ))]
+    {
+        keep_option(
+            "cache_expiry_time",
+            &current.cache_expiry_time,
+            &mut new.cache_expiry_time,
+            &mut changed,
+        );
+        keep_option(
+            "cache_soft_expiry_time",
+            &current.cache_soft_expiry_time,
+            &mut new.cache_soft_expiry_time,
+            &mut changed,
+        );
+    }
+    #[cfg(feature = "redis-cache")]
+    {
+        keep_option(
+            "redis_url",
+            &current.redis_url,
+            &mut new.redis_url,
+            &mut changed,
+        );
+        keep_option(
+            "redis_pool_size",
+            &current.redis_pool_size,
+            &mut new.redis_pool_size,
+            &mut changed,
+        );
+        keep_option(
+            "redis_key_prefix",
+            &current.redis_key_prefix,
+            &mut new.redis_key_prefix,
+            &mut changed,
+        );
+    }
+    #[cfg(feature = "memory-cache")]
+    {
+        keep_option(
+            "memory_cache_max_size",
+            &current.memory_cache_max_size,
+            &mut new.memory_cache_max_size,
+            &mut changed,
+        );
+        keep_option(
+            "cache_time_to_idle",
+            &current.cache_time_to_idle,
+            &mut new.cache_time_to_idle,
+            &mut changed,
+        );
+        keep_option(
+            "rendered_page_cache_size",
+            &current.rendered_page_cache_size,
+            &mut new.rendered_page_cache_size,
+            &mut changed,
+        );
+    }
+    #[cfg(feature = "disk-cache")]
+    {
+        keep_option(
+            "disk_cache_directory",
+            &current.disk_cache_directory,
+            &mut new.disk_cache_directory,
+            &mut changed,
+        );
+        keep_option(
+            "disk_cache_max_size",
+            &current.disk_cache_max_size,
+            &mut new.disk_cache_max_size,
+            &mut changed,
+        );
+    }
+    #[cfg(any(feature = "encrypt-cache-results", feature = "cec-cache-results"))]
+    keep_option(
+        "cache_encryption_keys",
+        &current.cache_encryption_keys,
+        &mut new.cache_encryption_keys,
+        &mut changed,
+    );
+
+    changed
+}
+
+/// A helper function which copies the value of a startup option from the current config into the
+/// reloaded one, recording the name of the option when its value changed.
+///
+/// # Arguments
+///
+/// * `option` - It takes the name of the option as an argument.
+/// * `current` - It takes the value of the option in the config in use as an argument.
+/// * `new` - It takes the value of the option in the reloaded config as an argument.
+/// * `changed` - It takes the names of the changed startup options as an argument.
+fn keep_option<T: Clone + PartialEq>(
+    option: &'static str,
+    current: &T,
+    new: &mut T,
+    changed: &mut Vec<&'static str>,
+) {
+    if current != new {
+        new.clone_from(current);
+        changed.push(option);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::cache_key::CacheKey, config::parser::test_support::config,
+        results::domain_rules::DomainRules,
+    };
+
+    #[test]
+    fn test_startup_options_are_kept_on_reload() {
+        let shared_config = SharedConfig::new(config()).unwrap();
+        let filter_engine = shared_config.snapshot().filter_engine.clone();
+        let mut reloaded = shared_config.subscribe();
+
+        let mut new = config();
+        new.safe_search = 0;
+        new.style.colorscheme = "monokai".to_owned();
+        new.port += 1;
+        new.debug = true;
+        new.rate_limiter.time_limit += 1;
+        assert_eq!(
+            shared_config.replace(new).unwrap(),
+            ["port", "rate_limiter", "debug"]
+        );
+        assert!(reloaded.has_changed().unwrap());
+
+        let snapshot = shared_config.snapshot();
+        assert_eq!(snapshot.config.safe_search, 0);
+        assert_eq!(snapshot.config.style.colorscheme, "monokai");
+        assert_eq!(snapshot.config.port, 8080);
+        assert!(!snapshot.config.debug);
+        assert!(Arc::ptr_eq(&snapshot.filter_engine, &filter_engine));
+    }
+
+    #[test]
+    fn test_invalid_styles_keep_the_current_config() {
+        let shared_config = SharedConfig::new(config()).unwrap();
+        let reloaded = shared_config.subscribe();
+
+        let mut new = config();
+        new.style.theme = "missing".to_owned();
+        assert!(shared_config.replace(new).is_err());
+        assert_eq!(shared_config.load().style.theme, "simple");
+        assert!(!reloaded.has_changed().unwrap());
+    }
+
+    #[test]
+    fn test_reloaded_domain_rules_change_the_cache_key() {
+        let shared_config = SharedConfig::new(config()).unwrap();
+        let key = CacheKey::new("rust", 0, 0, ["bing"]);
+        let before = key
+            .clone()
+            .with_config_hash(shared_config.snapshot().results_hash);
+
+        let mut new = config();
+        new.domain_rules = DomainRules::parse(["remove *.pinterest.com"]);
+        shared_config.replace(new).unwrap();
+        let after = key.with_config_hash(shared_config.snapshot().results_hash);
+        assert_ne!(before.hash(), after.hash());
+    }
+}
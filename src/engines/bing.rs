@@ -108,13 +108,12 @@ impl SearchEngine for Bing {
         }
 
         let re_span = Regex::new(r#"<span.*?>.*?(?:</span>&nbsp;·|</span>)"#).unwrap();
-        let re_strong = Regex::new(r#"(<strong>|</strong>)"#).unwrap();
 
         // scrape all the results from the html
         self.parser
             .parse_for_results(&document, |title, url, desc| {
                 Some(SearchResult::new(
-                    &re_strong.replace_all(title.inner_html().trim(), ""),
+                    title.inner_html().trim(),
                     url.value().attr("href").unwrap(),
                     &re_span.replace_all(desc.inner_html().trim(), ""),
                     &["bing"],
@@ -17,6 +17,12 @@ const CONFIG_FILE_NAME: &str = "config.lua";
 const ALLOWLIST_FILE_NAME: &str = "allowlist.txt";
 /// The constant holding the name of the BlockList text file.
 const BLOCKLIST_FILE_NAME: &str = "blocklist.txt";
+/// The constant holding the name of the domain rules text file.
+const DOMAIN_RULES_FILE_NAME: &str = "domain_rules.txt";
+/// The constant holding the name of the safe search word and domain lists text file.
+const SAFE_SEARCH_FILE_NAME: &str = "safe_search.txt";
+/// The constant holding the name of the folder containing the query category list files.
+const QUERY_CATEGORIES_DIRECTORY_NAME: &str = "categories";
 
 /// An enum type which provides different variants to handle paths for various files/folders.
 #[derive(Hash, PartialEq, Eq, Debug)]
@@ -27,6 +33,12 @@ pub enum FileType {
     AllowList,
     /// This variant handles all the paths associated with the BlockList text file.
     BlockList,
+    /// This variant handles all the paths associated with the domain rules text file.
+    DomainRules,
+    /// This variant handles all the paths associated with the safe search lists text file.
+    SafeSearch,
+    /// This variant handles all the paths associated with the query categories folder.
+    QueryCategories,
     /// This variant handles all the paths associated with the public folder (Theme folder).
     Theme,
 }
@@ -96,6 +108,51 @@ pub fn file_path(file_type: FileType) -> Result<&'static str, Error> {
                         format!("./{}/{}", COMMON_DIRECTORY_NAME, BLOCKLIST_FILE_NAME),
                     ],
                 ),
+                (
+                    FileType::DomainRules,
+                    vec![
+                        format!(
+                            "{}/.config/{}/{}",
+                            home, COMMON_DIRECTORY_NAME, DOMAIN_RULES_FILE_NAME
+                        ),
+                        format!(
+                            "/etc/xdg/{}/{}",
+                            COMMON_DIRECTORY_NAME, DOMAIN_RULES_FILE_NAME
+                        ),
+                        format!("./{}/{}", COMMON_DIRECTORY_NAME, DOMAIN_RULES_FILE_NAME),
+                    ],
+                ),
+                (
+                    FileType::SafeSearch,
+                    vec![
+                        format!(
+                            "{}/.config/{}/{}",
+                            home, COMMON_DIRECTORY_NAME, SAFE_SEARCH_FILE_NAME
+                        ),
+                        format!(
+                            "/etc/xdg/{}/{}",
+                            COMMON_DIRECTORY_NAME, SAFE_SEARCH_FILE_NAME
+                        ),
+                        format!("./{}/{}", COMMON_DIRECTORY_NAME, SAFE_SEARCH_FILE_NAME),
+                    ],
+                ),
+                (
+                    FileType::QueryCategories,
+                    vec![
+                        format!(
+                            "{}/.config/{}/{}/",
+                            home, COMMON_DIRECTORY_NAME, QUERY_CATEGORIES_DIRECTORY_NAME
+                        ),
+                        format!(
+                            "/etc/xdg/{}/{}/",
+                            COMMON_DIRECTORY_NAME, QUERY_CATEGORIES_DIRECTORY_NAME
+                        ),
+                        format!(
+                            "./{}/{}/",
+                            COMMON_DIRECTORY_NAME, QUERY_CATEGORIES_DIRECTORY_NAME
+                        ),
+                    ],
+                ),
             ])
         })
         .get(&file_type)
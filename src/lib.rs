@@ -28,13 +28,18 @@ use actix_web::{
     web, App, HttpServer,
 };
 use cache::cacher::{Cacher, SharedCache};
-use config::parser::Config;
+#[cfg(feature = "memory-cache")]
+use cache::page_cache::PageCache;
+use config::reload::{watch_config, SharedConfig};
 use handler::{file_path, FileType};
+use results::{filter_engine::watch_filter_lists, subscriptions::update_subscriptions};
 
 /// A static constant for holding the cache struct.
 static SHARED_CACHE: OnceLock<SharedCache> = OnceLock::new();
 
-/// Runs the web server on the provided TCP listener and returns a `Server` instance.
+/// Runs the web server on the provided TCP listener and returns a `Server` instance. It must be
+/// called from within a tokio runtime (as in the example below), as the background tasks which
+/// keep the subscription lists and the config up to date are spawned on the current runtime.
 ///
 /// # Arguments
 ///
@@ -44,32 +49,80 @@ static SHARED_CACHE: OnceLock<SharedCache> = OnceLock::new();
 ///
 /// Returns a `Result` containing a `Server` instance on success, or an `std::io::Error` on failure.
 ///
+/// # Panics
+///
+/// Panics if it is called outside of a tokio runtime.
+///
 /// # Example
 ///
 /// ```rust
 /// use std::{net::TcpListener, sync::OnceLock};
-/// use websurfx::{config::parser::Config, run, cache::cacher::create_cache};
+/// use websurfx::{
+///     cache::cacher::create_cache,
+///     config::{parser::Config, reload::SharedConfig},
+///     run,
+/// };
 ///
 /// /// A static constant for holding the parsed config.
-/// static CONFIG: OnceLock<Config> = OnceLock::new();
+/// static CONFIG: OnceLock<SharedConfig> = OnceLock::new();
 ///
 /// #[tokio::main]
 /// async fn main(){
 ///     // Initialize the parsed config globally.
-///     let config = CONFIG.get_or_init(|| Config::parse(true).unwrap());
+///     let config = CONFIG
+///         .get_or_init(|| SharedConfig::new(Config::parse(true).unwrap()).unwrap());
 ///     let listener = TcpListener::bind("127.0.0.1:8080").expect("Failed to bind address");
-///     let cache = create_cache(config).await;
-///     let server = run(listener,&config,cache).expect("Failed to start server");
+///     let cache = create_cache(&config.load()).await;
+///     let server = run(listener,config,cache).expect("Failed to start server");
 /// }
 /// ```
 pub fn run(
     listener: TcpListener,
-    config: &'static Config,
+    shared_config: &'static SharedConfig,
     cache: impl Cacher + 'static,
 ) -> std::io::Result<Server> {
     let public_folder_path: &str = file_path(FileType::Theme)?;
+    // The options read below only take effect on startup, the handlers load the current config.
+    let config = shared_config.load();
+
+    let cache = SHARED_CACHE.get_or_init(|| {
+        #[cfg(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        ))]
+        let shared_cache = SharedCache::new(cache)
+            .with_expiry_times(config.cache_soft_expiry_time, config.cache_expiry_time);
+
+        #[cfg(feature = "memory-cache")]
+        if let Some(max_size) = config.rendered_page_cache_size {
+            return shared_cache
+                .with_page_cache(PageCache::new(config.cache_expiry_time, max_size));
+        }
+
+        #[cfg(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        ))]
+        return shared_cache;
+
+        #[cfg(not(any(
+            feature = "redis-cache",
+            feature = "memory-cache",
+            feature = "disk-cache"
+        )))]
+        return SharedCache::new(cache);
+    });
+
+    // Keep the subscribed filter lists up to date in the background.
+    tokio::spawn(update_subscriptions(shared_config));
+    // Recompile the filter lists in the background whenever their files change.
+    tokio::spawn(watch_filter_lists(shared_config));
+    // Reload the config on SIGHUP or whenever the config files change.
+    tokio::spawn(watch_config(shared_config));
 
-    let cache = SHARED_CACHE.get_or_init(|| SharedCache::new(cache));
+    let rate_limiter = config.rate_limiter;
 
     let server = HttpServer::new(move || {
         let cors: Cors = Cors::default()
@@ -86,13 +139,13 @@ pub fn run(
             // Compress the responses provided by the server for the client requests.
             .wrap(Compress::default())
             .wrap(Logger::default()) // added logging middleware for logging.
-            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(shared_config))
             .app_data(web::Data::new(cache))
             .wrap(cors)
             .wrap(Governor::new(
                 &GovernorConfigBuilder::default()
-                    .per_second(config.rate_limiter.time_limit as u64)
-                    .burst_size(config.rate_limiter.number_of_requests as u32)
+                    .per_second(rate_limiter.time_limit as u64)
+                    .burst_size(rate_limiter.number_of_requests as u32)
                     .finish()
                     .unwrap(),
             ))
@@ -110,6 +163,9 @@ pub fn run(
             .service(server::routes::search::search) // search page
             .service(router::about) // about page
             .service(router::settings) // settings page
+            .service(server::routes::admin::cache_stats) // cache statistics admin route
+            .service(server::routes::admin::cache_clear) // cache purge admin route
+            .service(server::routes::admin::cache_invalidate) // cache invalidation admin route
             .default_service(web::route().to(router::not_found)) // error page
     })
     .workers(config.threads as usize)
@@ -26,6 +26,14 @@ pub struct SearchResult {
     pub engine: SmallVec<[String; 0]>,
     /// The td-tdf score of the result in regards to the title, url and description and the user's query
     pub relevance_score: f32,
+    /// The url as provided by the upstream engine when the hostname of the url has been
+    /// rewritten to a privacy respecting front-end.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_url: Option<String>,
+    /// Whether the result was classified as unsafe and should be blurred until the user reveals
+    /// it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blurred: bool,
 }
 
 impl SearchResult {
@@ -45,6 +53,8 @@ impl SearchResult {
             description: description.to_owned(),
             relevance_score: 0.0,
             engine: engine.iter().map(|name| name.to_string()).collect(),
+            original_url: None,
+            blurred: false,
         }
     }
     /// calculates and update the relevance score of the current search.
@@ -56,33 +66,13 @@ impl SearchResult {
     ///
 
     pub fn calculate_relevance(&mut self, query: &str) {
-        use stop_words::{get, LANGUAGE};
-        // when language settings can change to any of the ones supported on this crate: https://docs.rs/crate/stop-words/0.8.0
         let documents = [
             self.title.clone(),
             self.url.clone(),
             self.description.clone(),
         ];
 
-        let stop_words = get(LANGUAGE::English);
-        let punctuation = [
-            ".".to_owned(),
-            ",".to_owned(),
-            ":".to_owned(),
-            ";".to_owned(),
-            "!".to_owned(),
-            "?".to_owned(),
-            "(".to_owned(),
-            ")".to_owned(),
-            "[".to_owned(),
-            "]".to_owned(),
-            "{".to_owned(),
-            "}".to_owned(),
-            "\"".to_owned(),
-            "'".to_owned(),
-            "<".to_owned(),
-            ">".to_owned(),
-        ];
+        let (stop_words, punctuation) = tokenizer_word_lists();
 
         self.relevance_score = calculate_tf_idf(query, &documents, &stop_words, &punctuation);
     }
@@ -161,6 +151,10 @@ pub struct SearchResults {
     /// search query was disallowed when the safe search level set to 4 and it
     /// was present in the `Blocklist` file.
     pub disallowed: bool,
+    /// Stores the name of the query category which caused the search query to be disallowed,
+    /// when it was not disallowed by the `Blocklist` file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disallowed_category: Option<String>,
     /// Stores the flag option which holds the check value that the following
     /// search query was filtered when the safe search level set to 3 and it
     /// was present in the `Blocklist` file.
@@ -170,6 +164,10 @@ pub struct SearchResults {
     /// Stores the flag option which holds the check value that whether any search engines were
     /// selected or not.
     pub no_engines_selected: bool,
+    /// Stores the time (in seconds since the unix epoch) at which the search results were first
+    /// cached, which is recorded in the envelope of the cache entry rather than serialized.
+    #[serde(skip)]
+    cached_at: Option<u64>,
 }
 
 impl SearchResults {
@@ -188,9 +186,11 @@ impl SearchResults {
             results,
             engine_errors_info: engine_errors_info.to_owned(),
             disallowed: Default::default(),
+            disallowed_category: Default::default(),
             filtered: Default::default(),
             safe_search_level: Default::default(),
             no_engines_selected: Default::default(),
+            cached_at: None,
         }
     }
 
@@ -199,6 +199,17 @@ impl SearchResults {
         self.disallowed = true;
     }
 
+    /// A setter function that sets disallowed to true along with the name of the query category
+    /// which caused it.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - It takes the name of the query category as an argument.
+    pub fn set_disallowed_category(&mut self, category: &str) {
+        self.disallowed = true;
+        self.disallowed_category = Some(category.to_owned());
+    }
+
     /// A setter function that sets the filtered to true.
     pub fn set_filtered(&mut self, filtered: bool) {
         self.filtered = filtered;
@@ -227,6 +238,58 @@ impl SearchResults {
     pub fn set_no_engines_selected(&mut self) {
         self.no_engines_selected = true;
     }
+
+    /// A getter function that gets the time (in seconds since the unix epoch) at which the
+    /// search results were first cached, if they were fetched from the cache.
+    pub fn cached_at(&self) -> Option<u64> {
+        self.cached_at
+    }
+
+    /// A setter function to set the time at which the search results were first cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `cached_at` - It takes the time (in seconds since the unix epoch) as an argument.
+    pub fn set_cached_at(&mut self, cached_at: u64) {
+        self.cached_at = Some(cached_at);
+    }
+}
+
+/// Helper function which provides the stop words and the punctuation symbols which are ignored
+/// when tokenizing the search query and the search results.
+///
+/// # Returns
+///
+/// Returns a tuple of the language specific stop words and the punctuation symbols.
+fn tokenizer_word_lists() -> (Vec<String>, Vec<String>) {
+    use stop_words::{get, LANGUAGE};
+    // when language settings can change to any of the ones supported on this crate: https://docs.rs/crate/stop-words/0.8.0
+    let stop_words = get(LANGUAGE::English);
+    let punctuation = [
+        ".", ",", ":", ";", "!", "?", "(", ")", "[", "]", "{", "}", "\"", "'", "<", ">",
+    ]
+    .iter()
+    .map(|symbol| symbol.to_string())
+    .collect();
+
+    (stop_words, punctuation)
+}
+
+/// Splits the search query into word tokens with the same tokenizer, stop words and punctuation
+/// handling as is used to calculate the relevance score of the search results.
+///
+/// # Arguments
+///
+/// * `query` - It takes the user's search query as an argument.
+///
+/// # Returns
+///
+/// Returns the word tokens of the query.
+pub fn query_tokens(query: &str) -> Vec<String> {
+    use keyword_extraction::tokenizer::Tokenizer;
+
+    let (stop_words, punctuation) = tokenizer_word_lists();
+    Tokenizer::new(query, &stop_words, Some(&punctuation)).split_into_words()
 }
 
 /// Helper function to calculate the tf-idf for the search query.
@@ -219,6 +219,11 @@ impl EngineHandler {
         })
     }
 
+    /// A getter function that gets the name of the engine.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     /// This function converts the EngineHandler type into a tuple containing the engine name and
     /// the associated engine struct.
     pub fn into_name_engine(self) -> (&'static str, Box<dyn SearchEngine>) {
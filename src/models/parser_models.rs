@@ -47,9 +47,40 @@ pub struct AggregatorConfig {
 }
 
 /// Configuration options for the rate limiter middleware.
+#[derive(Clone, Copy, PartialEq)]
 pub struct RateLimiter {
     /// The number of request that are allowed within a provided time limit.
     pub number_of_requests: u8,
     /// The time limit in which the quantity of requests that should be accepted.
     pub time_limit: u8,
 }
+
+/// An enum type which provides the policies used to prefetch the neighbouring pages of search
+/// results, so that they can be served from the cache when the user moves to them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PrefetchPolicy {
+    /// Only the requested page of search results is fetched.
+    Off,
+    /// The next page of search results is fetched alongside the requested page.
+    NextPage,
+    /// The previous and next pages of search results are fetched in the background once the
+    /// requested page has been fetched, without delaying the response.
+    Background,
+}
+
+impl PrefetchPolicy {
+    /// Parses the prefetch policy from its name in the config file.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - It takes the name of the prefetch policy (`off`, `next` or `background`) as an
+    /// argument.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "off" => Some(PrefetchPolicy::Off),
+            "next" => Some(PrefetchPolicy::NextPage),
+            "background" => Some(PrefetchPolicy::Background),
+            _ => None,
+        }
+    }
+}
@@ -20,6 +20,13 @@ pub struct SearchParams {
     pub safesearch: Option<u8>,
 }
 
+/// A named struct which deserializes the url parameters of the cache invalidation admin route.
+#[derive(Deserialize)]
+pub struct InvalidateParams {
+    /// It stores the prefix of the queries whose cached search results should be removed.
+    pub prefix: Option<String>,
+}
+
 /// A named struct which is used to deserialize the cookies fetched from the client side.
 #[allow(dead_code)]
 #[derive(Deserialize)]
@@ -32,6 +39,22 @@ pub struct Cookie<'a> {
     pub engines: Cow<'a, Vec<Cow<'a, str>>>,
     /// It stores the user selected safe search level from the UI.
     pub safe_search_level: u8,
+    /// It stores the user provided domain rules (one rule per entry) used to boost, lower, pin
+    /// or remove search results from particular domains.
+    #[serde(default)]
+    pub domain_rules: Cow<'a, Vec<Cow<'a, str>>>,
+    /// It stores the original hostnames whose rewriting to a privacy respecting front-end was
+    /// disabled by the user from the UI.
+    #[serde(default)]
+    pub disabled_hostname_replacements: Cow<'a, Vec<Cow<'a, str>>>,
+    /// It stores the host patterns of the sites blocked by the user from the UI.
+    #[serde(default)]
+    pub blocked_sites: Cow<'a, Vec<Cow<'a, str>>>,
+    /// It stores the base64 encoded, deflate compressed and newline separated host patterns of
+    /// the sites blocked by the user, which are stored this way when the list grows too large to
+    /// fit in the cookie uncompressed.
+    #[serde(default)]
+    pub compressed_blocked_sites: Option<Cow<'a, str>>,
 }
 
 impl<'a> Cookie<'a> {
@@ -43,6 +66,10 @@ impl<'a> Cookie<'a> {
             colorscheme: Cow::Borrowed(&style.colorscheme),
             engines: Cow::Owned(engines),
             safe_search_level,
+            domain_rules: Cow::Owned(Vec::new()),
+            disabled_hostname_replacements: Cow::Owned(Vec::new()),
+            blocked_sites: Cow::Owned(Vec::new()),
+            compressed_blocked_sites: None,
         }
     }
 }
@@ -1,34 +1,56 @@
 //! This module provides the functionality to scrape and gathers all the results from the upstream
 //! search engines and then removes duplicate results.
 
+use super::blocked_sites::BlockedSites;
+use super::domain_rules::{apply_domain_rules, DomainRules};
+use super::filter_engine::FilterEngine;
+use super::hostname_rewriter::HostnameReplacements;
+use super::sanitizer::{html_to_text, is_safe_url};
 use super::user_agent::random_user_agent;
+use crate::cache::{cache_key::CacheKey, cacher::SharedCache};
 use crate::config::parser::Config;
-use crate::handler::{file_path, FileType};
 use crate::models::{
     aggregation_models::{EngineErrorInfo, SearchResult, SearchResults},
     engine_models::{EngineError, EngineHandler},
 };
 
 use error_stack::Report;
-use futures::stream::FuturesUnordered;
-use regex::Regex;
 use reqwest::{Client, ClientBuilder};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, BufReader},
-    task::JoinHandle,
-    time::Duration,
-};
+use tokio::{task::JoinHandle, time::Duration};
 
 /// A constant for holding the prebuilt Client globally in the app.
 static CLIENT: std::sync::OnceLock<Client> = std::sync::OnceLock::new();
 
+/// A named struct which stores the preferences provided by the user through the preferences
+/// cookie which are applied to the aggregated search results.
+#[derive(Default)]
+pub struct UserPreferences {
+    /// It stores the domain rules provided by the user which are applied alongside the domain
+    /// rules from the domain rules file.
+    pub domain_rules: DomainRules,
+    /// It stores the hostname replacements enabled for the user which are used to rewrite the
+    /// result urls to privacy respecting front-ends.
+    pub hostname_replacements: HostnameReplacements,
+    /// It stores the sites blocked by the user whose results are removed.
+    pub blocked_sites: BlockedSites,
+}
+
+/// A named struct which stores the parameters of the search sent to the upstream search engines.
+#[derive(Clone, Copy)]
+pub struct EngineQuery<'a> {
+    /// It stores the user's search query.
+    pub query: &'a str,
+    /// It stores the page number (starting from 0) of the search results.
+    pub page: u32,
+    /// It stores the safe search level sent to the upstream search engines.
+    pub safe_search: u8,
+}
+
 /// Aliases for long type annotations
 
-type FutureVec =
-    FuturesUnordered<JoinHandle<Result<Vec<(String, SearchResult)>, Report<EngineError>>>>;
+type EngineResults = Result<Vec<(String, SearchResult)>, Report<EngineError>>;
 
 /// The function aggregates the scraped results from the user-selected upstream search engines.
 /// These engines can be chosen either from the user interface (UI) or from the configuration file.
@@ -53,15 +75,25 @@ type FutureVec =
 /// If you search from the url like `https://127.0.0.1/search?q=huston` then the search bar should
 /// contain the word huston and not remain empty.
 ///
+/// The raw results of every engine are cached on their own, keyed by the engine and the search
+/// sent to it, so that the searches selecting different engines share the work done for the
+/// engines they have in common. Only the engines whose results are not cached or are stale are
+/// queried, and the cached and fetched results are merged together. The merged results are as
+/// old as the oldest cached results they are made of, so that they are refreshed in time.
+///
 /// # Arguments
 ///
-/// * `query` - Accepts a string to query with the above upstream search engines.
-/// * `page` - Accepts an u32 page number.
-/// * `random_delay` - Accepts a boolean value to add a random delay before making the request.
-/// * `debug` - Accepts a boolean value to enable or disable debug mode option.
+/// * `search` - Accepts the query, page number and safe search level sent to the upstream
+/// search engines.
+/// * `config` - Accepts the parsed config, which provides the request timeouts, the random
+/// delay option and the server side filters.
 /// * `upstream_search_engines` - Accepts a vector of search engine names which was selected by the
-/// * `request_timeout` - Accepts a time (secs) as a value which controls the server request timeout.
 /// user through the UI or the config file.
+/// * `user_preferences` - Accepts the domain rules, hostname replacements and blocked sites
+/// provided by the user through the preferences cookie.
+/// * `filter_engine` - Accepts the shared filter engine which filters the results using the
+/// blocklist, the subscription lists and the allowlist.
+/// * `cache` - Accepts the shared cache storing the raw results of the upstream search engines.
 ///
 /// # Error
 ///
@@ -69,11 +101,12 @@ type FutureVec =
 /// function in either `searx` or `duckduckgo` or both otherwise returns a `SearchResults struct`
 /// containing appropriate values.
 pub async fn aggregate(
-    query: &str,
-    page: u32,
+    search: EngineQuery<'_>,
     config: &Config,
     upstream_search_engines: &[EngineHandler],
-    safe_search: u8,
+    user_preferences: &UserPreferences,
+    filter_engine: &FilterEngine,
+    cache: &SharedCache,
 ) -> Result<SearchResults, Box<dyn std::error::Error>> {
     let client = CLIENT.get_or_init(|| {
         ClientBuilder::new()
@@ -93,42 +126,83 @@ pub async fn aggregate(
 
     let user_agent: &str = random_user_agent();
 
-    // Add a random delay before making the request.
-    if config.aggregator.random_delay || !config.debug {
-        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() as f32;
-        let delay = ((nanos / 1_0000_0000 as f32).floor() as u64) + 1;
-        tokio::time::sleep(Duration::from_secs(delay)).await;
-    }
-
-    let mut names: Vec<&str> = Vec::with_capacity(0);
-
-    // create tasks for upstream result fetching
-    let tasks: FutureVec = FutureVec::new();
+    // look up the results of every engine in the cache, so that only the engines whose results
+    // are missing are queried.
+    let mut engine_results: Vec<(&'static str, EngineResults)> =
+        Vec::with_capacity(upstream_search_engines.len());
+    let mut missing_engines: Vec<(&EngineHandler, CacheKey)> = Vec::new();
+    // The aggregated results are as old as the oldest cached results they are made of.
+    let mut oldest_cached_at: Option<u64> = None;
 
-    let query: Arc<String> = Arc::new(query.to_string());
     for engine_handler in upstream_search_engines {
-        let (name, search_engine) = engine_handler.clone().into_name_engine();
-        names.push(name);
-        let query_partially_cloned = query.clone();
-        tasks.push(tokio::spawn(async move {
-            search_engine
-                .results(
-                    &query_partially_cloned,
-                    page,
-                    user_agent,
-                    client,
-                    safe_search,
-                )
-                .await
-        }));
+        let key = CacheKey::for_engine(
+            engine_handler.name(),
+            search.query,
+            search.page,
+            search.safe_search,
+        );
+        match cache.cached_engine_results(&key).await {
+            Ok((results, cached_at)) => {
+                oldest_cached_at = match (oldest_cached_at, cached_at) {
+                    (Some(oldest), Some(cached_at)) => Some(oldest.min(cached_at)),
+                    (oldest, cached_at) => oldest.or(cached_at),
+                };
+                engine_results.push((engine_handler.name(), Ok(results)))
+            }
+            Err(_) => missing_engines.push((engine_handler, key)),
+        }
     }
 
-    // get upstream responses
-    let mut responses = Vec::with_capacity(tasks.len());
+    if !missing_engines.is_empty() {
+        // Add a random delay before making the request.
+        if config.aggregator.random_delay || !config.debug {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() as f32;
+            let delay = ((nanos / 1_0000_0000 as f32).floor() as u64) + 1;
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+        }
+
+        // create tasks for upstream result fetching
+        let query: Arc<String> = Arc::new(search.query.to_string());
+        let tasks: Vec<JoinHandle<EngineResults>> = missing_engines
+            .iter()
+            .map(|(engine_handler, _)| {
+                let (_, search_engine) = (*engine_handler).clone().into_name_engine();
+                let query_partially_cloned = query.clone();
+                tokio::spawn(async move {
+                    search_engine
+                        .results(
+                            &query_partially_cloned,
+                            search.page,
+                            user_agent,
+                            client,
+                            search.safe_search,
+                        )
+                        .await
+                })
+            })
+            .collect();
+
+        // get upstream responses, caching the results of the engines which succeeded
+        let (mut fetched_results, mut fetched_keys) = (Vec::new(), Vec::new());
+
+        for ((engine_handler, key), task) in missing_engines.into_iter().zip(tasks) {
+            if let Ok(response) = task.await {
+                if let Ok(results) = &response {
+                    fetched_results.push(results.clone());
+                    fetched_keys.push(key);
+                }
+                engine_results.push((engine_handler.name(), response));
+            }
+        }
 
-    for task in tasks {
-        if let Ok(result) = task.await {
-            responses.push(result)
+        if let Err(error) = cache
+            .cache_engine_results(&fetched_results, &fetched_keys)
+            .await
+        {
+            log::error!(
+                "Failed to cache the results of the upstream engines: {:?}",
+                error
+            );
         }
     }
 
@@ -136,117 +210,93 @@ pub async fn aggregate(
     let mut result_map: Vec<(String, SearchResult)> = Vec::new();
     let mut engine_errors_info: Vec<EngineErrorInfo> = Vec::new();
 
-    let mut handle_error = |error: &Report<EngineError>, engine_name: &'static str| {
-        log::error!("Engine Error: {:?}", error);
-        engine_errors_info.push(EngineErrorInfo::new(
-            error.downcast_ref::<EngineError>().unwrap(),
-            engine_name,
-        ));
-    };
-
-    for _ in 0..responses.len() {
-        let response = responses.pop().unwrap();
-        let engine = names.pop().unwrap();
-
-        if result_map.is_empty() {
-            match response {
-                Ok(results) => result_map = results,
-                Err(error) => handle_error(&error, engine),
-            };
-            continue;
-        }
-
+    for (engine, response) in engine_results {
         match response {
-            Ok(result) => {
-                result.into_iter().for_each(|(key, value)| {
-                    match result_map.iter().find(|(key_s, _)| key_s == &key) {
-                        Some(value) => value.1.to_owned().add_engines(engine),
-                        None => result_map.push((key, value)),
-                    };
-                });
+            Ok(results) => merge_engine_results(&mut result_map, engine, results),
+            Err(error) => {
+                log::error!("Engine Error: {:?}", error);
+                engine_errors_info.push(EngineErrorInfo::new(
+                    error.downcast_ref::<EngineError>().unwrap(),
+                    engine,
+                ));
             }
-            Err(error) => handle_error(&error, engine),
         };
     }
 
-    if safe_search >= 3 {
-        let mut blacklist_map: Vec<(String, SearchResult)> = Vec::new();
-        filter_with_lists(
-            &mut result_map,
-            &mut blacklist_map,
-            file_path(FileType::BlockList)?,
-        )
-        .await?;
-
-        filter_with_lists(
-            &mut blacklist_map,
-            &mut result_map,
-            file_path(FileType::AllowList)?,
-        )
-        .await?;
+    // normalize the html provided by the upstream engines to plain text (the query terms are
+    // highlighted by the search template) and drop the results with unsafe urls before anything
+    // else gets to see them.
+    result_map.retain(|(_, result)| is_safe_url(&result.url));
+    result_map.iter_mut().for_each(|(_, result)| {
+        result.title = html_to_text(&result.title);
+        result.description = html_to_text(&result.description);
+    });
 
-        drop(blacklist_map);
-    }
+    filter_engine.filter_results(&mut result_map);
+    user_preferences
+        .blocked_sites
+        .filter_results(&mut result_map);
+    config
+        .safe_search_classifier
+        .classify(&mut result_map, search.safe_search);
 
-    let mut results: Vec<SearchResult> = result_map
+    let results: Vec<SearchResult> = result_map
         .iter()
         .map(|(_, value)| {
             let mut copy = value.clone();
-            if !copy.url.contains("temu.com") {
-                copy.calculate_relevance(query.as_str())
-            }
+            copy.calculate_relevance(search.query);
             copy
         })
         .collect();
+
+    let (mut pinned_results, mut results) = apply_domain_rules(
+        results,
+        &config.domain_rules,
+        &user_preferences.domain_rules,
+    );
+    sort_search_results(&mut pinned_results);
     sort_search_results(&mut results);
+    pinned_results.append(&mut results);
+    let mut results = pinned_results;
 
-    Ok(SearchResults::new(results, &engine_errors_info))
+    results
+        .iter_mut()
+        .for_each(|result| user_preferences.hostname_replacements.rewrite(result));
+
+    let mut search_results = SearchResults::new(results, &engine_errors_info);
+    if let Some(cached_at) = oldest_cached_at {
+        search_results.set_cached_at(cached_at);
+    }
+    Ok(search_results)
 }
 
-/// Filters a map of search results using a list of regex patterns.
+/// Merges the results of an upstream search engine into the aggregated results. The results
+/// already returned by another engine are not added again, the engine being recorded on the
+/// existing result instead.
 ///
 /// # Arguments
 ///
-/// * `map_to_be_filtered` - A mutable reference to a `Vec` of search results to filter, where the filtered results will be removed from.
-/// * `resultant_map` - A mutable reference to a `Vec` to hold the filtered results.
-/// * `file_path` - A `&str` representing the path to a file containing regex patterns to use for filtering.
-///
-/// # Errors
-///
-/// Returns an error if the file at `file_path` cannot be opened or read, or if a regex pattern is invalid.
-pub async fn filter_with_lists(
-    map_to_be_filtered: &mut Vec<(String, SearchResult)>,
-    resultant_map: &mut Vec<(String, SearchResult)>,
-    file_path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let reader = BufReader::new(File::open(file_path).await?);
-    let mut lines = reader.lines();
-
-    while let Some(line) = lines.next_line().await? {
-        let re = Regex::new(line.trim())?;
-
-        let mut length = map_to_be_filtered.len();
-        let mut idx: usize = Default::default();
-        // Iterate over each search result in the map and check if it matches the regex pattern
-        while idx < length {
-            let ele = &map_to_be_filtered[idx];
-            let ele_inner = &ele.1;
-            match re.is_match(&ele.0.to_lowercase())
-                || re.is_match(&ele_inner.title.to_lowercase())
-                || re.is_match(&ele_inner.description.to_lowercase())
-            {
-                true => {
-                    // If the search result matches the regex pattern, move it from the original map to the resultant map
-                    resultant_map.push(map_to_be_filtered.swap_remove(idx));
-                    length -= 1;
-                }
-                false => idx += 1,
-            };
-        }
+/// * `result_map` - It takes a mutable reference to the aggregated results as an argument.
+/// * `engine` - It takes the name of the engine which returned the results as an argument.
+/// * `results` - It takes the results returned by the engine as an argument.
+fn merge_engine_results(
+    result_map: &mut Vec<(String, SearchResult)>,
+    engine: &str,
+    results: Vec<(String, SearchResult)>,
+) {
+    if result_map.is_empty() {
+        *result_map = results;
+        return;
     }
 
-    Ok(())
+    for (key, value) in results {
+        match result_map.iter_mut().find(|(key_s, _)| key_s == &key) {
+            Some((_, result)) => result.add_engines(engine),
+            None => result_map.push((key, value)),
+        };
+    }
 }
+
 /// Sorts  SearchResults by relevance score.
 /// <br> sort_unstable is used as its faster,stability is not an issue on our side.
 /// For reasons why, check out [`this`](https://rust-lang.github.io/rfcs/1884-unstable-sort.html)
@@ -262,160 +312,32 @@ fn sort_search_results(results: &mut [SearchResult]) {
             .unwrap_or(Ordering::Less)
     })
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use smallvec::smallvec;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
-
-    #[tokio::test]
-    async fn test_filter_with_lists() -> Result<(), Box<dyn std::error::Error>> {
-        // Create a map of search results to filter
-        let mut map_to_be_filtered = Vec::new();
-        map_to_be_filtered.push((
-            "https://www.example.com".to_owned(),
-            SearchResult {
-                title: "Example Domain".to_owned(),
-                url: "https://www.example.com".to_owned(),
-                description: "This domain is for use in illustrative examples in documents."
-                    .to_owned(),
-                relevance_score: 0.0,
-                engine: smallvec!["Google".to_owned(), "Bing".to_owned()],
-            },
-        ));
-        map_to_be_filtered.push((
-            "https://www.rust-lang.org/".to_owned(),
-            SearchResult {
-                title: "Rust Programming Language".to_owned(),
-                url: "https://www.rust-lang.org/".to_owned(),
-                description: "A systems programming language that runs blazingly fast, prevents segfaults, and guarantees thread safety.".to_owned(),
-                engine: smallvec!["Google".to_owned(), "DuckDuckGo".to_owned()],
-                relevance_score:0.0
-            },)
-        );
-
-        // Create a temporary file with regex patterns
-        let mut file = NamedTempFile::new()?;
-        writeln!(file, "example")?;
-        writeln!(file, "rust")?;
-        file.flush()?;
-
-        let mut resultant_map = Vec::new();
-        filter_with_lists(
-            &mut map_to_be_filtered,
-            &mut resultant_map,
-            file.path().to_str().unwrap(),
-        )
-        .await?;
-
-        assert_eq!(resultant_map.len(), 2);
-        assert!(resultant_map
-            .iter()
-            .any(|(key, _)| key == "https://www.example.com"));
-        assert!(resultant_map
-            .iter()
-            .any(|(key, _)| key == "https://www.rust-lang.org/"));
-        assert_eq!(map_to_be_filtered.len(), 0);
-
-        Ok(())
-    }
-
-    #[tokio::test]
-    async fn test_filter_with_lists_wildcard() -> Result<(), Box<dyn std::error::Error>> {
-        let mut map_to_be_filtered = Vec::new();
-        map_to_be_filtered.push((
-            "https://www.example.com".to_owned(),
-            SearchResult {
-                title: "Example Domain".to_owned(),
-                url: "https://www.example.com".to_owned(),
-                description: "This domain is for use in illustrative examples in documents."
-                    .to_owned(),
-                engine: smallvec!["Google".to_owned(), "Bing".to_owned()],
-                relevance_score: 0.0,
-            },
-        ));
-        map_to_be_filtered.push((
-            "https://www.rust-lang.org/".to_owned(),
-            SearchResult {
-                title: "Rust Programming Language".to_owned(),
-                url: "https://www.rust-lang.org/".to_owned(),
-                description: "A systems programming language that runs blazingly fast, prevents segfaults, and guarantees thread safety.".to_owned(),
-                engine: smallvec!["Google".to_owned(), "DuckDuckGo".to_owned()],
-                relevance_score:0.0
-            },
-        ));
-
-        // Create a temporary file with a regex pattern containing a wildcard
-        let mut file = NamedTempFile::new()?;
-        writeln!(file, "ex.*le")?;
-        file.flush()?;
 
-        let mut resultant_map = Vec::new();
-
-        filter_with_lists(
-            &mut map_to_be_filtered,
-            &mut resultant_map,
-            file.path().to_str().unwrap(),
-        )
-        .await?;
-
-        assert_eq!(resultant_map.len(), 1);
-        assert!(resultant_map
-            .iter()
-            .any(|(key, _)| key == "https://www.example.com"));
-        assert_eq!(map_to_be_filtered.len(), 1);
-        assert!(map_to_be_filtered
-            .iter()
-            .any(|(key, _)| key == "https://www.rust-lang.org/"));
-
-        Ok(())
-    }
-
-    #[tokio::test]
-    async fn test_filter_with_lists_file_not_found() {
-        let mut map_to_be_filtered = Vec::new();
-
-        let mut resultant_map = Vec::new();
-
-        // Call the `filter_with_lists` function with a non-existent file path
-        let result = filter_with_lists(
-            &mut map_to_be_filtered,
-            &mut resultant_map,
-            "non-existent-file.txt",
+    #[test]
+    fn test_duplicate_results_record_every_engine() {
+        let result =
+            |url: &str, engine: &str| (url.to_owned(), SearchResult::new("", url, "", &[engine]));
+        let mut result_map = Vec::new();
+        merge_engine_results(
+            &mut result_map,
+            "bing",
+            vec![result("https://www.rust-lang.org/", "bing")],
         );
-
-        assert!(result.await.is_err());
-    }
-
-    #[tokio::test]
-    async fn test_filter_with_lists_invalid_regex() {
-        let mut map_to_be_filtered = Vec::new();
-        map_to_be_filtered.push((
-            "https://www.example.com".to_owned(),
-            SearchResult {
-                title: "Example Domain".to_owned(),
-                url: "https://www.example.com".to_owned(),
-                description: "This domain is for use in illustrative examples in documents."
-                    .to_owned(),
-                engine: smallvec!["Google".to_owned(), "Bing".to_owned()],
-                relevance_score: 0.0,
-            },
-        ));
-
-        let mut resultant_map = Vec::new();
-
-        // Create a temporary file with an invalid regex pattern
-        let mut file = NamedTempFile::new().unwrap();
-        writeln!(file, "example(").unwrap();
-        file.flush().unwrap();
-
-        let result = filter_with_lists(
-            &mut map_to_be_filtered,
-            &mut resultant_map,
-            file.path().to_str().unwrap(),
+        merge_engine_results(
+            &mut result_map,
+            "brave",
+            vec![
+                result("https://www.rust-lang.org/", "brave"),
+                result("https://docs.rs/", "brave"),
+            ],
         );
 
-        assert!(result.await.is_err());
+        assert_eq!(result_map.len(), 2);
+        assert_eq!(result_map[0].1.engine.as_slice(), ["bing", "brave"]);
+        assert_eq!(result_map[1].1.engine.as_slice(), ["brave"]);
     }
 }
@@ -0,0 +1,203 @@
+//! This module provides the functionality to remove the search results pointing to the sites
+//! which the user blocked, either from the settings page or with the `block this site` button of
+//! a search result. The blocked sites are stored in the preferences cookie, compressed when the
+//! list grows large.
+
+use std::{borrow::Cow, io::Read};
+
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use flate2::read::DeflateDecoder;
+
+use super::domain_rules::{host_matches, host_of};
+use crate::models::aggregation_models::SearchResult;
+
+/// The maximum size (in bytes) of the decompressed list of blocked sites, which protects the
+/// server from decompression bombs sent through the cookie.
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024;
+
+/// A named struct which stores the host patterns of the sites blocked by the user.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockedSites {
+    /// It stores the sorted and deduplicated lowercased host patterns.
+    patterns: Vec<String>,
+}
+
+impl BlockedSites {
+    /// Constructs a new `BlockedSites` from the plain and the compressed lists stored in the
+    /// preferences cookie. The entries are normalized so that a pasted url blocks its host.
+    ///
+    /// # Arguments
+    ///
+    /// * `sites` - It takes the plain list of blocked sites from the cookie as an argument.
+    /// * `compressed_sites` - It takes the unpadded base64 encoded, deflate compressed and
+    /// newline separated list of blocked sites from the cookie as an argument.
+    pub fn new(sites: &[Cow<'_, str>], compressed_sites: Option<&str>) -> Self {
+        let decompressed = match compressed_sites.map(decompress) {
+            Some(Ok(decompressed)) => decompressed,
+            Some(Err(error)) => {
+                log::debug!("Failed to decompress the blocked sites: {}", error);
+                String::new()
+            }
+            None => String::new(),
+        };
+
+        let mut patterns: Vec<String> = sites
+            .iter()
+            .map(|site| site.as_ref())
+            .chain(decompressed.lines())
+            .filter_map(normalize)
+            .collect();
+        patterns.sort_unstable();
+        patterns.dedup();
+
+        BlockedSites { patterns }
+    }
+
+    /// Checks whether the user blocked no sites.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// A getter function that gets the normalized host patterns of the blocked sites.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Checks whether the given host is blocked by any of the patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - It takes the lowercased host name of the result url as an argument.
+    pub fn is_blocked(&self, host: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| host_matches(pattern, host))
+    }
+
+    /// Removes the search results pointing to any of the blocked sites.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - It takes a mutable reference to the search results to be filtered as an
+    /// argument.
+    pub fn filter_results(&self, results: &mut Vec<(String, SearchResult)>) {
+        if self.is_empty() {
+            return;
+        }
+
+        results.retain(|(_, result)| match host_of(&result.url) {
+            Some(host) => !self.is_blocked(&host),
+            None => true,
+        });
+    }
+}
+
+/// A helper function which normalizes an entry of the blocked sites list to a host pattern by
+/// stripping the scheme and the path of pasted urls.
+///
+/// # Arguments
+///
+/// * `site` - It takes the entry as written by the user as an argument.
+///
+/// # Returns
+///
+/// Returns `None` if the entry is blank or a comment.
+fn normalize(site: &str) -> Option<String> {
+    let site = site.trim();
+    if site.is_empty() || site.starts_with('#') {
+        return None;
+    }
+
+    let without_scheme = site.split_once("://").map_or(site, |(_, rest)| rest);
+    let host = without_scheme
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    (!host.is_empty()).then_some(host)
+}
+
+/// A helper function which decodes and decompresses the compressed list of blocked sites.
+///
+/// # Arguments
+///
+/// * `compressed_sites` - It takes the unpadded base64 encoded and deflate compressed list as an
+/// argument.
+///
+/// # Error
+///
+/// Returns an error if the list is not valid base64, not a valid deflate stream, not valid utf-8
+/// or larger than the allowed size once decompressed.
+fn decompress(compressed_sites: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let compressed = STANDARD_NO_PAD.decode(compressed_sites)?;
+    let mut decompressed = String::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_string(&mut decompressed)?;
+
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err("the decompressed list of blocked sites is too large".into());
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+
+    fn result(url: &str) -> (String, SearchResult) {
+        (
+            url.to_owned(),
+            SearchResult::new("title", url, "description", &["bing"]),
+        )
+    }
+
+    #[test]
+    fn test_normalized_patterns() {
+        let sites = [
+            Cow::Borrowed("https://www.Pinterest.com/pin/1"),
+            Cow::Borrowed("*.quora.com"),
+            Cow::Borrowed("  "),
+            Cow::Borrowed("*.quora.com"),
+        ];
+        assert_eq!(
+            BlockedSites::new(&sites, None).patterns(),
+            ["*.quora.com", "www.pinterest.com"]
+        );
+    }
+
+    #[test]
+    fn test_compressed_sites() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"example.com\n*.pinterest.com").unwrap();
+        let compressed = STANDARD_NO_PAD.encode(encoder.finish().unwrap());
+
+        let blocked = BlockedSites::new(&[Cow::Borrowed("quora.com")], Some(&compressed));
+        assert_eq!(
+            blocked.patterns(),
+            ["*.pinterest.com", "example.com", "quora.com"]
+        );
+
+        // An invalid compressed list is ignored instead of failing the search.
+        let blocked = BlockedSites::new(&[Cow::Borrowed("quora.com")], Some("not base64!"));
+        assert_eq!(blocked.patterns(), ["quora.com"]);
+    }
+
+    #[test]
+    fn test_filter_results() {
+        let blocked = BlockedSites::new(&[Cow::Borrowed("*.pinterest.com")], None);
+        let mut results = vec![
+            result("https://www.pinterest.com/pin/1"),
+            result("https://pinterest.com/"),
+            result("https://www.rust-lang.org/"),
+        ];
+        blocked.filter_results(&mut results);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "https://www.rust-lang.org/");
+    }
+}
@@ -0,0 +1,340 @@
+//! This module provides the functionality to boost, lower, pin or remove search results based on
+//! the domain they point to, using rules provided by the server administrator through the domain
+//! rules file and by the user through the preferences cookie.
+
+use std::fs;
+
+use reqwest::Url;
+
+use crate::models::aggregation_models::SearchResult;
+
+/// The default factor by which the relevance score of a result is multiplied when it is boosted.
+const DEFAULT_BOOST_FACTOR: f32 = 2.0;
+/// The default factor by which the relevance score of a result is multiplied when it is lowered.
+const DEFAULT_LOWER_FACTOR: f32 = 0.5;
+
+/// An enum type which provides the different actions that can be applied to a search result
+/// whose domain matches a rule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DomainAction {
+    /// Multiplies the relevance score of the result by the given factor (greater than 1).
+    Boost(f32),
+    /// Multiplies the relevance score of the result by the given factor (between 0 and 1).
+    Lower(f32),
+    /// Places the result above all the other (non pinned) results.
+    Pin,
+    /// Removes the result from the search results.
+    Remove,
+}
+
+/// A named struct which stores a single domain rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DomainRule {
+    /// It stores the lowercased host pattern which may contain `*` wildcards.
+    pattern: String,
+    /// It stores the action to be applied to the results matching the pattern.
+    action: DomainAction,
+}
+
+impl DomainRule {
+    /// Parses a single rule line of the form `<action> <host pattern> [factor]`, for example
+    /// `boost docs.rs`, `lower *.pinterest.com 0.2`, `pin wikipedia.org` or `remove *.temu.com`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - It takes the rule line to be parsed as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the parsed rule or `None` if the line is blank, a comment or malformed, which
+    /// includes a `boost` factor not greater than 1 and a `lower` factor not lower than 1.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut parts = line.split_whitespace();
+        let action = parts.next()?.to_lowercase();
+        let pattern = parts.next()?.trim_end_matches('.').to_lowercase();
+        let factor = match parts.next() {
+            Some(value) => match value.parse::<f32>() {
+                Ok(factor) if factor.is_finite() && factor >= 0.0 => Some(factor),
+                _ => return None,
+            },
+            None => None,
+        };
+
+        if parts.next().is_some() || pattern.is_empty() {
+            return None;
+        }
+
+        // A boost factor below 1 or a lower factor above 1 would turn the rule into its opposite.
+        let action = match (action.as_str(), factor) {
+            ("boost", None) => DomainAction::Boost(DEFAULT_BOOST_FACTOR),
+            ("boost", Some(factor)) if factor > 1.0 => DomainAction::Boost(factor),
+            ("lower", None) => DomainAction::Lower(DEFAULT_LOWER_FACTOR),
+            ("lower", Some(factor)) if factor < 1.0 => DomainAction::Lower(factor),
+            ("pin", None) => DomainAction::Pin,
+            ("remove", None) => DomainAction::Remove,
+            _ => return None,
+        };
+
+        Some(DomainRule { pattern, action })
+    }
+
+    /// Checks whether the given host matches the pattern of the rule. A `*` matches any sequence
+    /// of characters and a leading `*.` additionally matches the bare domain itself, so that
+    /// `*.example.com` matches both `example.com` and `www.example.com`.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - It takes the lowercased host name of the result url as an argument.
+    pub fn matches(&self, host: &str) -> bool {
+        host_matches(&self.pattern, host)
+    }
+
+    /// A getter function that gets the action of the rule.
+    pub fn action(&self) -> DomainAction {
+        self.action
+    }
+}
+
+/// A named struct which stores a list of domain rules in the order in which they were provided.
+#[derive(Clone, Debug, Default)]
+pub struct DomainRules {
+    /// It stores the parsed rules.
+    rules: Vec<DomainRule>,
+}
+
+impl DomainRules {
+    /// Parses a list of rule lines, skipping blank lines and comments. Malformed lines are
+    /// ignored and reported in the logs.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - It takes an iterator over the rule lines as an argument.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let rules = lines
+            .into_iter()
+            .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .filter_map(|line| {
+                let rule = DomainRule::parse(line);
+                if rule.is_none() {
+                    log::error!("Domain Rules Error: Ignoring malformed rule `{}`", line);
+                }
+                rule
+            })
+            .collect();
+        DomainRules { rules }
+    }
+
+    /// Reads and parses the domain rules file present at the given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - It takes the path of the domain rules file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an io error if the file cannot be read.
+    pub fn from_file(file_path: &str) -> Result<Self, std::io::Error> {
+        Ok(Self::parse(fs::read_to_string(file_path)?.lines()))
+    }
+
+    /// Returns the first rule which matches the given host, if any.
+    fn find(&self, host: &str) -> Option<&DomainRule> {
+        self.rules.iter().find(|rule| rule.matches(host))
+    }
+
+    /// Checks whether the list contains no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the hash of the rules, which only depends on the rules and their order, so that
+    /// it stays the same across restarts and between the servers sharing a cache.
+    pub fn content_hash(&self) -> blake3::Hash {
+        blake3::hash(format!("{:?}", self.rules).as_bytes())
+    }
+}
+
+/// Applies the server and user provided domain rules to the relevance scored search results and
+/// sorts them, placing the pinned results on top. A `remove` rule from the server always takes
+/// effect, otherwise the first matching user rule takes precedence over the server rules.
+///
+/// # Arguments
+///
+/// * `results` - It takes the relevance scored search results as an argument.
+/// * `server_rules` - It takes the rules provided through the domain rules file as an argument.
+/// * `user_rules` - It takes the rules provided through the preferences cookie as an argument.
+///
+/// # Returns
+///
+/// Returns a tuple of the pinned and the remaining (not removed) results.
+pub fn apply_domain_rules(
+    results: Vec<SearchResult>,
+    server_rules: &DomainRules,
+    user_rules: &DomainRules,
+) -> (Vec<SearchResult>, Vec<SearchResult>) {
+    let (mut pinned, mut rest) = (Vec::new(), Vec::with_capacity(results.len()));
+
+    if server_rules.is_empty() && user_rules.is_empty() {
+        return (pinned, results);
+    }
+
+    for mut result in results {
+        let host = match host_of(&result.url) {
+            Some(host) => host,
+            None => {
+                rest.push(result);
+                continue;
+            }
+        };
+
+        let server_rule = server_rules.find(&host);
+        let rule = match server_rule {
+            Some(rule) if rule.action == DomainAction::Remove => Some(rule),
+            _ => user_rules.find(&host).or(server_rule),
+        };
+
+        match rule.map(DomainRule::action) {
+            Some(DomainAction::Boost(factor)) | Some(DomainAction::Lower(factor)) => {
+                result.relevance_score *= factor;
+                rest.push(result);
+            }
+            Some(DomainAction::Pin) => pinned.push(result),
+            Some(DomainAction::Remove) => (),
+            None => rest.push(result),
+        }
+    }
+
+    (pinned, rest)
+}
+
+/// Extracts the lowercased host name from the given url.
+///
+/// # Arguments
+///
+/// * `url` - It takes the url of a search result as an argument.
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|host| host.trim_end_matches('.').to_lowercase())
+}
+
+/// Checks whether the given host matches the host pattern. A `*` matches any sequence of
+/// characters and a leading `*.` additionally matches the bare domain itself.
+///
+/// # Arguments
+///
+/// * `pattern` - It takes the lowercased host pattern as an argument.
+/// * `host` - It takes the lowercased host name of the result url as an argument.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        if host == domain {
+            return true;
+        }
+    }
+    wildcard_match(pattern.as_bytes(), host.as_bytes())
+}
+
+/// Matches the text against a pattern where `*` matches any sequence of characters.
+///
+/// # Arguments
+///
+/// * `pattern` - It takes the pattern as bytes as an argument.
+/// * `text` - It takes the text to be matched as bytes as an argument.
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(url: &str, relevance_score: f32) -> SearchResult {
+        let mut result = SearchResult::new("title", url, "description", &["bing"]);
+        result.relevance_score = relevance_score;
+        result
+    }
+
+    #[test]
+    fn test_rule_parsing() {
+        assert_eq!(
+            DomainRule::parse("boost docs.rs").map(|rule| rule.action()),
+            Some(DomainAction::Boost(DEFAULT_BOOST_FACTOR))
+        );
+        assert_eq!(
+            DomainRule::parse("lower *.pinterest.com 0.2").map(|rule| rule.action()),
+            Some(DomainAction::Lower(0.2))
+        );
+        assert!(DomainRule::parse("# comment").is_none());
+        assert!(DomainRule::parse("pin example.com 2").is_none());
+        assert!(DomainRule::parse("explode example.com").is_none());
+        assert!(DomainRule::parse("boost example.com -1").is_none());
+        assert!(DomainRule::parse("boost example.com 0.5").is_none());
+        assert!(DomainRule::parse("lower example.com 2").is_none());
+        assert_eq!(
+            DomainRule::parse("lower example.com 0").map(|rule| rule.action()),
+            Some(DomainAction::Lower(0.0))
+        );
+    }
+
+    #[test]
+    fn test_wildcard_host_matching() {
+        let rule = DomainRule::parse("remove *.example.com").unwrap();
+        assert!(rule.matches("example.com"));
+        assert!(rule.matches("www.example.com"));
+        assert!(rule.matches("a.b.example.com"));
+        assert!(!rule.matches("notexample.com"));
+
+        let rule = DomainRule::parse("remove docs.*").unwrap();
+        assert!(rule.matches("docs.rs"));
+        assert!(!rule.matches("www.docs.rs"));
+    }
+
+    #[test]
+    fn test_apply_domain_rules() {
+        let server_rules = DomainRules::parse(["remove *.temu.com", "lower docs.rs"]);
+        let user_rules = DomainRules::parse(["boost docs.rs 3", "pin *.wikipedia.org"]);
+
+        let (pinned, rest) = apply_domain_rules(
+            vec![
+                result("https://www.temu.com/item", 1.0),
+                result("https://docs.rs/regex", 1.0),
+                result("https://en.wikipedia.org/wiki/Rust", 0.5),
+                result("https://example.com/", 1.0),
+            ],
+            &server_rules,
+            &user_rules,
+        );
+
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].url, "https://en.wikipedia.org/wiki/Rust");
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].url, "https://docs.rs/regex");
+        assert_eq!(rest[0].relevance_score, 3.0);
+        assert_eq!(rest[1].relevance_score, 1.0);
+    }
+}
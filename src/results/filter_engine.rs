@@ -0,0 +1,880 @@
+//! This module provides the functionality to filter the search results and the search queries
+//! using the rules present in the blocklist and allowlist files and in the subscribed remote
+//! lists. The rules are either raw regexes or written in the uBlacklist syntax. The lists are
+//! compiled once into `RegexSet`s and are recompiled in the background whenever the files change
+//! on disk, so that the searches never wait for a list to be read or compiled.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use regex::{Regex, RegexSet};
+
+use super::subscriptions::subscription_paths;
+use crate::{
+    config::{parser::Config, reload::SharedConfig},
+    handler::{file_path, FileType},
+    models::aggregation_models::SearchResult,
+};
+
+/// The amount of time between two checks of whether the list files have changed on disk.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A named struct which stores the line of a filter list which matched.
+#[derive(Debug, PartialEq)]
+pub struct FilterMatch<'a> {
+    /// It stores the line number (starting from 1) of the matching rule in the list file.
+    pub line_number: usize,
+    /// It stores the matching rule as written in the list file.
+    pub pattern: &'a str,
+}
+
+/// An enum type which provides the different syntaxes in which a filter list can be written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListSyntax {
+    /// The syntax of the local `blocklist.txt` and `allowlist.txt` files, where every line is a
+    /// uBlacklist match pattern, a `/regex/` matched against the result urls or a raw regex
+    /// matched against the urls, titles and descriptions of the results and the search queries.
+    /// Any invalid line makes the whole list invalid.
+    Local,
+    /// The syntax of the remote subscription lists in the uBlacklist format, where only the match
+    /// patterns and the `/regex/` lines are understood and every other line is skipped.
+    Subscription,
+}
+
+/// An enum type which provides the different kinds of rules a line of a filter list can contain.
+enum Rule {
+    /// A rule which is only matched against the urls of the search results.
+    Url(String),
+    /// A rule which is matched against the urls, titles and descriptions of the search results
+    /// and against the search queries.
+    Text(String),
+}
+
+/// A named struct which stores the rules of a filter list compiled together.
+#[derive(Debug)]
+struct CompiledRules {
+    /// It stores all the rules compiled together.
+    set: RegexSet,
+    /// It stores the indices of the rules in the `lines` of the list.
+    indices: Vec<usize>,
+}
+
+impl Default for CompiledRules {
+    fn default() -> Self {
+        CompiledRules {
+            set: RegexSet::empty(),
+            indices: Vec::new(),
+        }
+    }
+}
+
+impl CompiledRules {
+    /// Compiles the given rules into a single `RegexSet`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - It takes the indices of the rules in the list along with their regexes as an
+    /// argument.
+    fn new(rules: Vec<(usize, String)>) -> Result<Self, regex::Error> {
+        let (indices, regexes): (Vec<usize>, Vec<String>) = rules.into_iter().unzip();
+        Ok(CompiledRules {
+            set: RegexSet::new(regexes)?,
+            indices,
+        })
+    }
+
+    /// Returns the index of the first rule (in the order of the list) which matches the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - It takes the text to be checked as an argument.
+    fn first_match(&self, text: &str) -> Option<usize> {
+        self.set
+            .matches(text)
+            .iter()
+            .next()
+            .map(|idx| self.indices[idx])
+    }
+}
+
+/// A named struct which stores a filter list compiled into `RegexSet`s.
+#[derive(Debug)]
+pub struct FilterList {
+    /// It stores the rules which are only matched against the result urls.
+    url_rules: CompiledRules,
+    /// It stores the rules which are matched against any text.
+    text_rules: CompiledRules,
+    /// It stores the line numbers of the rules along with the rules as written in the list file.
+    lines: Vec<(usize, String)>,
+    /// It stores the hash of the compiled rules, which identifies the contents of the list.
+    digest: blake3::Hash,
+}
+
+impl Default for FilterList {
+    fn default() -> Self {
+        FilterList {
+            url_rules: CompiledRules::default(),
+            text_rules: CompiledRules::default(),
+            lines: Vec::new(),
+            digest: blake3::Hasher::new().finalize(),
+        }
+    }
+}
+
+impl FilterList {
+    /// Compiles the rules of a local filter list. Blank lines and lines starting with `#` are
+    /// ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - It takes the contents of the list file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a regex error if any of the rules is not a valid regex.
+    pub fn parse(contents: &str) -> Result<Self, regex::Error> {
+        Self::parse_with_syntax(contents, ListSyntax::Local)
+    }
+
+    /// Compiles the rules of a filter list written in the given syntax. Blank lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - It takes the contents of the list file as an argument.
+    /// * `syntax` - It takes the syntax in which the list is written as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns a regex error if any of the rules of a local list is not a valid regex.
+    pub fn parse_with_syntax(contents: &str, syntax: ListSyntax) -> Result<Self, regex::Error> {
+        let mut lines = Vec::new();
+        let mut url_rules = Vec::new();
+        let mut text_rules = Vec::new();
+
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let rule = match (parse_rule(line), syntax) {
+                (Some(rule), _) => rule,
+                (None, ListSyntax::Local) => Rule::Text(line.to_owned()),
+                (None, ListSyntax::Subscription) => continue,
+            };
+            let rule = match rule {
+                // Skip the invalid regexes of the subscription lists instead of rejecting the
+                // whole list, as they are not under the control of the server administrator.
+                Rule::Url(regex) | Rule::Text(regex)
+                    if syntax == ListSyntax::Subscription && Regex::new(&regex).is_err() =>
+                {
+                    log::debug!("Skipped the invalid line {} of a subscription", idx + 1);
+                    continue;
+                }
+                rule => rule,
+            };
+
+            match rule {
+                Rule::Url(regex) => url_rules.push((lines.len(), regex)),
+                Rule::Text(regex) => text_rules.push((lines.len(), regex)),
+            }
+            lines.push((idx + 1, line.to_owned()));
+        }
+
+        // The rules are hashed along with their kind, so that the comments, the blank lines and
+        // the skipped lines do not change the digest.
+        let mut hasher = blake3::Hasher::new();
+        for (kind, rules) in [(0u8, &url_rules), (1u8, &text_rules)] {
+            for (_, regex) in rules {
+                hasher.update(&[kind]);
+                hasher.update(&(regex.len() as u64).to_le_bytes());
+                hasher.update(regex.as_bytes());
+            }
+        }
+
+        Ok(FilterList {
+            url_rules: CompiledRules::new(url_rules)?,
+            text_rules: CompiledRules::new(text_rules)?,
+            lines,
+            digest: hasher.finalize(),
+        })
+    }
+
+    /// Reads and compiles the local filter list present at the given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - It takes the path of the list file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the file cannot be read or if any of the rules is invalid.
+    pub fn from_file(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_file_with_syntax(file_path, ListSyntax::Local)
+    }
+
+    /// Reads and compiles the filter list written in the given syntax present at the given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - It takes the path of the list file as an argument.
+    /// * `syntax` - It takes the syntax in which the list is written as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the file cannot be read or if any of the rules is invalid.
+    pub fn from_file_with_syntax(
+        file_path: &str,
+        syntax: ListSyntax,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse_with_syntax(
+            &fs::read_to_string(file_path)?,
+            syntax,
+        )?)
+    }
+
+    /// A getter function that gets the hash of the compiled rules, which only changes when the
+    /// rules of the list change.
+    pub fn digest(&self) -> blake3::Hash {
+        self.digest
+    }
+
+    /// Checks whether any of the text rules of the list matches the given text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - It takes the text to be checked as an argument.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.text_rules.set.is_match(text)
+    }
+
+    /// Returns the first line of the list whose text rule matches the given text, if any. The
+    /// rules which only apply to the urls of the results are not taken into account.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - It takes the text to be checked as an argument.
+    pub fn first_match(&self, text: &str) -> Option<FilterMatch<'_>> {
+        self.text_rules
+            .first_match(text)
+            .map(|idx| self.filter_match(idx))
+    }
+
+    /// Returns the first line of the list which matches the url, the title or the description
+    /// of the given search result, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - It takes the url (key) of the search result as an argument.
+    /// * `result` - It takes the search result as an argument.
+    pub fn first_result_match(&self, url: &str, result: &SearchResult) -> Option<FilterMatch<'_>> {
+        let url = url.to_lowercase();
+        self.url_rules
+            .first_match(&url)
+            .map(|idx| self.filter_match(idx))
+            .or_else(|| self.first_match(&url))
+            .or_else(|| self.first_match(&result.title.to_lowercase()))
+            .or_else(|| self.first_match(&result.description.to_lowercase()))
+    }
+
+    /// A helper function which builds the `FilterMatch` for the rule at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - It takes the index of the rule in the `lines` of the list as an argument.
+    fn filter_match(&self, idx: usize) -> FilterMatch<'_> {
+        let (line_number, pattern) = &self.lines[idx];
+        FilterMatch {
+            line_number: *line_number,
+            pattern,
+        }
+    }
+}
+
+/// A helper function which parses a line written in the uBlacklist syntax, which is either a
+/// match pattern (`*://*.example.com/*`) or a regex enclosed in slashes (`/example\.(com|net)/`).
+/// Both are converted to a regex matched against the lowercased result urls.
+///
+/// # Arguments
+///
+/// * `line` - It takes the trimmed line of the list as an argument.
+///
+/// # Returns
+///
+/// Returns `None` if the line is not written in the uBlacklist syntax.
+fn parse_rule(line: &str) -> Option<Rule> {
+    if let Some(regex) = line
+        .strip_prefix('/')
+        .and_then(|line| line.strip_suffix('/'))
+        .filter(|regex| !regex.is_empty())
+    {
+        return Some(Rule::Url(regex.to_owned()));
+    }
+
+    let (scheme, rest) = line.split_once("://")?;
+    let (host, path) = rest.split_at(rest.find('/')?);
+    let scheme = match scheme {
+        "*" => "https?",
+        "http" | "https" => scheme,
+        _ => return None,
+    };
+    // Anything which looks like a regex is left to be parsed as one.
+    if host.is_empty() || host.contains(|c: char| "\\()[]{}?+|^$".contains(c)) {
+        return None;
+    }
+    let host = host.to_lowercase();
+    let host = match host.as_str() {
+        "*" => "[^/]*".to_owned(),
+        _ => match host.strip_prefix("*.") {
+            Some(domain) if !domain.contains('*') => {
+                format!("([^/]*\\.)?{}", regex::escape(domain))
+            }
+            None if !host.contains('*') => regex::escape(&host),
+            _ => return None,
+        },
+    };
+    let path = match path {
+        "/*" => "(/.*)?".to_owned(),
+        _ => path
+            .to_lowercase()
+            .split('*')
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(".*"),
+    };
+
+    Some(Rule::Url(format!("^{scheme}://{host}(:[0-9]+)?{path}$")))
+}
+
+/// A named struct which stores a filter list which is recompiled whenever its file changes.
+struct WatchedFilterList {
+    /// It stores the path of the list file.
+    path: PathBuf,
+    /// It stores the syntax in which the list is written.
+    syntax: ListSyntax,
+    /// It stores the currently compiled list, which is swapped for a new one once the list file
+    /// has been recompiled.
+    list: ArcSwap<FilterList>,
+    /// It stores the modification time of the list file when it was last compiled.
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl WatchedFilterList {
+    /// Compiles the local list present at the given path. Like a subscription list which has not
+    /// been downloaded yet, a missing list is treated as an empty list until its file shows up.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - It takes the path of the list file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the existing file cannot be read or if any of the rules is invalid.
+    fn new(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = PathBuf::from(file_path);
+        let (list, modified) = match fs::metadata(&path) {
+            Ok(metadata) => (FilterList::from_file(file_path)?, metadata.modified().ok()),
+            Err(error) if error.kind() == ErrorKind::NotFound => (FilterList::default(), None),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(WatchedFilterList {
+            path,
+            syntax: ListSyntax::Local,
+            list: ArcSwap::from_pointee(list),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// Compiles the subscription list present at the given path. As the subscription lists are
+    /// downloaded in the background, a list which has not been downloaded yet is treated as an
+    /// empty list until its file shows up.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - It takes the path of the downloaded subscription list as an argument.
+    fn subscription(path: PathBuf) -> Self {
+        let (list, modified) = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => {
+                match FilterList::from_file_with_syntax(
+                    &path.to_string_lossy(),
+                    ListSyntax::Subscription,
+                ) {
+                    Ok(list) => (list, Some(modified)),
+                    Err(error) => {
+                        log::error!(
+                            "Filter List Error: Failed to load {}: {}",
+                            path.display(),
+                            error
+                        );
+                        (FilterList::default(), Some(modified))
+                    }
+                }
+            }
+            Err(_) => (FilterList::default(), None),
+        };
+
+        WatchedFilterList {
+            path,
+            syntax: ListSyntax::Subscription,
+            list: ArcSwap::from_pointee(list),
+            modified: Mutex::new(modified),
+        }
+    }
+
+    /// A getter function that gets the currently compiled list.
+    fn get(&self) -> Arc<FilterList> {
+        self.list.load_full()
+    }
+
+    /// Recompiles the list if the list file has changed since it was last compiled. If the
+    /// changed file cannot be compiled, the error is logged and the previously compiled list
+    /// keeps being used. As the file is read and compiled while this function runs, it must not
+    /// be called while serving a request.
+    fn reload(&self) {
+        let mut modified = self
+            .modified
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        match fs::metadata(&self.path).and_then(|metadata| metadata.modified()) {
+            Ok(file_modified) if Some(file_modified) != *modified => {
+                // The modification time is recorded even on failure so that a broken file is
+                // only reported once per change.
+                *modified = Some(file_modified);
+                match FilterList::from_file_with_syntax(&self.path.to_string_lossy(), self.syntax) {
+                    Ok(list) => {
+                        log::info!("Reloaded the filter list {}", self.path.display());
+                        self.list.store(Arc::new(list));
+                    }
+                    Err(error) => log::error!(
+                        "Filter List Error: Failed to reload {}: {}",
+                        self.path.display(),
+                        error
+                    ),
+                }
+            }
+            Ok(_) => (),
+            // A subscription list which has not been downloaded yet is not an error.
+            Err(error) if error.kind() == ErrorKind::NotFound && modified.is_none() => {}
+            Err(error) => log::error!(
+                "Filter List Error: Failed to check {}: {}",
+                self.path.display(),
+                error
+            ),
+        }
+    }
+}
+
+/// A named struct which provides the blocklist and allowlist filtering of the search results and
+/// search queries. It is built on startup and whenever the subscription lists change on a config
+/// reload, and it is shared between all the requests.
+pub struct FilterEngine {
+    /// It stores the compiled blocklist.
+    blocklist: WatchedFilterList,
+    /// It stores the compiled allowlist.
+    allowlist: WatchedFilterList,
+    /// It stores the compiled subscription lists, which are used as additional blocklists.
+    subscriptions: Vec<WatchedFilterList>,
+}
+
+impl FilterEngine {
+    /// Builds the filter engine from the blocklist and allowlist files found on the standard
+    /// paths along with the subscription lists provided in the config file.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes the parsed config as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if any of the existing list files cannot be read or compiled.
+    pub fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        // The blocklist and the allowlist are optional, a missing list being an empty list.
+        Ok(Self::from_files(
+            file_path(FileType::BlockList).unwrap_or_default(),
+            file_path(FileType::AllowList).unwrap_or_default(),
+        )?
+        .with_subscriptions(subscription_paths(&config.filter_subscriptions)?))
+    }
+
+    /// Builds the filter engine from the blocklist and allowlist files present at the given
+    /// paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `blocklist_path` - It takes the path of the blocklist file as an argument.
+    /// * `allowlist_path` - It takes the path of the allowlist file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if any of the existing list files cannot be read or compiled.
+    pub fn from_files(
+        blocklist_path: &str,
+        allowlist_path: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(FilterEngine {
+            blocklist: WatchedFilterList::new(blocklist_path)?,
+            allowlist: WatchedFilterList::new(allowlist_path)?,
+            subscriptions: Vec::new(),
+        })
+    }
+
+    /// Adds the subscription lists downloaded to the given paths to the filter engine. The
+    /// lists are picked up as soon as they are downloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - It takes the paths of the downloaded subscription lists as an argument.
+    pub fn with_subscriptions(mut self, paths: Vec<PathBuf>) -> Self {
+        self.subscriptions
+            .extend(paths.into_iter().map(WatchedFilterList::subscription));
+        self
+    }
+
+    /// Recompiles the lists whose files have changed since they were last compiled. As the files
+    /// are read and compiled while this function runs, it is meant to be called on a blocking
+    /// thread.
+    pub fn reload_changed_lists(&self) {
+        for list in std::iter::once(&self.blocklist)
+            .chain(std::iter::once(&self.allowlist))
+            .chain(&self.subscriptions)
+        {
+            list.reload();
+        }
+    }
+
+    /// A getter function that gets the currently compiled blocklist.
+    pub fn blocklist(&self) -> Arc<FilterList> {
+        self.blocklist.get()
+    }
+
+    /// A getter function that gets the currently compiled allowlist.
+    pub fn allowlist(&self) -> Arc<FilterList> {
+        self.allowlist.get()
+    }
+
+    /// Returns the hash of the contents of the compiled lists, which changes whenever the rules
+    /// of any of the lists change, so that the results filtered with the previous lists can be
+    /// told apart. As it only depends on the contents of the lists, it stays the same across
+    /// restarts and between the servers sharing a cache.
+    pub fn content_hash(&self) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        for list in std::iter::once(&self.blocklist)
+            .chain(std::iter::once(&self.allowlist))
+            .chain(&self.subscriptions)
+        {
+            hasher.update(list.get().digest().as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    /// Removes the search results matching the blocklist or any of the subscription lists unless
+    /// they also match the allowlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - It takes a mutable reference to the search results to be filtered as an
+    /// argument.
+    pub fn filter_results(&self, results: &mut Vec<(String, SearchResult)>) {
+        let allowlist = self.allowlist();
+        let blocklists: Vec<(&Path, Arc<FilterList>)> = std::iter::once(&self.blocklist)
+            .chain(&self.subscriptions)
+            .map(|list| (list.path.as_path(), list.get()))
+            .collect();
+
+        results.retain(|(url, result)| {
+            let (path, blocked) = match blocklists.iter().find_map(|(path, list)| {
+                list.first_result_match(url, result)
+                    .map(|blocked| (path, blocked))
+            }) {
+                Some(blocked) => blocked,
+                None => return true,
+            };
+            if allowlist.first_result_match(url, result).is_some() {
+                return true;
+            }
+
+            log::debug!(
+                "Filtered {} by line {} of {}: {}",
+                url,
+                blocked.line_number,
+                path.display(),
+                blocked.pattern
+            );
+            false
+        });
+    }
+}
+
+/// Recompiles the filter lists of the current filter engine whenever their files change. This
+/// function runs until the server is stopped and it is meant to be spawned as a background task
+/// on startup.
+///
+/// # Arguments
+///
+/// * `shared_config` - It takes the shared config holding the current filter engine as an
+/// argument.
+pub async fn watch_filter_lists(shared_config: &'static SharedConfig) {
+    loop {
+        tokio::time::sleep(RELOAD_CHECK_INTERVAL).await;
+        let filter_engine = Arc::clone(&shared_config.snapshot().filter_engine);
+        // The lists are read and compiled on a blocking thread to keep serving the requests.
+        if let Err(error) =
+            tokio::task::spawn_blocking(move || filter_engine.reload_changed_lists()).await
+        {
+            log::error!("Filter List Error: Failed to reload the filter lists: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smallvec::smallvec;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn results() -> Vec<(String, SearchResult)> {
+        vec![
+            (
+                "https://www.example.com".to_owned(),
+                SearchResult {
+                    title: "Example Domain".to_owned(),
+                    url: "https://www.example.com".to_owned(),
+                    description: "This domain is for use in illustrative examples in documents."
+                        .to_owned(),
+                    relevance_score: 0.0,
+                    engine: smallvec!["Google".to_owned(), "Bing".to_owned()],
+                    original_url: None,
+                    blurred: false,
+                },
+            ),
+            (
+                "https://www.rust-lang.org/".to_owned(),
+                SearchResult {
+                    title: "Rust Programming Language".to_owned(),
+                    url: "https://www.rust-lang.org/".to_owned(),
+                    description: "A systems programming language that runs blazingly fast, prevents segfaults, and guarantees thread safety.".to_owned(),
+                    engine: smallvec!["Google".to_owned(), "DuckDuckGo".to_owned()],
+                    relevance_score: 0.0,
+                    original_url: None,
+                    blurred: false,
+                },
+            ),
+        ]
+    }
+
+    fn list_file(lines: &[&str]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in lines {
+            writeln!(file, "{line}").unwrap();
+        }
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn test_filter_results() -> Result<(), Box<dyn std::error::Error>> {
+        let blocklist = list_file(&["example", "rust"]);
+        let allowlist = list_file(&[]);
+        let engine = FilterEngine::from_files(
+            blocklist.path().to_str().unwrap(),
+            allowlist.path().to_str().unwrap(),
+        )?;
+
+        let mut results = results();
+        engine.filter_results(&mut results);
+
+        assert!(results.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_results_wildcard() -> Result<(), Box<dyn std::error::Error>> {
+        let blocklist = list_file(&["ex.*le"]);
+        let allowlist = list_file(&[]);
+        let engine = FilterEngine::from_files(
+            blocklist.path().to_str().unwrap(),
+            allowlist.path().to_str().unwrap(),
+        )?;
+
+        let mut results = results();
+        engine.filter_results(&mut results);
+
+        assert_eq!(results.len(), 1);
+        assert!(results
+            .iter()
+            .any(|(key, _)| key == "https://www.rust-lang.org/"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_allowlist_overrides_blocklist() -> Result<(), Box<dyn std::error::Error>> {
+        let blocklist = list_file(&["example", "rust"]);
+        let allowlist = list_file(&["rust-lang\\.org"]);
+        let engine = FilterEngine::from_files(
+            blocklist.path().to_str().unwrap(),
+            allowlist.path().to_str().unwrap(),
+        )?;
+
+        let mut results = results();
+        engine.filter_results(&mut results);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "https://www.rust-lang.org/");
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments_blank_lines_and_matched_line() -> Result<(), Box<dyn std::error::Error>> {
+        let list = FilterList::parse("# a comment\n\n  gambling  \ncasino\n")?;
+
+        assert_eq!(
+            list.first_match("online casino"),
+            Some(FilterMatch {
+                line_number: 4,
+                pattern: "casino"
+            })
+        );
+        assert_eq!(list.first_match("gambling").map(|m| m.line_number), Some(3));
+        assert!(!list.is_match("a comment"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_on_change() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = list_file(&["example"]);
+        let list = WatchedFilterList::new(file.path().to_str().unwrap())?;
+        assert!(list.get().is_match("example"));
+        let digest = list.get().digest();
+
+        writeln!(file, "rust")?;
+        file.flush()?;
+        // Pretend that the file was compiled a while ago.
+        *list.modified.lock().unwrap() = Some(SystemTime::UNIX_EPOCH);
+
+        assert!(!list.get().is_match("rust"));
+        list.reload();
+        assert!(list.get().is_match("rust"));
+        assert_ne!(list.get().digest(), digest);
+        Ok(())
+    }
+
+    #[test]
+    fn test_digest_only_depends_on_the_rules() -> Result<(), Box<dyn std::error::Error>> {
+        let list = FilterList::parse("example\n*://*.example.com/*\n")?;
+        let same = FilterList::parse("# a comment\n\n  example  \n*://*.example.com/*\n")?;
+        let other = FilterList::parse("example\n")?;
+
+        assert_eq!(list.digest(), same.digest());
+        assert_ne!(list.digest(), other.digest());
+        assert_ne!(other.digest(), FilterList::default().digest());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ublacklist_match_patterns() -> Result<(), Box<dyn std::error::Error>> {
+        let list = FilterList::parse("*://*.example.com/*\nhttps://www.rust-lang.org/learn*\n")?;
+        let result = SearchResult::new("Title", "", "", &["bing"]);
+
+        let blocked = |url: &str| list.first_result_match(url, &result).map(|m| m.line_number);
+        assert_eq!(blocked("https://example.com"), Some(1));
+        assert_eq!(blocked("http://sub.EXAMPLE.com:8080/path?q=1"), Some(1));
+        assert_eq!(blocked("https://notexample.com/"), None);
+        assert_eq!(
+            blocked("https://www.rust-lang.org/learn/get-started"),
+            Some(2)
+        );
+        assert_eq!(blocked("http://www.rust-lang.org/learn"), None);
+        // The url patterns are never matched against the search queries.
+        assert!(list.first_match("*://*.example.com/*").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ublacklist_regex_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let list = FilterList::parse("/example\\.(com|net)/\nhttps?://casino\n")?;
+        let result = SearchResult::new("Title", "", "", &["bing"]);
+
+        assert!(list
+            .first_result_match("https://www.example.net/", &result)
+            .is_some());
+        // A line which is not a valid match pattern is still parsed as a raw regex.
+        assert_eq!(
+            list.first_match("http://casino").map(|m| m.line_number),
+            Some(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscription_syntax_skips_unknown_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let list = FilterList::parse_with_syntax(
+            "! title\n@*://allowed.com/*\n/invalid(/\n*://*.pinterest.com/*\n",
+            ListSyntax::Subscription,
+        )?;
+        let result = SearchResult::new("Title", "", "", &["bing"]);
+
+        assert_eq!(
+            list.first_result_match("https://www.pinterest.com/pin/1", &result),
+            Some(FilterMatch {
+                line_number: 4,
+                pattern: "*://*.pinterest.com/*"
+            })
+        );
+        assert!(list
+            .first_result_match("https://allowed.com/", &result)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscriptions_block_results() -> Result<(), Box<dyn std::error::Error>> {
+        let blocklist = list_file(&[]);
+        let allowlist = list_file(&[]);
+        let subscription = list_file(&["*://www.example.com/*"]);
+        let missing_subscription = PathBuf::from("non-existent-subscription.txt");
+        let engine = FilterEngine::from_files(
+            blocklist.path().to_str().unwrap(),
+            allowlist.path().to_str().unwrap(),
+        )?
+        .with_subscriptions(vec![subscription.path().to_owned(), missing_subscription]);
+
+        let mut results = results();
+        engine.filter_results(&mut results);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "https://www.rust-lang.org/");
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_lists_are_empty() -> Result<(), Box<dyn std::error::Error>> {
+        let engine = FilterEngine::from_files("non-existent-blocklist.txt", "")?;
+
+        let mut results = results();
+        engine.filter_results(&mut results);
+
+        assert_eq!(results.len(), 2);
+        assert!(!engine.blocklist().is_match("example"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_list_file_not_found() {
+        assert!(FilterList::from_file("non-existent-file.txt").is_err());
+    }
+
+    #[test]
+    fn test_filter_list_invalid_regex() {
+        let file = list_file(&["example("]);
+        assert!(FilterList::from_file(file.path().to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! This module provides the functionality to highlight the terms of the search query in the
+//! plain text titles and descriptions of the search results. The matching is case and
+//! diacritic insensitive and supports quoted phrases.
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::models::aggregation_models::query_tokens;
+
+/// A named struct which stores the folded terms and phrases of the search query which should be
+/// highlighted in the search results.
+pub struct Highlighter {
+    /// It stores the case and diacritic folded terms and quoted phrases of the query.
+    needles: Vec<String>,
+}
+
+impl Highlighter {
+    /// Constructs a new `Highlighter` from the search query. Quoted phrases are highlighted as a
+    /// whole while the rest of the query is split into terms using the same tokenizer and stop
+    /// words as are used to calculate the relevance score of the results.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - It takes the user's search query as an argument.
+    pub fn new(query: &str) -> Self {
+        let parts: Vec<&str> = query.split('"').collect();
+        // An unbalanced quote leaves the text after it unquoted.
+        let closed_parts = parts.len() - usize::from(parts.len() % 2 == 0);
+
+        let mut needles = Vec::new();
+        let mut unquoted = String::new();
+        for (idx, part) in parts.iter().enumerate() {
+            if idx % 2 == 1 && idx < closed_parts {
+                let phrase = fold(part).split_whitespace().collect::<Vec<_>>().join(" ");
+                if !phrase.is_empty() {
+                    needles.push(phrase);
+                }
+            } else {
+                unquoted.push_str(part);
+                unquoted.push(' ');
+            }
+        }
+
+        needles.extend(
+            query_tokens(&unquoted)
+                .iter()
+                .map(|token| fold(token))
+                .filter(|token| !token.is_empty()),
+        );
+        needles.sort_unstable();
+        needles.dedup();
+
+        Highlighter { needles }
+    }
+
+    /// Splits the text into segments, marking the ones which match a term or a phrase of the
+    /// query. A term only matches at the start of a word, so `rust` highlights the start of
+    /// `Rustacean` but not `trust`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - It takes the plain text title or description of a search result as an argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns the segments of the text along with whether they should be highlighted or not.
+    pub fn highlight<'a>(&self, text: &'a str) -> Vec<(&'a str, bool)> {
+        if self.needles.is_empty() {
+            return vec![(text, false)];
+        }
+
+        // The folded text along with the byte range of the original character for each byte.
+        let mut folded = String::with_capacity(text.len());
+        let mut offsets: Vec<(usize, usize)> = Vec::with_capacity(text.len());
+        for (start, character) in text.char_indices() {
+            let end = start + character.len_utf8();
+            for folded_character in fold_chars(std::iter::once(character)) {
+                folded.push(folded_character);
+                offsets.extend(std::iter::repeat((start, end)).take(folded_character.len_utf8()));
+            }
+        }
+
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for needle in &self.needles {
+            for (idx, _) in folded.match_indices(needle.as_str()) {
+                let at_word_start = !matches!(
+                    folded[..idx].chars().next_back(),
+                    Some(previous) if previous.is_alphanumeric()
+                );
+                if at_word_start {
+                    ranges.push((offsets[idx].0, offsets[idx + needle.len() - 1].1));
+                }
+            }
+        }
+        ranges.sort_unstable();
+
+        let mut segments = Vec::with_capacity(ranges.len() * 2 + 1);
+        let mut position = 0;
+        for (start, end) in ranges {
+            if end <= position {
+                continue;
+            }
+            let start = start.max(position);
+            if start > position {
+                segments.push((&text[position..start], false));
+            }
+            // Merge with the previous highlighted segment when the ranges overlap or touch.
+            match segments.last_mut() {
+                Some((segment, true)) if start == position => {
+                    *segment = &text[position - segment.len()..end];
+                }
+                _ => segments.push((&text[start..end], true)),
+            }
+            position = end;
+        }
+        if position < text.len() {
+            segments.push((&text[position..], false));
+        }
+
+        segments
+    }
+}
+
+/// Folds the given text by removing the diacritics and converting it to lowercase.
+///
+/// # Arguments
+///
+/// * `text` - It takes the text to be folded as an argument.
+fn fold(text: &str) -> String {
+    fold_chars(text.chars()).collect()
+}
+
+/// Folds the given characters by decomposing them, removing the combining marks (diacritics)
+/// and converting them to lowercase.
+///
+/// # Arguments
+///
+/// * `characters` - It takes an iterator over the characters to be folded as an argument.
+fn fold_chars(characters: impl Iterator<Item = char>) -> impl Iterator<Item = char> {
+    characters
+        .nfd()
+        .filter(|character| !is_combining_mark(*character))
+        .flat_map(char::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(highlighter: &Highlighter, text: &str) -> String {
+        highlighter
+            .highlight(text)
+            .into_iter()
+            .map(|(segment, highlighted)| match highlighted {
+                true => format!("[{segment}]"),
+                false => segment.to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_case_and_diacritic_insensitive_highlighting() {
+        let highlighter = Highlighter::new("cafe rust");
+        assert_eq!(
+            marked(&highlighter, "The Café of RUST and trust"),
+            "The [Café] of [RUST] and trust"
+        );
+    }
+
+    #[test]
+    fn test_quoted_phrase_highlighting() {
+        let highlighter = Highlighter::new("\"memory safety\" language");
+        assert_eq!(
+            marked(
+                &highlighter,
+                "Memory  safety and memory safety in a language"
+            ),
+            "Memory  safety and [memory safety] in a [language]"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_matches_are_merged() {
+        let highlighter = Highlighter::new("\"rust lang\" rust");
+        assert_eq!(marked(&highlighter, "rust language"), "[rust lang]uage");
+    }
+
+    #[test]
+    fn test_no_terms() {
+        let highlighter = Highlighter::new("");
+        assert_eq!(marked(&highlighter, "nothing to see"), "nothing to see");
+    }
+}
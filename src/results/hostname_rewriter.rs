@@ -0,0 +1,100 @@
+//! This module provides the functionality to rewrite the hostnames of the search result urls to
+//! privacy respecting front-ends (for example `www.youtube.com` to `yewtu.be`) based on the
+//! replacement table provided in the config file.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use reqwest::Url;
+
+use crate::models::aggregation_models::SearchResult;
+
+/// A named struct which stores the hostname replacements which are enabled for the current
+/// search request. The replacements are copied from the config, so that a search keeps using them
+/// when the config is reloaded while it is running.
+#[derive(Default)]
+pub struct HostnameReplacements {
+    /// It stores the lowercased original hostnames mapped to their replacement hostnames.
+    replacements: HashMap<String, String>,
+}
+
+impl HostnameReplacements {
+    /// Constructs a new `HostnameReplacements` from the replacement table provided in the config
+    /// file by leaving out the replacements that were disabled by the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `replacements` - It takes the replacement table from the config file as an argument.
+    /// * `disabled` - It takes the original hostnames whose replacement was disabled by the user
+    /// through the settings page as an argument.
+    pub fn new(replacements: &HashMap<String, String>, disabled: &[Cow<'_, str>]) -> Self {
+        Self {
+            replacements: replacements
+                .iter()
+                .filter(|(from, _)| !disabled.iter().any(|host| host == from.as_str()))
+                .map(|(from, to)| (from.clone(), to.clone()))
+                .collect(),
+        }
+    }
+
+    /// Rewrites the hostname of the search result url if a replacement is available for it. The
+    /// original url is kept in the `original_url` field of the search result.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - It takes the search result to be rewritten as an argument.
+    pub fn rewrite(&self, result: &mut SearchResult) {
+        if self.replacements.is_empty() {
+            return;
+        }
+
+        let mut url = match Url::parse(&result.url) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+
+        let replacement = match url
+            .host_str()
+            .and_then(|host| self.replacements.get(host.to_lowercase().as_str()))
+        {
+            Some(replacement) => replacement.as_str(),
+            None => return,
+        };
+
+        if url.set_host(Some(replacement)).is_ok() {
+            result.original_url = Some(std::mem::replace(&mut result.url, url.into()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostname_rewrite() {
+        let table = HashMap::from([
+            ("www.youtube.com".to_owned(), "yewtu.be".to_owned()),
+            ("www.reddit.com".to_owned(), "old.reddit.com".to_owned()),
+        ]);
+        let disabled = [Cow::Borrowed("www.reddit.com")];
+        let replacements = HostnameReplacements::new(&table, &disabled);
+
+        let mut result = SearchResult::new(
+            "Rust",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "",
+            &["bing"],
+        );
+        replacements.rewrite(&mut result);
+        assert_eq!(result.url, "https://yewtu.be/watch?v=dQw4w9WgXcQ");
+        assert_eq!(
+            result.original_url.as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+        );
+
+        let mut result = SearchResult::new("Rust", "https://www.reddit.com/r/rust/", "", &["bing"]);
+        replacements.rewrite(&mut result);
+        assert_eq!(result.url, "https://www.reddit.com/r/rust/");
+        assert!(result.original_url.is_none());
+    }
+}
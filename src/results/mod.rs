@@ -1,6 +1,15 @@
 //! This module provides modules that handle the functionality to aggregate the fetched search
-//! results from the upstream search engines and filters it if safe search is set to 3 or 4. Also,
+//! results from the upstream search engines and filters it using the filter lists. Also,
 //! provides various models to aggregate search results into a standardized form.
 
 pub mod aggregator;
+pub mod blocked_sites;
+pub mod domain_rules;
+pub mod filter_engine;
+pub mod highlighter;
+pub mod hostname_rewriter;
+pub mod query_classifier;
+pub mod safe_search_classifier;
+pub mod sanitizer;
+pub mod subscriptions;
 mod user_agent;
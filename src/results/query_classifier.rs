@@ -0,0 +1,236 @@
+//! This module provides the functionality to classify the search queries into categories (like
+//! adult, violence or gambling) using the category list files, so that the queries belonging to a
+//! category can be disallowed from the safe search level configured for it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use super::safe_search_classifier::words_of;
+
+/// A named struct which stores the words and phrases of a single query category.
+#[derive(Clone, Debug, Default)]
+pub struct QueryCategory {
+    /// It stores the name of the category, which is the name of its list file.
+    name: String,
+    /// It stores the safe search level from which the queries of the category are disallowed.
+    blocked_from_level: u8,
+    /// It stores the lowercased single words of the category.
+    words: HashSet<String>,
+    /// It stores the lowercased phrases (made of several words) of the category.
+    phrases: Vec<String>,
+}
+
+impl QueryCategory {
+    /// Parses the lines of a category list file, where each line is a word or a phrase. Blank
+    /// lines and lines starting with `#` are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - It takes the name of the category as an argument.
+    /// * `blocked_from_level` - It takes the safe search level from which the queries of the
+    /// category are disallowed as an argument.
+    /// * `lines` - It takes an iterator over the lines of the category list file as an argument.
+    pub fn parse<'a>(
+        name: &str,
+        blocked_from_level: u8,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut category = QueryCategory {
+            name: name.to_owned(),
+            blocked_from_level,
+            ..Default::default()
+        };
+
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = words_of(line);
+            match words.len() {
+                0 => (),
+                1 => {
+                    category.words.extend(words);
+                }
+                _ => category.phrases.push(words.join(" ")),
+            }
+        }
+
+        category
+    }
+
+    /// A getter function that gets the name of the category.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks whether the query, split into lowercased words, belongs to the category.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - It takes the lowercased words of the query as an argument.
+    /// * `text` - It takes the lowercased words of the query joined and surrounded by spaces as
+    /// an argument.
+    fn matches(&self, words: &[String], text: &str) -> bool {
+        words.iter().any(|word| self.words.contains(word))
+            || self
+                .phrases
+                .iter()
+                .any(|phrase| text.contains(&format!(" {phrase} ")))
+    }
+}
+
+/// A named struct which stores the query categories which are disallowed from some safe search
+/// level.
+#[derive(Clone, Debug, Default)]
+pub struct QueryClassifier {
+    /// It stores the categories in the order of their names.
+    categories: Vec<QueryCategory>,
+}
+
+impl QueryClassifier {
+    /// Loads the list files (`<category>.txt`) of the categories configured in the config file
+    /// from the given categories folder. A configured category without a list file is reported
+    /// in the logs and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - It takes the path of the categories folder as an argument.
+    /// * `blocked_categories` - It takes the category names mapped to the safe search level from
+    /// which they are disallowed as an argument.
+    pub fn from_directory(directory: &Path, blocked_categories: &HashMap<String, u8>) -> Self {
+        let mut categories: Vec<QueryCategory> = blocked_categories
+            .iter()
+            .filter_map(|(name, level)| {
+                let path = directory.join(format!("{name}.txt"));
+                match fs::read_to_string(&path) {
+                    Ok(contents) => Some(QueryCategory::parse(name, *level, contents.lines())),
+                    Err(error) => {
+                        log::error!(
+                            "Query Category Error: Failed to read {}: {}",
+                            path.display(),
+                            error
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        categories.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        QueryClassifier { categories }
+    }
+
+    /// Constructs a new `QueryClassifier` from already parsed categories.
+    ///
+    /// # Arguments
+    ///
+    /// * `categories` - It takes the parsed categories as an argument.
+    pub fn new(categories: Vec<QueryCategory>) -> Self {
+        QueryClassifier { categories }
+    }
+
+    /// Returns the first category which the query belongs to and which is disallowed at the
+    /// given safe search level, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - It takes the user's search query as an argument.
+    /// * `safe_search_level` - It takes the safe search level of the current search as an
+    /// argument.
+    pub fn blocked_category(&self, query: &str, safe_search_level: u8) -> Option<&QueryCategory> {
+        let mut blocking = self
+            .categories
+            .iter()
+            .filter(|category| safe_search_level >= category.blocked_from_level)
+            .peekable();
+        blocking.peek()?;
+
+        let words = words_of(query);
+        let text = format!(" {} ", words.join(" "));
+        blocking.find(|category| category.matches(&words, &text))
+    }
+
+    /// Returns the hash of the categories, which only depends on their contents, so that it
+    /// stays the same across restarts and between the servers sharing a cache.
+    pub fn content_hash(&self) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        for category in &self.categories {
+            // The words are sorted as the iteration order of the set changes between runs.
+            let mut words: Vec<&String> = category.words.iter().collect();
+            words.sort_unstable();
+            let contents = (
+                &category.name,
+                category.blocked_from_level,
+                words,
+                &category.phrases,
+            );
+            hasher.update(format!("{contents:?}").as_bytes());
+        }
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classifier() -> QueryClassifier {
+        QueryClassifier::new(vec![
+            QueryCategory::parse("adult", 2, ["# comment", "porn", "adult video"]),
+            QueryCategory::parse("gambling", 4, ["casino", "sports betting"]),
+        ])
+    }
+
+    #[test]
+    fn test_blocked_category_per_level() {
+        let classifier = classifier();
+
+        assert!(classifier.blocked_category("free porn", 1).is_none());
+        assert_eq!(
+            classifier
+                .blocked_category("Free PORN", 2)
+                .map(QueryCategory::name),
+            Some("adult")
+        );
+        assert!(classifier.blocked_category("online casino", 3).is_none());
+        assert_eq!(
+            classifier
+                .blocked_category("online casino", 4)
+                .map(QueryCategory::name),
+            Some("gambling")
+        );
+    }
+
+    #[test]
+    fn test_phrases_match_whole_words() {
+        let classifier = classifier();
+
+        assert!(classifier
+            .blocked_category("best sports betting sites", 4)
+            .is_some());
+        assert!(classifier.blocked_category("sports bettings", 4).is_none());
+        assert!(classifier.blocked_category("pornography", 4).is_none());
+        assert!(classifier.blocked_category("rust programming", 4).is_none());
+    }
+
+    #[test]
+    fn test_missing_category_file_is_skipped() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("adult.txt"), "porn\n").unwrap();
+        let classifier = QueryClassifier::from_directory(
+            directory.path(),
+            &HashMap::from([("adult".to_owned(), 3), ("missing".to_owned(), 3)]),
+        );
+
+        assert_eq!(
+            classifier
+                .blocked_category("porn", 3)
+                .map(QueryCategory::name),
+            Some("adult")
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! This module provides a local content classifier which scores the titles, descriptions and
+//! domains of the search results against the weighted word and domain lists of the safe search
+//! file. The file provides a list and a threshold for each of the `low` and `moderate` levels,
+//! the lists of a level adding up with the lists of the lower levels. The results scoring above
+//! the threshold of the level are blurred with the `low` safe search level and removed with the
+//! higher levels, so that the filtering does not depend on whether the upstream engines honour the
+//! safe search level or not.
+
+use std::{collections::HashMap, fs};
+
+use super::domain_rules::{host_matches, host_of};
+use crate::models::aggregation_models::SearchResult;
+
+/// The default score from which a search result is considered to be unsafe.
+const DEFAULT_UNSAFE_SCORE_THRESHOLD: f32 = 1.0;
+
+/// The names of the sections of the safe search file, in the order of the safe search levels
+/// (starting from the `low` level) from which their lists apply.
+const LEVEL_SECTIONS: [&str; 2] = ["low", "moderate"];
+
+/// An enum type which provides the different actions that are applied to the unsafe search
+/// results depending on the safe search level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClassifierAction {
+    /// Keeps the unsafe results but blurs them until the user reveals them.
+    Blur,
+    /// Removes the unsafe results.
+    Remove,
+}
+
+impl ClassifierAction {
+    /// Returns the action to be applied to the unsafe results for the given safe search level.
+    ///
+    /// # Arguments
+    ///
+    /// * `safe_search_level` - It takes the safe search level of the current search as an
+    /// argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if no classification should occur for the level.
+    pub fn for_level(safe_search_level: u8) -> Option<Self> {
+        match safe_search_level {
+            0 => None,
+            1 => Some(ClassifierAction::Blur),
+            _ => Some(ClassifierAction::Remove),
+        }
+    }
+}
+
+/// A named struct which stores the weighted words, phrases and domains of a safe search level
+/// along with the score from which the results are considered to be unsafe at that level.
+#[derive(Clone, Debug)]
+struct WeightedLists {
+    /// It stores the lowercased single words mapped to their weights.
+    words: HashMap<String, f32>,
+    /// It stores the lowercased phrases (made of several words) along with their weights.
+    phrases: Vec<(String, f32)>,
+    /// It stores the lowercased host patterns, which may contain `*` wildcards, along with their
+    /// weights.
+    domains: Vec<(String, f32)>,
+    /// It stores the score from which a search result is considered to be unsafe.
+    threshold: f32,
+}
+
+impl Default for WeightedLists {
+    fn default() -> Self {
+        WeightedLists {
+            words: HashMap::new(),
+            phrases: Vec::new(),
+            domains: Vec::new(),
+            threshold: DEFAULT_UNSAFE_SCORE_THRESHOLD,
+        }
+    }
+}
+
+impl WeightedLists {
+    /// Checks whether the lists contain no words, phrases or domains.
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.phrases.is_empty() && self.domains.is_empty()
+    }
+
+    /// Scores a search result by adding up the weights of the distinct words and phrases found
+    /// in its title and description along with the weight of the first domain pattern matching
+    /// its host.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - It takes the words of the title of the search result as an argument.
+    /// * `description` - It takes the words of the description of the search result as an
+    /// argument.
+    /// * `host` - It takes the host of the search result url, if any, as an argument.
+    fn score(&self, title: &[String], description: &[String], host: Option<&str>) -> f32 {
+        let mut matched_words: Vec<&str> = title
+            .iter()
+            .chain(description)
+            .map(String::as_str)
+            .filter(|word| self.words.contains_key(*word))
+            .collect();
+        matched_words.sort_unstable();
+        matched_words.dedup();
+        let mut score: f32 = matched_words.iter().map(|word| self.words[*word]).sum();
+
+        if !self.phrases.is_empty() {
+            // The words are joined with spaces around, so that phrases only match whole words.
+            let text = format!(" {} | {} ", title.join(" "), description.join(" "));
+            score += self
+                .phrases
+                .iter()
+                .filter(|(phrase, _)| text.contains(&format!(" {phrase} ")))
+                .map(|(_, weight)| weight)
+                .sum::<f32>();
+        }
+
+        if let Some(host) = host {
+            score += self
+                .domains
+                .iter()
+                .find(|(pattern, _)| host_matches(pattern, host))
+                .map_or(0.0, |(_, weight)| *weight);
+        }
+
+        score
+    }
+}
+
+/// A named struct which stores the weighted words, phrases and domains used to score the search
+/// results for each of the safe search levels.
+#[derive(Clone, Debug, Default)]
+pub struct SafeSearchClassifier {
+    /// It stores the lists of the `low` and `moderate` levels, in that order.
+    levels: [WeightedLists; 2],
+}
+
+impl SafeSearchClassifier {
+    /// Parses a list of lines of the form `word <weight> <word or phrase>`,
+    /// `domain <weight> <host pattern>` or `threshold <score>`, skipping blank lines and
+    /// comments. The `[low]` and `[moderate]` section headers select the level to which the
+    /// following lines apply, the lines before any header applying to the `low` level. Malformed
+    /// lines and the lines of unknown sections are ignored and reported in the logs.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - It takes an iterator over the lines of the safe search file as an argument.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut classifier = SafeSearchClassifier::default();
+        let mut section = Some(0);
+
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                section = LEVEL_SECTIONS
+                    .iter()
+                    .position(|level| *level == name.trim());
+                if section.is_none() {
+                    log::error!("Safe Search Error: Ignoring the unknown section `{}`", line);
+                }
+                continue;
+            }
+            let Some(lists) = section.map(|idx| &mut classifier.levels[idx]) else {
+                continue;
+            };
+
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let kind = parts.next();
+            let weight = parts
+                .next()
+                .and_then(|weight| weight.parse::<f32>().ok())
+                .filter(|weight| weight.is_finite());
+
+            match (kind, weight, parts.next()) {
+                (Some("threshold"), Some(threshold), None) if threshold > 0.0 => {
+                    lists.threshold = threshold
+                }
+                (Some("word"), Some(weight), Some(pattern)) => {
+                    let words = words_of(pattern);
+                    match words.len() {
+                        0 => log::error!("Safe Search Error: Ignoring malformed line `{}`", line),
+                        1 => {
+                            lists.words.insert(words[0].clone(), weight);
+                        }
+                        _ => lists.phrases.push((words.join(" "), weight)),
+                    }
+                }
+                (Some("domain"), Some(weight), Some(pattern)) => lists
+                    .domains
+                    .push((pattern.trim().trim_end_matches('.').to_lowercase(), weight)),
+                _ => log::error!("Safe Search Error: Ignoring malformed line `{}`", line),
+            }
+        }
+
+        classifier
+    }
+
+    /// Reads and parses the safe search file present at the given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - It takes the path of the safe search file as an argument.
+    ///
+    /// # Error
+    ///
+    /// Returns an io error if the file cannot be read.
+    pub fn from_file(file_path: &str) -> Result<Self, std::io::Error> {
+        Ok(Self::parse(fs::read_to_string(file_path)?.lines()))
+    }
+
+    /// Checks whether the classifier contains no words, phrases or domains for any level.
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(WeightedLists::is_empty)
+    }
+
+    /// Returns the hash of the lists and thresholds of every level, which only depends on their
+    /// contents, so that it stays the same across restarts and between the servers sharing a
+    /// cache.
+    pub fn content_hash(&self) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        for lists in &self.levels {
+            // The words are sorted as the iteration order of the map changes between runs.
+            let mut words: Vec<(&String, &f32)> = lists.words.iter().collect();
+            words.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            let contents = (words, &lists.phrases, &lists.domains, lists.threshold);
+            hasher.update(format!("{contents:?}").as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    /// Scores the search result for the given safe search level by adding up its scores against
+    /// the lists of the level and of the lower levels.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - It takes the search result to be scored as an argument.
+    /// * `safe_search_level` - It takes the safe search level of the current search as an
+    /// argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns `0` for the `none` safe search level, for which no classification occurs.
+    pub fn score(&self, result: &SearchResult, safe_search_level: u8) -> f32 {
+        let Some(lists) = self.lists_for_level(safe_search_level) else {
+            return 0.0;
+        };
+
+        let (title, description) = (words_of(&result.title), words_of(&result.description));
+        let host = host_of(&result.url);
+        lists
+            .iter()
+            .map(|lists| lists.score(&title, &description, host.as_deref()))
+            .sum()
+    }
+
+    /// Classifies the search results for the given safe search level, blurring or removing the
+    /// results whose score reaches the threshold of the level.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - It takes a mutable reference to the search results to be classified as an
+    /// argument.
+    /// * `safe_search_level` - It takes the safe search level of the current search as an
+    /// argument.
+    pub fn classify(&self, results: &mut Vec<(String, SearchResult)>, safe_search_level: u8) {
+        let (action, lists) = match (
+            ClassifierAction::for_level(safe_search_level),
+            self.lists_for_level(safe_search_level),
+        ) {
+            (Some(action), Some(lists)) if !lists.iter().all(WeightedLists::is_empty) => {
+                (action, lists)
+            }
+            _ => return,
+        };
+        let threshold = lists
+            .last()
+            .map_or(DEFAULT_UNSAFE_SCORE_THRESHOLD, |lists| lists.threshold);
+
+        results.retain_mut(|(url, result)| {
+            if self.score(result, safe_search_level) < threshold {
+                return true;
+            }
+
+            log::debug!("Classified {} as unsafe ({:?})", url, action);
+            match action {
+                ClassifierAction::Blur => {
+                    result.blurred = true;
+                    true
+                }
+                ClassifierAction::Remove => false,
+            }
+        });
+    }
+
+    /// A helper function which returns the lists applying to the given safe search level, which
+    /// are the lists of the level and of the lower levels, the levels above `moderate` using the
+    /// lists of the `moderate` level.
+    ///
+    /// # Arguments
+    ///
+    /// * `safe_search_level` - It takes the safe search level of the current search as an
+    /// argument.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` for the `none` safe search level.
+    fn lists_for_level(&self, safe_search_level: u8) -> Option<&[WeightedLists]> {
+        let count = usize::from(safe_search_level).min(self.levels.len());
+        (count > 0).then(|| &self.levels[..count])
+    }
+}
+
+/// Splits the text into lowercased words made of alphanumeric characters, which is how the
+/// titles, descriptions and search queries are matched against the safe search lists.
+///
+/// # Arguments
+///
+/// * `text` - It takes the text to be split as an argument.
+pub fn words_of(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classifier() -> SafeSearchClassifier {
+        SafeSearchClassifier::parse([
+            "# comment",
+            "word 1.0 explicit",
+            "word 0.5 suggestive",
+            "word 0.5 risky",
+            "word 1.0 adult video",
+            "domain 1.0 *.unsafe.example",
+            "malformed",
+            "threshold 0.5",
+            "[moderate]",
+            "word 1.0 gambling",
+            "[unknown]",
+            "word 1.0 rust",
+        ])
+    }
+
+    fn results() -> Vec<(String, SearchResult)> {
+        [
+            (
+                "https://safe.example/",
+                "Rust",
+                "A systems programming language",
+            ),
+            ("https://a.example/", "Explicit content", ""),
+            (
+                "https://b.example/",
+                "Suggestive",
+                "suggestive and suggestive",
+            ),
+            ("https://c.example/", "Suggestive", "and risky"),
+            ("https://d.example/", "Adult Video", ""),
+            ("https://www.unsafe.example/", "Harmless title", ""),
+            ("https://e.example/", "Gambling", "suggestive"),
+        ]
+        .into_iter()
+        .map(|(url, title, description)| {
+            (
+                url.to_owned(),
+                SearchResult::new(title, url, description, &["bing"]),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_scores_per_level() {
+        let classifier = classifier();
+        let scores = |level| -> Vec<f32> {
+            results()
+                .iter()
+                .map(|(_, result)| classifier.score(result, level))
+                .collect()
+        };
+
+        assert_eq!(scores(0), [0.0; 7]);
+        // A repeated word only counts once.
+        assert_eq!(scores(1), [0.0, 1.0, 0.5, 1.0, 1.0, 1.0, 0.5]);
+        // The lists of the moderate level add up with the lists of the low level.
+        assert_eq!(scores(2), [0.0, 1.0, 0.5, 1.0, 1.0, 1.0, 1.5]);
+        assert_eq!(scores(4), scores(2));
+    }
+
+    #[test]
+    fn test_classification_per_level() {
+        let classifier = classifier();
+
+        let mut unfiltered = results();
+        classifier.classify(&mut unfiltered, 0);
+        assert_eq!(unfiltered.len(), 7);
+        assert!(unfiltered.iter().all(|(_, result)| !result.blurred));
+
+        let mut blurred = results();
+        classifier.classify(&mut blurred, 1);
+        let blurred: Vec<bool> = blurred.iter().map(|(_, result)| result.blurred).collect();
+        assert_eq!(blurred, [false, true, true, true, true, true, true]);
+
+        // The moderate level keeps the default threshold of 1.
+        let mut removed = results();
+        classifier.classify(&mut removed, 2);
+        let urls: Vec<&str> = removed.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, ["https://safe.example/", "https://b.example/"]);
+    }
+
+    #[test]
+    fn test_empty_levels_are_skipped() {
+        let classifier = SafeSearchClassifier::parse(["[moderate]", "word 1.0 explicit"]);
+        assert!(!classifier.is_empty());
+
+        let mut results = results();
+        classifier.classify(&mut results, 1);
+        assert!(results.iter().all(|(_, result)| !result.blurred));
+
+        classifier.classify(&mut results, 2);
+        assert_eq!(results.len(), 6);
+    }
+
+    #[test]
+    fn test_content_hash_only_depends_on_the_lists() {
+        assert_eq!(classifier().content_hash(), classifier().content_hash());
+
+        let mut changed = classifier();
+        changed.levels[1].threshold = 2.0;
+        assert_ne!(classifier().content_hash(), changed.content_hash());
+    }
+}
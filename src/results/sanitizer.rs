@@ -0,0 +1,118 @@
+//! This module provides the functionality to sanitize the html snippets (titles and descriptions)
+//! provided by the upstream search engines by converting them to plain text, which is escaped by
+//! the search template, so that no upstream markup reaches the users' pages.
+
+use scraper::{ElementRef, Html};
+
+/// The tags which are removed together with all of their content when sanitizing a snippet.
+const DROPPED_TAGS: [&str; 12] = [
+    "script", "style", "noscript", "template", "iframe", "object", "embed", "textarea", "title",
+    "head", "svg", "math",
+];
+
+/// The url schemes which are allowed for the result urls.
+const ALLOWED_URL_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+/// Converts the given html snippet to plain text by unwrapping every element and dropping
+/// dangerous elements along with their content. The entities are decoded by the html parser, so
+/// the returned text must be escaped before being rendered.
+///
+/// # Arguments
+///
+/// * `html` - It takes the html snippet provided by an upstream search engine as an argument.
+///
+/// # Returns
+///
+/// Returns the decoded plain text of the snippet.
+pub fn html_to_text(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut output = String::with_capacity(html.len());
+    write_children(fragment.root_element(), &mut output);
+    output.trim().to_owned()
+}
+
+/// Checks whether the given result url uses a scheme which is safe to be placed in a link, so
+/// that `javascript:` and `data:` urls provided by an upstream engine never reach the users.
+///
+/// # Arguments
+///
+/// * `url` - It takes the result url as an argument.
+pub fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start().to_lowercase();
+    ALLOWED_URL_SCHEMES
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// A helper function which writes the text of the children of the given element to the output,
+/// unwrapping every element and dropping the dangerous ones along with their content.
+///
+/// # Arguments
+///
+/// * `element` - It takes the element whose children should be written as an argument.
+/// * `output` - It takes the string to which the plain text is written as an argument.
+fn write_children(element: ElementRef<'_>, output: &mut String) {
+    for child in element.children() {
+        if let Some(text) = child.value().as_text() {
+            output.push_str(text);
+        } else if let Some(child_element) = ElementRef::wrap(child) {
+            if !DROPPED_TAGS.contains(&child_element.value().name()) {
+                write_children(child_element, output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangerous_elements_are_removed_with_their_content() {
+        assert_eq!(html_to_text("safe<script>alert(1)</script>"), "safe");
+        assert_eq!(
+            html_to_text("<style>body{display:none}</style>text"),
+            "text"
+        );
+        assert_eq!(
+            html_to_text("<svg><script>alert(1)</script></svg>after"),
+            "after"
+        );
+        assert_eq!(
+            html_to_text(r#"<img src=x onerror="alert(1)">caption"#),
+            "caption"
+        );
+        assert_eq!(
+            html_to_text(r#"<div onmouseover="alert(1)"><span>nested</span></div>"#),
+            "nested"
+        );
+    }
+
+    #[test]
+    fn test_entities_are_decoded() {
+        assert_eq!(html_to_text("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(html_to_text("caf&eacute; &#x2014; &#169;"), "café — ©");
+        assert_eq!(html_to_text("non&nbsp;breaking"), "non\u{a0}breaking");
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<strong>Rust</strong> &amp; <b>Cargo</b><script>alert(1)</script>"),
+            "Rust & Cargo"
+        );
+        assert_eq!(
+            html_to_text("&lt;script&gt;alert(1)&lt;/script&gt;"),
+            "<script>alert(1)</script>"
+        );
+    }
+
+    #[test]
+    fn test_unsafe_urls() {
+        assert!(is_safe_url("https://www.rust-lang.org/"));
+        assert!(is_safe_url("http://example.com"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url(" JavaScript:alert(1)"));
+        assert!(!is_safe_url("data:text/html,<script>alert(1)</script>"));
+    }
+}
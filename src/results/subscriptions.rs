@@ -0,0 +1,227 @@
+//! This module provides the functionality to download the remote filter lists (written in the
+//! uBlacklist format) which the server administrator subscribed to in the config file. The lists
+//! are downloaded into the `subscriptions` folder next to the config file and are kept up to date
+//! in the background, from where they are picked up by the filter engine.
+
+use std::{
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{Client, ClientBuilder};
+
+use crate::{
+    config::{parser::Config, reload::SharedConfig},
+    handler::{file_path, FileType},
+};
+
+/// The constant holding the name of the folder in which the subscription lists are downloaded.
+const SUBSCRIPTIONS_DIRECTORY_NAME: &str = "subscriptions";
+
+/// The maximum size (in bytes) of a subscription list, above which the download is aborted so
+/// that a misbehaving server cannot fill the memory or the disk.
+const MAX_SUBSCRIPTION_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The time between two checks of whether any of the subscription lists should be downloaded
+/// again. A failed download is retried on the next check.
+const SUBSCRIPTION_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// A function which returns the path of the folder in which the subscription lists are
+/// downloaded, which is the `subscriptions` folder next to the config file.
+///
+/// # Error
+///
+/// Returns an error if the config file cannot be found.
+pub fn subscriptions_directory() -> Result<PathBuf, Error> {
+    Path::new(file_path(FileType::Config)?)
+        .parent()
+        .map(|directory| directory.join(SUBSCRIPTIONS_DIRECTORY_NAME))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Config folder not found!!"))
+}
+
+/// A function which returns the paths to which the given subscription lists are downloaded.
+///
+/// # Arguments
+///
+/// * `urls` - It takes the urls of the subscription lists from the config file as an argument.
+///
+/// # Error
+///
+/// Returns an error if the config file cannot be found.
+pub fn subscription_paths(urls: &[String]) -> Result<Vec<PathBuf>, Error> {
+    if urls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let directory = subscriptions_directory()?;
+    Ok(urls
+        .iter()
+        .map(|url| directory.join(subscription_file_name(url)))
+        .collect())
+}
+
+/// A helper function which derives a readable file name for the subscription list from its url,
+/// for example `https://example.com/list.txt` is saved as `example_com_list_txt.txt`.
+///
+/// # Arguments
+///
+/// * `url` - It takes the url of the subscription list as an argument.
+fn subscription_file_name(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name: String = without_scheme
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+    format!("{}.txt", name.trim_matches('_'))
+}
+
+/// Keeps the subscription lists from the config file up to date by downloading every list whose
+/// downloaded copy is missing or older than the configured update interval. The lists are checked
+/// again right away when the config is reloaded, so that the newly subscribed lists are
+/// downloaded. This function runs until the server is stopped and it is meant to be spawned as a
+/// background task on startup.
+///
+/// # Arguments
+///
+/// * `shared_config` - It takes the shared config holding the current config as an argument.
+pub async fn update_subscriptions(shared_config: &'static SharedConfig) {
+    let client = match ClientBuilder::new()
+        .timeout(Duration::from_secs(
+            shared_config.load().request_timeout as u64,
+        ))
+        .gzip(true)
+        .brotli(true)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            log::error!("Filter List Error: Failed to build the http client: {error}");
+            return;
+        }
+    };
+
+    // The reloads happening while the lists are downloaded are seen by the next wait.
+    let mut reloaded = shared_config.subscribe();
+    loop {
+        update_subscription_lists(&client, &shared_config.load()).await;
+        tokio::select! {
+            _ = tokio::time::sleep(SUBSCRIPTION_CHECK_INTERVAL) => (),
+            _ = reloaded.changed() => (),
+        }
+    }
+}
+
+/// A helper function which downloads every subscription list from the config whose downloaded
+/// copy is missing or older than the configured update interval.
+///
+/// # Arguments
+///
+/// * `client` - It takes the http client used to download the lists as an argument.
+/// * `config` - It takes the parsed config as an argument.
+async fn update_subscription_lists(client: &Client, config: &Config) {
+    if config.filter_subscriptions.is_empty() {
+        return;
+    }
+
+    let paths = match subscription_paths(&config.filter_subscriptions).and_then(|paths| {
+        std::fs::create_dir_all(subscriptions_directory()?)?;
+        Ok(paths)
+    }) {
+        Ok(paths) => paths,
+        Err(error) => {
+            log::error!("Filter List Error: Failed to prepare the subscriptions folder: {error}");
+            return;
+        }
+    };
+    let update_interval =
+        Duration::from_secs(config.filter_subscription_update_interval as u64 * 60 * 60);
+
+    for (url, path) in config.filter_subscriptions.iter().zip(&paths) {
+        if is_up_to_date(path, update_interval) {
+            continue;
+        }
+        match download_subscription(client, url, path).await {
+            Ok(()) => log::info!("Downloaded the subscription list {url}"),
+            Err(error) => {
+                log::error!("Filter List Error: Failed to download {url}: {error}")
+            }
+        }
+    }
+}
+
+/// A helper function which checks whether the downloaded copy of a subscription list exists and
+/// is younger than the update interval.
+///
+/// # Arguments
+///
+/// * `path` - It takes the path of the downloaded subscription list as an argument.
+/// * `update_interval` - It takes the time after which the list should be downloaded again as an
+/// argument.
+fn is_up_to_date(path: &Path, update_interval: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < update_interval)
+}
+
+/// A helper function which downloads a subscription list to the given path. The list is read
+/// chunk by chunk and the download is aborted once it grows above the maximum size. It is then
+/// written to a temporary file which replaces the previous copy, so that the filter engine never
+/// reads a partially written list.
+///
+/// # Arguments
+///
+/// * `client` - It takes the http client used to download the list as an argument.
+/// * `url` - It takes the url of the subscription list as an argument.
+/// * `path` - It takes the path to which the list should be saved as an argument.
+///
+/// # Error
+///
+/// Returns an error if the list cannot be downloaded or saved, or if it is larger than the
+/// maximum size.
+async fn download_subscription(
+    client: &Client,
+    url: &str,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let too_large = || format!("The list is larger than {MAX_SUBSCRIPTION_SIZE} bytes");
+
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_SUBSCRIPTION_SIZE)
+    {
+        return Err(too_large().into());
+    }
+
+    let mut contents = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (contents.len() + chunk.len()) as u64 > MAX_SUBSCRIPTION_SIZE {
+            return Err(too_large().into());
+        }
+        contents.extend_from_slice(&chunk);
+    }
+
+    let temporary_path = path.with_extension("txt.part");
+    tokio::fs::write(&temporary_path, contents).await?;
+    tokio::fs::rename(&temporary_path, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_file_name() {
+        assert_eq!(
+            subscription_file_name("https://raw.example.com/lists/uBlacklist.txt"),
+            "raw_example_com_lists_ublacklist_txt.txt"
+        );
+    }
+}
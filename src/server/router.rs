@@ -3,7 +3,7 @@
 //! when requested.
 
 use crate::{
-    config::parser::Config,
+    config::reload::SharedConfig,
     handler::{file_path, FileType},
 };
 use actix_web::{get, http::header::ContentType, web, HttpRequest, HttpResponse};
@@ -12,8 +12,9 @@ use tokio::fs::read_to_string;
 /// Handles the route of index page or main page of the `websurfx` meta search engine website.
 #[get("/")]
 pub async fn index(
-    config: web::Data<&'static Config>,
+    config: web::Data<&'static SharedConfig>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
         crate::templates::views::index::index(
             &config.style.colorscheme,
@@ -27,8 +28,9 @@ pub async fn index(
 /// Handles the route of any other accessed route/page which is not provided by the
 /// website essentially the 404 error page.
 pub async fn not_found(
-    config: web::Data<&'static Config>,
+    config: web::Data<&'static SharedConfig>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
         crate::templates::views::not_found::not_found(
             &config.style.colorscheme,
@@ -52,8 +54,9 @@ pub async fn robots_data(_req: HttpRequest) -> Result<HttpResponse, Box<dyn std:
 /// Handles the route of about page of the `websurfx` meta search engine website.
 #[get("/about")]
 pub async fn about(
-    config: web::Data<&'static Config>,
+    config: web::Data<&'static SharedConfig>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
         crate::templates::views::about::about(
             &config.style.colorscheme,
@@ -67,8 +70,9 @@ pub async fn about(
 /// Handles the route of settings page of the `websurfx` meta search engine website.
 #[get("/settings")]
 pub async fn settings(
-    config: web::Data<&'static Config>,
+    config: web::Data<&'static SharedConfig>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
         crate::templates::views::settings::settings(
             config.safe_search,
@@ -76,6 +80,7 @@ pub async fn settings(
             &config.style.theme,
             &config.style.animation,
             &config.upstream_search_engines,
+            &config.hostname_replacements,
         )?
         .0,
     ))
@@ -0,0 +1,171 @@
+//! This module handles the admin routes of the search engine website, which inspect and purge
+//! the cache. The routes are only served when an admin token is configured and every request
+//! must carry that token as a bearer token.
+
+use crate::{
+    cache::{cacher::SharedCache, error::CacheError},
+    config::{parser::Config, reload::SharedConfig},
+    models::server_models::InvalidateParams,
+};
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse};
+use error_stack::Report;
+use serde_json::json;
+
+/// Handles the route which returns the statistics of every tier of the cache along with the
+/// prefetch counters.
+///
+/// # Example
+///
+/// ```bash
+/// curl -H "Authorization: Bearer <admin token>" "http://127.0.0.1:8080/admin/cache/stats"
+/// ```
+#[get("/admin/cache/stats")]
+pub async fn cache_stats(
+    req: HttpRequest,
+    config: web::Data<&'static SharedConfig>,
+    cache: web::Data<&'static SharedCache>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
+    if let Err(response) = authorize(&req, &config) {
+        return Ok(response);
+    }
+
+    Ok(match cache.stats().await {
+        Ok(backends) => HttpResponse::Ok().json(json!({
+            "backends": backends,
+            "prefetch": {
+                "prefetched": cache.prefetches().prefetched(),
+                "used": cache.prefetches().used(),
+            },
+        })),
+        Err(error) => cache_error(error),
+    })
+}
+
+/// Handles the route which removes all the cached search results.
+///
+/// # Example
+///
+/// ```bash
+/// curl -X POST -H "Authorization: Bearer <admin token>" "http://127.0.0.1:8080/admin/cache/clear"
+/// ```
+#[post("/admin/cache/clear")]
+pub async fn cache_clear(
+    req: HttpRequest,
+    config: web::Data<&'static SharedConfig>,
+    cache: web::Data<&'static SharedCache>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
+    if let Err(response) = authorize(&req, &config) {
+        return Ok(response);
+    }
+
+    Ok(match cache.clear().await {
+        Ok(deleted) => HttpResponse::Ok().json(json!({ "deleted": deleted })),
+        Err(error) => cache_error(error),
+    })
+}
+
+/// Handles the route which removes the cached search results whose query starts with the
+/// prefix given by the `prefix` url parameter.
+///
+/// # Example
+///
+/// ```bash
+/// curl -X POST -H "Authorization: Bearer <admin token>" "http://127.0.0.1:8080/admin/cache/invalidate?prefix=rust"
+/// ```
+#[post("/admin/cache/invalidate")]
+pub async fn cache_invalidate(
+    req: HttpRequest,
+    config: web::Data<&'static SharedConfig>,
+    cache: web::Data<&'static SharedCache>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let config = config.load();
+    if let Err(response) = authorize(&req, &config) {
+        return Ok(response);
+    }
+
+    let params = web::Query::<InvalidateParams>::from_query(req.query_string())?;
+    let prefix = match &params.prefix {
+        Some(prefix) if !prefix.trim().is_empty() => prefix,
+        // An empty prefix would match every entry, which is what the clear route is for.
+        _ => {
+            return Ok(HttpResponse::BadRequest()
+                .json(json!({ "error": "the `prefix` parameter should not be empty" })))
+        }
+    };
+
+    Ok(match cache.delete(prefix).await {
+        Ok(deleted) => HttpResponse::Ok().json(json!({ "deleted": deleted })),
+        Err(error) => cache_error(error),
+    })
+}
+
+/// A helper function which checks that the request carries the admin token.
+///
+/// # Arguments
+///
+/// * `req` - It takes the request to the admin route as an argument.
+/// * `config` - It takes the parsed config as an argument.
+///
+/// # Error
+///
+/// Returns a not found response when no admin token is configured, as the admin routes are
+/// disabled, or an unauthorized response when the request does not carry the admin token.
+fn authorize(req: &HttpRequest, config: &Config) -> Result<(), HttpResponse> {
+    let Some(admin_token) = &config.admin_token else {
+        return Err(HttpResponse::NotFound().finish());
+    };
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token.trim(), admin_token) => Ok(()),
+        _ => Err(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish()),
+    }
+}
+
+/// A helper function which compares the given token with the admin token in a time which does
+/// not depend on where they differ, so that the admin token cannot be guessed byte by byte.
+///
+/// # Arguments
+///
+/// * `token` - It takes the token carried by the request as an argument.
+/// * `admin_token` - It takes the configured admin token as an argument.
+fn tokens_match(token: &str, admin_token: &str) -> bool {
+    token.len() == admin_token.len()
+        && token
+            .bytes()
+            .zip(admin_token.bytes())
+            .fold(0, |difference, (left, right)| difference | (left ^ right))
+            == 0
+}
+
+/// A helper function which logs the cache error and turns it into an internal server error
+/// response.
+///
+/// # Arguments
+///
+/// * `error` - It takes the error returned by the cache as an argument.
+fn cache_error(error: Report<CacheError>) -> HttpResponse {
+    log::error!("The cache administration failed: {:?}", error);
+    HttpResponse::InternalServerError().json(json!({ "error": error.to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens_match;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret2", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+}
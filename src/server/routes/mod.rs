@@ -1,3 +1,4 @@
 //! This module provides modules to handle various routes in the search engine website.
 
+pub mod admin;
 pub mod search;
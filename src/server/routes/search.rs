@@ -1,24 +1,29 @@
 //! This module handles the search route of the search engine website.
 
 use crate::{
-    cache::cacher::SharedCache,
-    config::parser::Config,
-    handler::{file_path, FileType},
+    cache::{cache_key::CacheKey, cacher::SharedCache},
+    config::{parser::Config, reload::SharedConfig},
     models::{
         aggregation_models::SearchResults,
         engine_models::EngineHandler,
+        parser_models::PrefetchPolicy,
         server_models::{self, SearchParams},
     },
-    results::aggregator::aggregate,
+    results::{
+        aggregator::{aggregate, EngineQuery, UserPreferences},
+        blocked_sites::BlockedSites,
+        domain_rules::DomainRules,
+        filter_engine::FilterEngine,
+        hostname_rewriter::HostnameReplacements,
+    },
 };
-use actix_web::{get, http::header::ContentType, web, HttpRequest, HttpResponse};
-use regex::Regex;
-use std::borrow::Cow;
-use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, BufReader},
-    join,
+use actix_web::{
+    get,
+    http::header::{self, ContentType},
+    web, HttpRequest, HttpResponse,
 };
+use std::{borrow::Cow, sync::Arc};
+use tokio::join;
 
 /// Handles the route of search page of the `websurfx` meta search engine website and it takes
 /// two search url parameters `q` and `page` where `page` parameter is optional.
@@ -37,10 +42,16 @@ use tokio::{
 #[get("/search")]
 pub async fn search(
     req: HttpRequest,
-    config: web::Data<&'static Config>,
+    config: web::Data<&'static SharedConfig>,
     cache: web::Data<&'static SharedCache>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    use std::sync::Arc;
+    // The config and the filter engine are loaded together so that they stay consistent.
+    let snapshot = config.snapshot();
+    let (config, filter_engine, results_hash) = (
+        Arc::clone(&snapshot.config),
+        Arc::clone(&snapshot.filter_engine),
+        snapshot.results_hash,
+    );
     let params = web::Query::<SearchParams>::from_query(req.query_string())?;
     match &params.q {
         Some(query) => {
@@ -75,66 +86,93 @@ pub async fn search(
                 config.safe_search,
             );
 
-            // Closure wrapping the results function capturing local references
-            let get_results = |page| results(&config, &cache, query, page, &search_settings);
+            // Closure building the search job of a page from the local references
+            let search_job = |page| {
+                SearchJob::new(
+                    &config,
+                    &filter_engine,
+                    results_hash,
+                    query,
+                    page,
+                    &search_settings,
+                )
+            };
 
             // .max(1) makes sure that the page >= 0.
             let page = params.page.unwrap_or(1).max(1) - 1;
-            let previous_page = page.saturating_sub(1);
-            let next_page = page + 1;
-
-            let mut results = Arc::new((SearchResults::default(), String::default()));
-            if page != previous_page {
-                let (previous_results, current_results, next_results) = join!(
-                    get_results(previous_page),
-                    get_results(page),
-                    get_results(next_page)
-                );
-                let (parsed_previous_results, parsed_next_results) =
-                    (previous_results?, next_results?);
-
-                let (cache_keys, results_list) = (
-                    [
-                        parsed_previous_results.1,
-                        results.1.clone(),
-                        parsed_next_results.1,
-                    ],
-                    [
-                        parsed_previous_results.0,
-                        results.0.clone(),
-                        parsed_next_results.0,
-                    ],
-                );
-
-                results = Arc::new(current_results?);
+            let current_job = search_job(page);
+            let current_key = current_job.cache_key.clone();
+            let current_results = results(&config, &cache, &filter_engine, current_job);
 
-                tokio::spawn(async move { cache.cache_results(&results_list, &cache_keys).await });
-            } else {
-                let (current_results, next_results) =
-                    join!(get_results(page), get_results(page + 1));
-
-                let parsed_next_results = next_results?;
-
-                results = Arc::new(current_results?);
-
-                let (cache_keys, results_list) = (
-                    [results.1.clone(), parsed_next_results.1.clone()],
-                    [results.0.clone(), parsed_next_results.0],
-                );
-
-                tokio::spawn(async move { cache.cache_results(&results_list, &cache_keys).await });
+            let results = match config.prefetch_pages {
+                PrefetchPolicy::Off => current_results.await?,
+                PrefetchPolicy::NextPage => {
+                    let (current_results, _) = join!(
+                        current_results,
+                        prefetch(&config, &cache, &filter_engine, search_job(page + 1))
+                    );
+                    current_results?
+                }
+                PrefetchPolicy::Background => {
+                    let current_results = current_results.await?;
+                    let neighbour_jobs: Vec<SearchJob> = page
+                        .checked_sub(1)
+                        .into_iter()
+                        .chain([page + 1])
+                        .map(search_job)
+                        .collect();
+                    let (config, cache, filter_engine) =
+                        (Arc::clone(&config), **cache, Arc::clone(&filter_engine));
+                    actix_web::rt::spawn(async move {
+                        for neighbour_job in neighbour_jobs {
+                            prefetch(&config, cache, &filter_engine, neighbour_job).await;
+                        }
+                    });
+                    current_results
+                }
+            };
+            if results.cached_at().is_some() {
+                cache.prefetches().record_view(&current_key);
             }
 
-            Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+            // Only the pages of the cached results are cached, as the fresh results only get the
+            // cache time which their page key depends on once they are read back from the cache.
+            let page_key = results
+                .cached_at()
+                .map(|cached_at| current_key.page_hash(cached_at, &config.style, query));
+            let page = cache.rendered_page(page_key, || {
                 crate::templates::views::search::search(
                     &config.style.colorscheme,
                     &config.style.theme,
                     &config.style.animation,
                     query,
-                    &results.0,
+                    &results,
                 )
-                .0,
-            ))
+                .0
+            });
+
+            let not_modified = req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|if_none_match| if_none_match.to_str().ok())
+                .is_some_and(|if_none_match| page.matches(if_none_match));
+            let mut response = if not_modified {
+                HttpResponse::NotModified()
+            } else {
+                HttpResponse::Ok()
+            };
+            response
+                .insert_header((header::ETAG, page.etag.clone()))
+                // The browsers must check with the server before reusing the page, as the
+                // search results change once they expire from the cache.
+                .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+                .insert_header((header::VARY, "Cookie"));
+            if not_modified {
+                return Ok(response.finish());
+            }
+            Ok(response
+                .content_type(ContentType::html())
+                .body(page.html.clone()))
         }
         None => Ok(HttpResponse::TemporaryRedirect()
             .insert_header(("location", "/"))
@@ -143,129 +181,266 @@ pub async fn search(
 }
 
 /// Fetches the results for a query and page. It First checks the redis cache, if that
-/// fails it gets proper results by requesting from the upstream search engines.
+/// fails it gets proper results by requesting from the upstream search engines. The stale
+/// results found in the cache are served right away and refreshed in the background.
 ///
 /// # Arguments
 ///
-/// * `url` - It takes the url of the current page that requested the search results for a
-/// particular search query.
 /// * `config` - It takes a parsed config struct.
-/// * `query` - It takes the page number as u32 value.
-/// * `req` - It takes the `HttpRequest` struct as a value.
+/// * `cache` - It takes the shared cache storing the aggregated search results.
+/// * `filter_engine` - It takes the shared filter engine holding the compiled filter lists.
+/// * `search_job` - It takes the search job of the page as an argument.
 ///
 /// # Error
 ///
-/// It returns the `SearchResults` struct if the search results could be successfully fetched from
-/// the cache or from the upstream search engines otherwise it returns an appropriate error.
+/// It returns the `SearchResults` struct if the search results could be successfully fetched
+/// from the cache or from the upstream search engines otherwise it returns an appropriate error.
 async fn results(
-    config: &'static Config,
+    config: &Arc<Config>,
     cache: &'static SharedCache,
-    query: &str,
-    page: u32,
-    search_settings: &server_models::Cookie<'_>,
-) -> Result<(SearchResults, String), Box<dyn std::error::Error>> {
-    // eagerly parse cookie value to evaluate safe search level
-    let safe_search_level = search_settings.safe_search_level;
-
-    let cache_key = format!(
-        "http://{}:{}/search?q={}&page={}&safesearch={}&engines={}",
-        config.binding_ip,
-        config.port,
-        query,
-        page,
-        safe_search_level,
-        search_settings.engines.join(",")
-    );
+    filter_engine: &Arc<FilterEngine>,
+    search_job: SearchJob,
+) -> Result<SearchResults, Box<dyn std::error::Error>> {
+    let (cache_key, safe_search_level) =
+        (search_job.cache_key.clone(), search_job.safe_search_level);
 
     // fetch the cached results json.
     let cached_results = cache.cached_results(&cache_key).await;
     // check if fetched cache results was indeed fetched or it was an error and if so
     // handle the data accordingly.
     match cached_results {
-        Ok(results) => Ok((results, cache_key)),
-        Err(_) => {
-            if safe_search_level == 4 {
-                let mut results: SearchResults = SearchResults::default();
-
-                let flag: bool =
-                    !is_match_from_filter_list(file_path(FileType::BlockList)?, query).await?;
-                // Return early when query contains disallowed words,
-                if flag {
-                    results.set_disallowed();
-                    cache
-                        .cache_results(&[results.clone()], &[cache_key.clone()])
-                        .await?;
-                    results.set_safe_search_level(safe_search_level);
-                    return Ok((results, cache_key));
+        Ok(results) => {
+            if cache.is_stale(&results) {
+                // Only one refresh runs per key, the other requests keep serving the stale results.
+                if let Some(refresh) = cache.start_refresh(&cache_key) {
+                    let (config, filter_engine) = (Arc::clone(config), Arc::clone(filter_engine));
+                    actix_web::rt::spawn(async move {
+                        let _refresh = refresh;
+                        if let Err(error) = search_job.run(&config, cache, &filter_engine).await {
+                            log::error!("Failed to refresh the stale search results: {error}");
+                        }
+                    });
                 }
             }
-
-            // check if the cookie value is empty or not if it is empty then use the
-            // default selected upstream search engines from the config file otherwise
-            // parse the non-empty cookie and grab the user selected engines from the
-            // UI and use that.
-            let mut results: SearchResults = match search_settings.engines.is_empty() {
-                false => {
-                    aggregate(
-                        query,
-                        page,
-                        config,
-                        &search_settings
-                            .engines
-                            .iter()
-                            .filter_map(|engine| EngineHandler::new(engine).ok())
-                            .collect::<Vec<EngineHandler>>(),
-                        safe_search_level,
-                    )
-                    .await?
-                }
-                true => {
-                    let mut search_results = SearchResults::default();
-                    search_results.set_no_engines_selected();
-                    search_results
-                }
-            };
-            let (engine_errors_info, results_empty_check, no_engines_selected) = (
-                results.engine_errors_info().is_empty(),
-                results.results().is_empty(),
-                results.no_engines_selected(),
-            );
-            results.set_filtered(engine_errors_info & results_empty_check & !no_engines_selected);
-            cache
-                .cache_results(&[results.clone()], &[cache_key.clone()])
+            Ok(results)
+        }
+        Err(_) => {
+            // The identical searches running at the same time share a single aggregation.
+            let mut results = cache
+                .coalesce(&cache_key, || search_job.run(config, cache, filter_engine))
                 .await?;
             results.set_safe_search_level(safe_search_level);
-            Ok((results, cache_key))
+            Ok(results)
         }
     }
 }
 
-/// A helper function which checks whether the search query contains any keywords which should be
-/// disallowed/allowed based on the regex based rules present in the blocklist and allowlist files.
+/// Prefetches the results for a query and page, so that they are served from the cache when the
+/// user moves to the page. The pages which were not cached yet are recorded as prefetched.
 ///
 /// # Arguments
 ///
-/// * `file_path` - It takes the file path of the list as the argument.
-/// * `query` - It takes the search query to be checked against the list as an argument.
-///
-/// # Error
-///
-/// Returns a bool indicating whether the results were found in the list or not on success
-/// otherwise returns a standard error type on a failure.
-async fn is_match_from_filter_list(
-    file_path: &str,
-    query: &str,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    let reader = BufReader::new(File::open(file_path).await?);
-    let mut lines = reader.lines();
-    while let Some(line) = lines.next_line().await? {
-        let re = Regex::new(&line)?;
-        if re.is_match(query) {
-            return Ok(true);
+/// * `config` - It takes a parsed config struct.
+/// * `cache` - It takes the shared cache storing the aggregated search results.
+/// * `filter_engine` - It takes the shared filter engine holding the compiled filter lists.
+/// * `search_job` - It takes the search job of the prefetched page as an argument.
+async fn prefetch(
+    config: &Arc<Config>,
+    cache: &'static SharedCache,
+    filter_engine: &Arc<FilterEngine>,
+    search_job: SearchJob,
+) {
+    let cache_key = search_job.cache_key.clone();
+    match results(config, cache, filter_engine, search_job).await {
+        // The search results fetched from the upstream search engines carry no cache time.
+        Ok(results) if results.cached_at().is_none() => {
+            cache.prefetches().record_prefetch(&cache_key)
+        }
+        Ok(_) => (),
+        Err(error) => log::debug!("Failed to prefetch the search results: {error}"),
+    }
+}
+
+/// A named struct which stores everything needed to fetch the search results of a page from the
+/// upstream search engines, so that they can also be refreshed in the background.
+struct SearchJob {
+    /// It stores the user's search query.
+    query: String,
+    /// It stores the page number (starting from 0) of the search results.
+    page: u32,
+    /// It stores the safe search level of the search.
+    safe_search_level: u8,
+    /// It stores the selected upstream search engines.
+    engines: Vec<EngineHandler>,
+    /// It stores whether the user did not select any upstream search engine.
+    no_engines_selected: bool,
+    /// It stores the preferences provided by the user which are applied to the search results.
+    user_preferences: UserPreferences,
+    /// It stores the key under which the search results are cached.
+    cache_key: CacheKey,
+}
+
+impl SearchJob {
+    /// Constructs a new `SearchJob` for a query and page from the user's search settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes a parsed config struct.
+    /// * `filter_engine` - It takes the shared filter engine holding the compiled filter lists.
+    /// * `results_hash` - It takes the hash of the options of the config which affect the search
+    /// results as an argument.
+    /// * `query` - It takes the user's search query as an argument.
+    /// * `page` - It takes the page number (starting from 0) as an argument.
+    /// * `search_settings` - It takes the user's search settings as an argument.
+    fn new(
+        config: &Config,
+        filter_engine: &FilterEngine,
+        results_hash: blake3::Hash,
+        query: &str,
+        page: u32,
+        search_settings: &server_models::Cookie<'_>,
+    ) -> Self {
+        // eagerly parse cookie value to evaluate safe search level
+        let safe_search_level = search_settings.safe_search_level;
+
+        let blocked_sites = BlockedSites::new(
+            &search_settings.blocked_sites,
+            search_settings.compressed_blocked_sites.as_deref(),
+        );
+
+        let cache_key = CacheKey::new(
+            query,
+            page,
+            safe_search_level,
+            search_settings.engines.iter(),
+        )
+        .with_filter_list_hash(filter_engine.content_hash())
+        .with_config_hash(results_hash)
+        .with_user_preferences(
+            search_settings.domain_rules.iter(),
+            search_settings.disabled_hostname_replacements.iter(),
+            blocked_sites.patterns(),
+        );
+
+        SearchJob {
+            query: query.to_owned(),
+            page,
+            safe_search_level,
+            engines: search_settings
+                .engines
+                .iter()
+                .filter_map(|engine| EngineHandler::new(engine).ok())
+                .collect(),
+            no_engines_selected: search_settings.engines.is_empty(),
+            user_preferences: UserPreferences {
+                domain_rules: DomainRules::parse(
+                    search_settings
+                        .domain_rules
+                        .iter()
+                        .map(|rule| rule.as_ref()),
+                ),
+                hostname_replacements: HostnameReplacements::new(
+                    &config.hostname_replacements,
+                    &search_settings.disabled_hostname_replacements,
+                ),
+                blocked_sites,
+            },
+            cache_key,
         }
     }
 
-    Ok(false)
+    /// Fetches the search results from the upstream search engines and caches them.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - It takes a parsed config struct.
+    /// * `cache` - It takes the shared cache storing the aggregated search results.
+    /// * `filter_engine` - It takes the shared filter engine holding the compiled filter lists.
+    ///
+    /// # Error
+    ///
+    /// It returns the `SearchResults` struct if the search results could be successfully fetched
+    /// from the upstream search engines otherwise it returns an appropriate error.
+    async fn run(
+        &self,
+        config: &Config,
+        cache: &'static SharedCache,
+        filter_engine: &FilterEngine,
+    ) -> Result<SearchResults, Box<dyn std::error::Error>> {
+        let (query, safe_search_level) = (self.query.as_str(), self.safe_search_level);
+
+        // Return early when the query belongs to a category disallowed at this level.
+        if let Some(category) = config
+            .query_classifier
+            .blocked_category(query, safe_search_level)
+        {
+            log::debug!(
+                "Disallowed the query by the `{}` query category",
+                category.name()
+            );
+            let mut results: SearchResults = SearchResults::default();
+            results.set_disallowed_category(category.name());
+            cache
+                .cache_results(&[results.clone()], &[self.cache_key.clone()])
+                .await?;
+            return Ok(results);
+        }
+
+        if safe_search_level == 4 {
+            let mut results: SearchResults = SearchResults::default();
+
+            // Return early when query contains disallowed words,
+            if let Some(blocked) = filter_engine.blocklist().first_match(&query.to_lowercase()) {
+                log::debug!(
+                    "Disallowed the query by line {} of the blocklist: {}",
+                    blocked.line_number,
+                    blocked.pattern
+                );
+                results.set_disallowed();
+                cache
+                    .cache_results(&[results.clone()], &[self.cache_key.clone()])
+                    .await?;
+                return Ok(results);
+            }
+        }
+
+        // check if the cookie value is empty or not if it is empty then use the
+        // default selected upstream search engines from the config file otherwise
+        // parse the non-empty cookie and grab the user selected engines from the
+        // UI and use that.
+        let mut results: SearchResults = match self.no_engines_selected {
+            false => {
+                aggregate(
+                    EngineQuery {
+                        query,
+                        page: self.page,
+                        safe_search: safe_search_level,
+                    },
+                    config,
+                    &self.engines,
+                    &self.user_preferences,
+                    filter_engine,
+                    cache,
+                )
+                .await?
+            }
+            true => {
+                let mut search_results = SearchResults::default();
+                search_results.set_no_engines_selected();
+                search_results
+            }
+        };
+        let (engine_errors_info, results_empty_check, no_engines_selected) = (
+            results.engine_errors_info().is_empty(),
+            results.results().is_empty(),
+            results.no_engines_selected(),
+        );
+        results.set_filtered(engine_errors_info & results_empty_check & !no_engines_selected);
+        cache
+            .cache_results(&[results.clone()], &[self.cache_key.clone()])
+            .await?;
+        Ok(results)
+    }
 }
 
 /// A helper function to choose the safe search level value based on the URL parameters,
@@ -0,0 +1,23 @@
+//! A module that handles the blocked sites tab for setting page view in the `websurfx` frontend.
+
+use maud::{html, Markup};
+
+/// A functions that handles the html code for the blocked sites tab for the settings page for the search page.
+///
+/// # Returns
+///
+/// It returns the compiled html markup code for the blocked sites tab.
+pub fn blocked_sites() -> Markup {
+    html!(
+        div class="blocked_sites tab"{
+           h1{"Blocked Sites"}
+           h3{"manage blocked sites"}
+           p class="description"{
+              "Results from the sites listed below are never shown. Write one site per line, for
+              example `www.example.com` or `*.example.com` to also block all of its subdomains.
+              Sites can also be blocked with the `block this site` button below each result."
+           }
+           textarea name="blocked_sites" rows="10" placeholder="*.example.com"{}
+        }
+    )
+}
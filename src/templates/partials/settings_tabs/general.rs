@@ -37,6 +37,12 @@ pub fn general(safe_search_level: u8) -> Markup {
                      option value=(SAFE_SEARCH_LEVELS[2].0){(SAFE_SEARCH_LEVELS[2].1)}
                }
            }
+           h3{"Domain rules"}
+           p class="description"{
+               "Boost, lower, pin or remove results from particular domains. Write one rule per
+               line in the form `action domain`, for example `boost docs.rs` or `remove *.example.com`."
+           }
+           textarea name="domain_rules" rows="6" placeholder="boost docs.rs"{}
         }
     )
 }
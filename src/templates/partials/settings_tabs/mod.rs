@@ -1,7 +1,9 @@
 //! This module provides other modules to handle the partials for the tabs for the settings page
 //! view in the `websurfx` frontend.
 
+pub mod blocked_sites;
 pub mod cookies;
 pub mod engines;
 pub mod general;
+pub mod privacy;
 pub mod user_interface;
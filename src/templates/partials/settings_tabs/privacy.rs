@@ -0,0 +1,45 @@
+//! A module that handles the privacy tab for setting page view in the `websurfx` frontend.
+
+use std::collections::{BTreeMap, HashMap};
+
+use maud::{html, Markup};
+
+/// A functions that handles the html code for the privacy tab for the settings page for the search page.
+///
+/// # Arguments
+///
+/// * `hostname_replacements` - It takes the hostname replacement table from the config file,
+/// mapping the original hostnames to the privacy respecting front-ends, as an argument.
+///
+/// # Returns
+///
+/// It returns the compiled html markup code for the privacy tab.
+pub fn privacy(hostname_replacements: &HashMap<String, String>) -> Markup {
+    // The replacements are sorted so that the page lists them in the same order on every render.
+    let hostname_replacements: BTreeMap<&String, &String> = hostname_replacements.iter().collect();
+
+    html!(
+        div class="privacy tab"{
+           h1{"Privacy"}
+           h3{"select privacy front-ends"}
+           p class="description"{
+              "Select the sites whose result links should be rewritten to point to privacy
+              respecting front-ends. The original link stays available below each result."
+           }
+           .hostname_replacement_selection{
+               @if hostname_replacements.is_empty() {
+                   p class="description"{"No privacy front-ends have been configured by the server administrator."}
+               }
+               @for (from, to) in &hostname_replacements {
+                   .toggle_btn{
+                       label class="switch"{
+                          input type="checkbox" class="hostname_replacement" value=(from) checked;
+                          span class="slider round"{}
+                       }
+                       (format!("{from} → {to}"))
+                   }
+               }
+           }
+        }
+    )
+}
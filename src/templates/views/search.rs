@@ -4,6 +4,7 @@ use maud::{html, Markup, PreEscaped};
 
 use crate::{
     models::aggregation_models::SearchResults,
+    results::{domain_rules::host_of, highlighter::Highlighter},
     templates::partials::{footer::footer, header::header, search_bar::search_bar},
 };
 
@@ -26,6 +27,8 @@ pub fn search(
     query: &str,
     search_results: &SearchResults,
 ) -> Markup {
+    let highlighter = Highlighter::new(query);
+
     html!(
         (header(colorscheme, theme, animation))
         main class="results"{
@@ -33,11 +36,20 @@ pub fn search(
            .results_aggregated{
               @if !search_results.results.is_empty() {
                   @for result in search_results.results.iter(){
-                      .result {
-                         h1{a href=(result.url){(PreEscaped(&result.title))}}
+                      // The results classified as unsafe are blurred until they are clicked.
+                      .result.blurred[result.blurred] onclick=[result.blurred.then_some("this.classList.remove('blurred')")] {
+                         h1{a href=(result.url){(highlighted(&highlighter, &result.title))}}
                          small{(result.url)}
-                         p{(PreEscaped(&result.description))}
+                         @if let Some(original_url) = &result.original_url {
+                            small class="original_url"{
+                               "Original: " a href=(original_url){(original_url)}
+                            }
+                         }
+                         p{(highlighted(&highlighter, &result.description))}
                          .upstream_engines{
+                            @if let Some(site) = blocked_site_pattern(result.original_url.as_deref().unwrap_or(&result.url)) {
+                               button type="button" class="block_site" data-site=(site) onclick="blockSite(this)"{"block this site"}
+                            }
                             @for name in &result.engine {
                                span{(name)}
                             }
@@ -53,10 +65,21 @@ pub fn search(
                           has been disallowed."
                        }
                        p class="description_paragraph"{"Dear user,"}
-                       p class="description_paragraph"{
-                          "The query - "{span class="user_query"{(query)}}" - has
-                          been blacklisted via server configuration and hence disallowed by the
-                          server. Henceforth no results could be displayed for your query."
+                       @if let Some(category) = &search_results.disallowed_category {
+                          p class="description_paragraph"{
+                             "The query - "{span class="user_query"{(query)}}" - has been
+                             classified under the "{span class="disallowed_category"{(category)}}"
+                             category, which has been blocked via server configuration for the
+                             current safe search level. Henceforth no results could be displayed
+                             for your query."
+                          }
+                       }
+                       @else {
+                          p class="description_paragraph"{
+                             "The query - "{span class="user_query"{(query)}}" - has
+                             been blacklisted via server configuration and hence disallowed by the
+                             server. Henceforth no results could be displayed for your query."
+                          }
                        }
                     }
                     img src="./images/barricade.png" alt="Image of a Barricade";
@@ -118,6 +141,74 @@ pub fn search(
         script src="static/search_area_options.js"{}
         script src="static/pagination.js"{}
         script src="static/error_box.js"{}
+        script src="static/blocked_sites.js"{}
         (footer())
     )
 }
+
+/// A helper function that handles the html code for a plain text title or description of a search
+/// result with the terms of the search query wrapped in `mark` tags.
+///
+/// # Arguments
+///
+/// * `highlighter` - It takes the highlighter built from the current search query as an argument.
+/// * `text` - It takes the plain text to be highlighted as an argument.
+///
+/// # Returns
+///
+/// It returns the compiled html markup code for the highlighted text.
+fn highlighted(highlighter: &Highlighter, text: &str) -> Markup {
+    html!(
+        @for (segment, highlighted) in highlighter.highlight(text) {
+            @if highlighted {
+                mark{(segment)}
+            }
+            @else {
+                (segment)
+            }
+        }
+    )
+}
+
+/// A helper function that builds the host pattern added to the blocked sites when the `block this
+/// site` button of a search result is clicked. The pattern covers the whole site, so blocking
+/// `www.example.com` blocks `example.com` along with all of its subdomains.
+///
+/// # Arguments
+///
+/// * `url` - It takes the original (not rewritten) url of the search result as an argument.
+///
+/// # Returns
+///
+/// It returns the host pattern or `None` if the url has no host.
+fn blocked_site_pattern(url: &str) -> Option<String> {
+    let host = host_of(url)?;
+    Some(format!(
+        "*.{}",
+        host.strip_prefix("www.").unwrap_or(host.as_str())
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::sanitizer::html_to_text;
+
+    #[test]
+    fn test_snippets_are_rendered_escaped() {
+        let highlighter = Highlighter::new("script");
+        let rendered =
+            |snippet: &str| highlighted(&highlighter, &html_to_text(snippet)).into_string();
+
+        assert_eq!(rendered("<b>bold</b><script>alert(1)</script>"), "bold");
+        assert_eq!(
+            rendered("&lt;script&gt;alert(1)&lt;/script&gt;"),
+            "&lt;<mark>script</mark>&gt;alert(1)&lt;/<mark>script</mark>&gt;"
+        );
+        assert_eq!(
+            rendered(r#"</title>"><img src=x onerror="alert(1)">"#),
+            "&quot;&gt;"
+        );
+        assert_eq!(rendered("Tom &amp; Jerry"), "Tom &amp; Jerry");
+    }
+}
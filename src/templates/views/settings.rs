@@ -8,7 +8,8 @@ use crate::templates::partials::{
     footer::footer,
     header::header,
     settings_tabs::{
-        cookies::cookies, engines::engines, general::general, user_interface::user_interface,
+        blocked_sites::blocked_sites, cookies::cookies, engines::engines, general::general,
+        privacy::privacy, user_interface::user_interface,
     },
 };
 
@@ -21,6 +22,7 @@ use crate::templates::partials::{
 /// * `theme` - It takes the theme name as an argument.
 /// * `animation` - It takes the animation name as an argument.
 /// * `engine_names` - It takes a list of engine names as an argument.
+/// * `hostname_replacements` - It takes the hostname replacement table as an argument.
 ///
 /// # Error
 ///
@@ -32,6 +34,7 @@ pub fn settings(
     theme: &str,
     animation: &Option<String>,
     engine_names: &HashMap<String, bool>,
+    hostname_replacements: &HashMap<String, String>,
 ) -> Result<Markup, Box<dyn std::error::Error>> {
     Ok(html!(
         (header(colorscheme, theme, animation))
@@ -43,18 +46,23 @@ pub fn settings(
                   div class="btn active" onclick="setActiveTab(this)"{"general"}
                   .btn onclick="setActiveTab(this)"{"user interface"}
                   .btn onclick="setActiveTab(this)"{"engines"}
+                  .btn onclick="setActiveTab(this)"{"blocked sites"}
+                  .btn onclick="setActiveTab(this)"{"privacy"}
                   .btn onclick="setActiveTab(this)"{"cookies"}
               }
               .main_container{
                   (general(safe_search_level))
                   (user_interface(theme, colorscheme, animation)?)
                   (engines(engine_names))
+                  (blocked_sites())
+                  (privacy(hostname_replacements))
                   (cookies())
                   p class="message"{}
                   button type="submit" onclick="setClientSettings()"{"Save"}
               }
            }
         }
+        script src="static/blocked_sites.js"{}
         script src="static/settings.js"{}
         script src="static/cookies.js"{}
         (footer())
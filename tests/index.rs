@@ -1,17 +1,21 @@
 use std::{net::TcpListener, sync::OnceLock};
 
-use websurfx::{config::parser::Config, run, templates::views};
+use websurfx::{
+    config::{parser::Config, reload::SharedConfig},
+    run,
+    templates::views,
+};
 
 /// A static constant for holding the parsed config.
-static CONFIG: OnceLock<Config> = OnceLock::new();
+static CONFIG: OnceLock<SharedConfig> = OnceLock::new();
 
 // Starts a new instance of the HTTP server, bound to a random available port
 async fn spawn_app() -> String {
     // Binding to port 0 will trigger the OS to assign a port for us.
     let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
     let port = listener.local_addr().unwrap().port();
-    let config = CONFIG.get_or_init(|| Config::parse(false).unwrap());
-    let cache = websurfx::cache::cacher::create_cache(config).await;
+    let config = CONFIG.get_or_init(|| SharedConfig::new(Config::parse(false).unwrap()).unwrap());
+    let cache = websurfx::cache::cacher::create_cache(&config.load()).await;
     let server = run(listener, config, cache).expect("Failed to bind address");
 
     tokio::spawn(server);
@@ -20,6 +20,9 @@ rate_limiter = {
 https_adaptive_window_size = false
 -- Set keep-alive timer in seconds; keeps clients connected to the HTTP server, different from the connection to upstream search engines
 client_connection_keep_alive = 120
+-- The token authenticating the requests to the admin routes and the `websurfx cache` subcommands, which are disabled
+-- when set to `nil`. It can also be provided through the `WEBSURFX_ADMIN_TOKEN` environment variable.
+admin_token = nil
 
 -- ### Search ###
 -- Filter results based on different levels. The levels provided are:
@@ -31,6 +34,34 @@ client_connection_keep_alive = 120
 -- 4 - Aggressive
 -- }}
 safe_search = 2
+-- Prefetch the neighbouring pages of search results so that they are served from the cache. The policies provided are:
+-- {{
+-- off - Only the requested page is fetched
+-- next - The next page is fetched alongside the requested page
+-- background - The previous and next pages are fetched in the background after the requested page
+-- }}
+prefetch_pages = "next"
+-- Remote filter lists in the uBlacklist format which are used as additional blocklists at every
+-- safe search level, for example:
+-- filter_subscriptions = { "https://example.com/ublacklist.txt" }
+filter_subscriptions = {}
+filter_subscription_update_interval = 24 -- the time after which the subscribed lists are downloaded again (value in hours).
+-- Disallow the search queries belonging to a category from the given safe search level. The
+-- words and phrases of each category are listed in the `categories/<category>.txt` file.
+blocked_query_categories = {
+    adult = 4,
+    gambling = 4,
+    violence = 4,
+}
+-- Rewrite the hostnames of the result urls to privacy respecting front-ends. The users can
+-- disable each of the replacements individually from the settings page.
+hostname_replacements = {
+    ["www.youtube.com"] = "yewtu.be",
+    ["youtube.com"] = "yewtu.be",
+    ["twitter.com"] = "nitter.net",
+    ["www.reddit.com"] = "old.reddit.com",
+    ["medium.com"] = "scribe.rip",
+}
 
 -- ### Website ###
 -- The different colorschemes provided are:
@@ -61,8 +92,36 @@ theme = "simple" -- the theme name which should be used for the website
 animation = "simple-frosted-glow" -- the animation name which should be used with the theme or `nil` if you don't want any animations.
 
 -- ### Caching ###
-redis_url = "redis://127.0.0.1:8082" -- redis connection url address on which the client should connect on.
+-- redis connection url address on which the client should connect on. Clusters are reached with `redis+cluster://host1:port,host2:port`,
+-- sentinels with `redis+sentinel://host1:port,host2:port/<service name>` and TLS is used with the `rediss` scheme.
+redis_url = "redis://127.0.0.1:8082"
+redis_pool_size = 5 -- the number of connections opened to redis.
+redis_key_prefix = "websurfx:" -- the prefix of the keys written to redis, which allows several instances to share one redis.
 cache_expiry_time = 600 -- This option takes the expiry time of the search results (value in seconds and the value should be greater than or equal to 60 seconds).
+-- The time after which the cached search results are refreshed in the background while still being served until they expire
+-- (value in seconds and the value should be less than `cache_expiry_time`, stale-while-revalidate is disabled when set to `nil`).
+cache_soft_expiry_time = nil
+memory_cache_max_size = 64 -- The maximum size of the in-memory cache (value in megabytes), the least recently used results being removed above it.
+cache_time_to_idle = nil -- The time after which the search results which were not looked up expire from the in-memory cache (value in seconds, disabled when set to `nil`).
+rendered_page_cache_size = nil -- The maximum size of the cache of the rendered search pages (value in megabytes, disabled when set to `nil`).
+-- The directory in which the search results are stored when the app is compiled with the `disk-cache` feature
+-- (defaults to the `websurfx` folder under `$XDG_CACHE_HOME` or `~/.cache` when set to `nil`).
+disk_cache_directory = nil
+disk_cache_max_size = 256 -- The maximum size of the disk cache (value in megabytes), the least recently used results being removed above it.
+-- The format in which the search results are serialized before being cached, `json` or `messagepack` (which needs the
+-- `msgpack-cache-results` feature and is the default when it is compiled in).
+cache_serializer = nil
+-- Whether the cached search results should be compressed or encrypted. The options default to the `compress-cache-results`,
+-- `encrypt-cache-results` and `cec-cache-results` features the app was compiled with and can only be enabled with them.
+compress_cache_results = nil
+encrypt_cache_results = nil
+-- The keys used to encrypt the cached search results when the app is compiled with the `encrypt-cache-results`
+-- or `cec-cache-results` feature, written as `<key id>:<base64 encoded 32 byte key>` (for example generated with
+-- `echo "$(date +%Y):$(openssl rand -base64 32)"`). The first key encrypts the new results while all of them are
+-- used to decrypt the cached results, which allows rotating the keys. They can also be provided through the
+-- `WEBSURFX_CACHE_ENCRYPTION_KEYS` environment variable (comma separated) or a key file (one key per line).
+cache_encryption_keys = {}
+-- cache_encryption_key_file = "/etc/websurfx/cache_keys.txt"
 -- ### Search Engines ###
 upstream_search_engines = {
     DuckDuckGo = true,